@@ -1,6 +1,12 @@
 use kiddo::{KdTree, SquaredEuclidean};
 
-use crate::models::city::City;
+use super::checkpoint::CheckpointSettings;
+use super::island_model::IslandStats;
+use super::operator_selector::OperatorStats;
+use super::progress::{Observer, TracePoint};
+use super::stopping_criteria::{StopReason, StoppingCriteria, Termination};
+use crate::models::{city::City, distance_metric::DistanceMetric};
+use std::error::Error;
 use std::time::Duration;
 
 #[derive(Clone)]
@@ -10,6 +16,14 @@ pub struct ExecuteResponse {
     distance: f64,
     total_time: Duration,
     metadata_info: String,
+    operator_stats: Vec<OperatorStats>,
+    island_stats: Vec<IslandStats>,
+    stop_reason: StopReason,
+    iterations: u64,
+    evaluations: u64,
+    trace: Vec<TracePoint>,
+    // the exact algorithms prove the final path optimal when they finish their search
+    optimal: bool,
 }
 
 impl ExecuteResponse {
@@ -26,9 +40,37 @@ impl ExecuteResponse {
             distance,
             total_time,
             metadata_info,
+            operator_stats: vec![],
+            island_stats: vec![],
+            stop_reason: StopReason::Completed,
+            iterations: 0,
+            evaluations: 0,
+            trace: vec![],
+            optimal: false,
         }
     }
 
+    pub fn set_termination(&mut self, termination: &Termination) {
+        self.stop_reason = termination.get_reason();
+        self.iterations = termination.get_iterations();
+        self.evaluations = termination.get_evaluations();
+        self.trace = termination.get_trace().clone();
+    }
+
+    // Only filled by the algorithms with selectable operators
+    pub fn set_operator_stats(&mut self, operator_stats: Vec<OperatorStats>) {
+        self.operator_stats = operator_stats;
+    }
+
+    // Only filled by the island model of the genetic algorithm
+    pub fn set_island_stats(&mut self, island_stats: Vec<IslandStats>) {
+        self.island_stats = island_stats;
+    }
+
+    pub fn set_optimal(&mut self, optimal: bool) {
+        self.optimal = optimal;
+    }
+
     pub fn get_initial_path(&self) -> &Vec<u16> {
         &self.initial_path
     }
@@ -48,11 +90,64 @@ impl ExecuteResponse {
     pub fn get_metadata_info(&self) -> &String {
         &self.metadata_info
     }
+
+    pub fn get_operator_stats(&self) -> &Vec<OperatorStats> {
+        &self.operator_stats
+    }
+
+    pub fn get_island_stats(&self) -> &Vec<IslandStats> {
+        &self.island_stats
+    }
+
+    pub fn get_stop_reason(&self) -> StopReason {
+        self.stop_reason
+    }
+
+    pub fn get_iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    pub fn get_evaluations(&self) -> u64 {
+        self.evaluations
+    }
+
+    // Improvements of the best distance along the run
+    pub fn get_trace(&self) -> &Vec<TracePoint> {
+        &self.trace
+    }
+
+    pub fn is_optimal(&self) -> bool {
+        self.optimal
+    }
 }
 
 pub trait Algorithm {
     fn execute(&mut self) -> ExecuteResponse;
 
+    // Run control shared by every algorithm: stopping criteria, progress and checkpoints
+    fn get_termination(&mut self) -> &mut Termination;
+
+    // Loads the state saved by a checkpoint of the same algorithm, execute continues from it
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>>;
+
+    // Tour to start from instead of the algorithm's own, a permutation of the cities
+    fn set_initial_tour(&mut self, tour: &[u16]);
+
+    // Common limits checked along with the algorithm's own stopping rule
+    fn set_stopping_criteria(&mut self, stopping_criteria: &StoppingCriteria) {
+        self.get_termination().set_criteria(stopping_criteria);
+    }
+
+    // Called with the progress every time the best tour improves
+    fn set_observer(&mut self, observer: Box<dyn Observer>) {
+        self.get_termination().set_observer(observer);
+    }
+
+    // Saves the state periodically and when the run ends
+    fn set_checkpoint(&mut self, checkpoint: &CheckpointSettings) {
+        self.get_termination().set_checkpoint(checkpoint);
+    }
+
     fn calculate_path_distance(path: &[u16], distance_matrix: &[f64]) -> f64 {
         let mut distance: f64 = 0.0;
         let n = path.len();
@@ -61,38 +156,41 @@ pub trait Algorithm {
                 Self::get_in_matrix(distance_matrix, n, path[i] as usize, path[i + 1] as usize);
         }
         distance += Self::get_in_matrix(distance_matrix, n, path[n - 1] as usize, path[0] as usize);
-        //Result is in degress
+        //Result is in the unit of the distance matrix (see DistanceMetric::get_unit)
         distance
     }
 
-    fn calculate_distance_between_cities(city1: &City, city2: &City) -> f64 {
-        let c1_coord = city1.get_coordinates();
-        let c2_coord = city2.get_coordinates();
-
-        let x_part: f64 = (c2_coord.get_longitude() - c1_coord.get_longitude())
-            .powi(2)
-            .into();
-        let y_part: f64 = (c2_coord.get_latitude() - c1_coord.get_latitude())
-            .powi(2)
-            .into();
-
-        (x_part + y_part).sqrt()
+    fn calculate_distance_between_cities(
+        city1: &City,
+        city2: &City,
+        metric: &DistanceMetric,
+    ) -> f64 {
+        metric.calculate_distance(city1.get_coordinates(), city2.get_coordinates())
     }
 
-    fn calculate_distance_between_cities_ids(id1: usize, id2: usize, cities: &Vec<City>) -> f64 {
-        Self::calculate_distance_between_cities(&cities[id1], &cities[id2])
+    fn calculate_distance_between_cities_ids(
+        id1: usize,
+        id2: usize,
+        cities: &[City],
+        metric: &DistanceMetric,
+    ) -> f64 {
+        Self::calculate_distance_between_cities(&cities[id1], &cities[id2], metric)
     }
 
     fn find_best_n_neighbours_kd_tree(
-        kd_tree: &KdTree<f32, 2>,
+        kd_tree: &KdTree<f32, 3>,
         city: &City,
         n: usize,
         filter: &Vec<&City>,
+        metric: &DistanceMetric,
     ) -> Vec<usize> {
-        let coordinates = city.get_coordinates().get_as_array();
+        let coordinates = metric.get_kd_tree_point(city.get_coordinates());
         let mut custom_kd_tree = kd_tree.clone();
         for c in filter {
-            custom_kd_tree.remove(&c.get_coordinates().get_as_array(), c.get_id() as u64);
+            custom_kd_tree.remove(
+                &metric.get_kd_tree_point(c.get_coordinates()),
+                c.get_id() as u64,
+            );
         }
         let n_nearest = custom_kd_tree.nearest_n::<SquaredEuclidean>(&coordinates, n);
         n_nearest.iter().map(|c| c.item as usize).collect()
@@ -114,6 +212,43 @@ pub trait Algorithm {
             .unwrap()
     }
 
+    // Best city to come before id_city, i.e. the minimum of its column.
+    // Same as find_best_neighbour when the matrix is symmetric.
+    fn find_best_predecessor(
+        distance_matrix: &[f64],
+        id_city: usize,
+        n: usize,
+        filter: &[usize],
+    ) -> usize {
+        (0..n)
+            .filter(|&i| i != id_city && !filter.contains(&i))
+            .min_by(|&a, &b| {
+                Self::get_in_matrix(distance_matrix, n, a, id_city)
+                    .partial_cmp(&Self::get_in_matrix(distance_matrix, n, b, id_city))
+                    .unwrap()
+            })
+            .unwrap()
+    }
+
+    // Tour from city 0 always going to the closest city not visited yet
+    fn create_nearest_neighbour_path(distance_matrix: &[f64], n: usize) -> Vec<u16> {
+        let mut visited = vec![false; n];
+        let mut path = Vec::with_capacity(n);
+        let mut current = 0;
+        for _ in 0..n {
+            visited[current] = true;
+            path.push(current as u16);
+            current = (0..n)
+                .filter(|&city| !visited[city])
+                .min_by(|&a, &b| {
+                    Self::get_in_matrix(distance_matrix, n, current, a)
+                        .total_cmp(&Self::get_in_matrix(distance_matrix, n, current, b))
+                })
+                .unwrap_or(0);
+        }
+        path
+    }
+
     fn find_n_best_neighbours(
         distance_matrix: &[f64],
         id_city: usize,
@@ -142,13 +277,13 @@ pub trait Algorithm {
         connections_tuple.iter().take(n).map(|(i, _)| *i).collect()
     }
 
-    fn create_kd_tree(cities: Vec<City>) -> KdTree<f32, 2> {
-        let tree_entries: Vec<([f32; 2], u16)> = cities
+    fn create_kd_tree(cities: &[City], metric: &DistanceMetric) -> KdTree<f32, 3> {
+        let tree_entries: Vec<([f32; 3], u16)> = cities
             .iter()
-            .map(|c| (c.get_coordinates().get_as_array(), c.get_id()))
+            .map(|c| (metric.get_kd_tree_point(c.get_coordinates()), c.get_id()))
             .collect();
 
-        let mut kd_tree: KdTree<f32, 2> = KdTree::new();
+        let mut kd_tree: KdTree<f32, 3> = KdTree::new();
 
         for (coord, id) in tree_entries {
             kd_tree.add(&coord, id as u64);
@@ -157,24 +292,6 @@ pub trait Algorithm {
         kd_tree
     }
 
-    fn create_distance_matrix(cities: &Vec<City>) -> Vec<f64> {
-        let n = cities.len();
-
-        let mut distance_matrix: Vec<f64> = vec![0.0; n * n];
-
-        for i in 0..n {
-            for j in 0..n {
-                if i < j {
-                    let distance = Self::calculate_distance_between_cities(&cities[i], &cities[j]);
-                    distance_matrix[Self::matrix_index(n, i, j)] = distance;
-                    distance_matrix[Self::matrix_index(n, j, i)] = distance;
-                }
-            }
-        }
-
-        distance_matrix
-    }
-
     fn get_in_matrix(matrix: &[f64], size: usize, row: usize, column: usize) -> f64 {
         matrix[Self::matrix_index(size, row, column)]
     }
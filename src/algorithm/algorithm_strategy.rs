@@ -1,20 +1,116 @@
+use std::error::Error;
+
 use super::algorithm::{Algorithm, ExecuteResponse};
 use super::ant_colony_optimization::AntColonyOptimization;
 use super::branch_and_bound::BranchAndBound;
+use super::checkpoint::CheckpointSettings;
 use super::genetic::Genetic;
+use super::held_karp::HeldKarp;
+use super::island_model::IslandModel;
+use super::parameters::AlgorithmParameters;
+use super::progress::Observer;
 use super::simulated_annealing::SimulatedAnnealing;
-use crate::models::city::City;
+use super::stopping_criteria::StoppingCriteria;
+use super::tour_improvement::TourImprovement;
+use crate::models::{city::City, distance_metric::DistanceMetric};
+
+// Codes accepted by execute_algorithm
+pub const ALGORITHMS: [&str; 6] = ["ACO", "BB", "G", "HK", "LS", "SA"];
+
+// How a run is driven, on top of the algorithm parameters
+#[derive(Default)]
+pub struct RunOptions {
+    // drives every random choice, the same seed replays the same run
+    pub seed: u64,
+    pub stopping_criteria: StoppingCriteria,
+    pub observer: Option<Box<dyn Observer>>,
+    pub checkpoint: Option<CheckpointSettings>,
+    // checkpoint file to continue from
+    pub resume: Option<String>,
+    // tour to start from, in positions of the cities
+    pub initial_tour: Option<Vec<u16>>,
+}
 
 pub struct AlgorithmStrategy;
 
 impl AlgorithmStrategy {
-    pub fn execute_algorithm(algorithm: &str, cities: &Vec<City>) -> ExecuteResponse {
+    pub fn execute_algorithm(
+        algorithm: &str,
+        cities: &[City],
+        distance_matrix: &[f64],
+        symmetric: bool,
+        metric: &DistanceMetric,
+        parameters: &AlgorithmParameters,
+        options: RunOptions,
+    ) -> Result<ExecuteResponse, Box<dyn Error>> {
+        let seed = options.seed;
         match algorithm.to_uppercase().as_str() {
-            "ACO" => AntColonyOptimization::new(cities).execute(),
-            "BB" => BranchAndBound::new(cities).execute(),
-            "G" => Genetic::new(cities).execute(),
-            "SA" => SimulatedAnnealing::new(cities).execute(),
-            _ => panic!("Unknown algorithm"),
+            "ACO" => Self::run(
+                AntColonyOptimization::new(
+                    cities,
+                    distance_matrix,
+                    symmetric,
+                    &parameters.ant_colony_optimization,
+                    seed,
+                ),
+                options,
+            ),
+            "BB" => Self::run(
+                BranchAndBound::new(cities, distance_matrix, &parameters.branch_and_bound),
+                options,
+            ),
+            "G" if parameters.genetic.islands > 1 => Self::run(
+                IslandModel::new(cities, distance_matrix, metric, &parameters.genetic, seed),
+                options,
+            ),
+            "G" => Self::run(
+                Genetic::new(cities, distance_matrix, metric, &parameters.genetic, seed),
+                options,
+            ),
+            "HK" => Self::run(
+                HeldKarp::new(cities, distance_matrix, &parameters.held_karp),
+                options,
+            ),
+            "LS" => Self::run(
+                TourImprovement::new(cities, distance_matrix, &parameters.local_search),
+                options,
+            ),
+            "SA" => Self::run(
+                SimulatedAnnealing::new(
+                    cities,
+                    distance_matrix,
+                    metric,
+                    &parameters.simulated_annealing,
+                    seed,
+                ),
+                options,
+            ),
+            _ => Err(format!(
+                "unknown algorithm '{}' ({})",
+                algorithm,
+                ALGORITHMS.join(", ")
+            )
+            .into()),
+        }
+    }
+
+    fn run<A: Algorithm>(
+        mut algorithm: A,
+        options: RunOptions,
+    ) -> Result<ExecuteResponse, Box<dyn Error>> {
+        algorithm.set_stopping_criteria(&options.stopping_criteria);
+        if let Some(observer) = options.observer {
+            algorithm.set_observer(observer);
+        }
+        if let Some(checkpoint) = &options.checkpoint {
+            algorithm.set_checkpoint(checkpoint);
+        }
+        if let Some(tour) = &options.initial_tour {
+            algorithm.set_initial_tour(tour);
+        }
+        if let Some(path) = &options.resume {
+            algorithm.resume(path)?;
         }
+        Ok(algorithm.execute())
     }
 }
@@ -1,13 +1,36 @@
-use rand::{Rng, rng, rngs::ThreadRng};
-use std::cmp;
-use std::{process::exit, time::Instant};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::{error::Error, process::exit};
 
 use super::algorithm::{Algorithm, ExecuteResponse};
+use super::checkpoint::Checkpoint;
+use super::local_search::{DEFAULT_NEIGHBOURS, LocalSearch};
+use super::parameters::{AntColonyOptimizationParameters, describe_parameters};
+use super::stopping_criteria::Termination;
 use crate::models::city::City;
 use std::collections::HashSet;
 
+// Everything a run needs to continue from a checkpoint, the pheromone matrix is n x n
+#[derive(Serialize, Deserialize)]
+struct ColonyState {
+    pheromone_matrix: Vec<f64>,
+    best_path: Vec<u16>,
+    best_cost: f64,
+    iteration: usize,
+    iterations_without_improvement: usize,
+    s: usize,
+    vaporation_rate: f64,
+    alpha: f64,
+    beta: f64,
+    // the random sequence goes on where it stopped
+    rng: ChaCha12Rng,
+}
+
 pub struct AntColonyOptimization {
     cities: Vec<City>,
+    distance_matrix: Vec<f64>,
+    symmetric: bool,      // false when d(i, j) may differ from d(j, i) (ATSP)
     alpha: f64,           // importance of pheromone
     beta: f64,            // importance of heuristic
     vaporation_rate: f64, // rate of pheromone evaporation
@@ -17,46 +40,62 @@ pub struct AntColonyOptimization {
     stall_limit: usize,   // max number of iterations before stopping
     s: usize,             // number of iterations without improvement
     s_threshold: usize,   // nº of consecutive iterations without reducing pheromone vaporation rate
-    rng: ThreadRng,       // random number generator
+    rng: ChaCha12Rng,     // random number generator, seeded for reproducible runs
     num_ants: usize,      // number of ants to simulate
+    q: f64,               // pheromone deposited by the best ranked ant
     best_path: Vec<u16>,  // best path found
     best_cost: f64,       // cost of the best path found
     candidate_lists: Vec<Vec<usize>>,
+    local_search: LocalSearch, // improves the ranked paths before they deposit pheromone
+    parameters: AntColonyOptimizationParameters,
+    termination: Termination,
+    initial_tour: Option<Vec<u16>>, // first best path, it deposits pheromone as the best ranked ant
+    resume_state: Option<ColonyState>,
 }
 
 impl AntColonyOptimization {
-    pub fn new(cities: &Vec<City>) -> Self {
-        let min_ants = 50;
-        let max_ants;
-        if cities.len() >= 400 {
-            max_ants = 50;
-        } else if cities.len() >= 200 {
-            max_ants = 50;
-        } else {
-            max_ants = 50;
-        }
-        let ants = cmp::max(min_ants, cmp::min(cities.len(), max_ants));
+    pub fn new(
+        cities: &[City],
+        distance_matrix: &[f64],
+        symmetric: bool,
+        parameters: &AntColonyOptimizationParameters,
+        seed: u64,
+    ) -> Self {
+        let ants = parameters.num_ants;
         print!(
             "AntColonyOptimization::new() called with {} cities, using {} ants",
             cities.len(),
             ants
         );
         AntColonyOptimization {
-            cities: cities.clone(),
-            alpha: 1.0,
-            beta: 1.0,
+            cities: cities.to_vec(),
+            distance_matrix: distance_matrix.to_vec(),
+            symmetric,
+            alpha: parameters.alpha,
+            beta: parameters.beta,
             vaporation_rate: 0.75,
-            rho0: 0.3,
-            gamma: 0.8,
-            omega: 0.7,
-            stall_limit: 200,
-            s_threshold: 30,
+            rho0: parameters.rho0,
+            gamma: parameters.gamma,
+            omega: parameters.omega,
+            stall_limit: parameters.stall_limit,
+            s_threshold: parameters.s_threshold,
             s: 0,
-            rng: rng(),
+            rng: ChaCha12Rng::seed_from_u64(seed),
             num_ants: ants,
+            q: parameters.q,
             best_path: vec![],
             best_cost: f64::MAX,
-            candidate_lists: Self::build_candidate_lists(cities),
+            candidate_lists: Self::build_candidate_lists(distance_matrix, cities.len()),
+            local_search: LocalSearch::new(
+                distance_matrix,
+                cities.len(),
+                parameters.local_search,
+                DEFAULT_NEIGHBOURS,
+            ),
+            parameters: parameters.clone(),
+            termination: Termination::default(),
+            initial_tour: None,
+            resume_state: None,
         }
     }
     pub fn update_rho(&mut self, iteration: usize) {
@@ -73,9 +112,9 @@ impl AntColonyOptimization {
     // Atualização de feromônio com ASrank (formigas ordenadas por qualidade de solução)
     pub fn update_pheromone(
         &mut self,
-        pheromone_matrix: &mut Vec<f64>,
-        distance_matrix: &Vec<f64>,
-        paths: &Vec<(Vec<u16>, f64)>,
+        pheromone_matrix: &mut [f64],
+        distance_matrix: &[f64],
+        paths: &[(Vec<u16>, f64)],
         q: f64,
     ) {
         let n = self.cities.len();
@@ -84,14 +123,14 @@ impl AntColonyOptimization {
             *tau *= 1.0 - self.vaporation_rate;
         }
 
-        let mut ranked_paths = paths.clone();
+        let mut ranked_paths = paths.to_vec();
         ranked_paths.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
 
         // Top lambda*m ants updates pheromone (ASrank)
         let lambda = (self.num_ants as f64 * 0.2).ceil() as usize;
 
         // if self.s % 10 == 0 {
-        self.optimize_best_paths(&mut ranked_paths, distance_matrix, lambda, n);
+        self.optimize_best_paths(&mut ranked_paths, distance_matrix, lambda);
         // }
 
         for (rank, (path, cost)) in ranked_paths.iter().take(lambda).enumerate() {
@@ -101,7 +140,9 @@ impl AntColonyOptimization {
                 let j = w[1] as usize;
                 let delta = weight * q / cost;
                 pheromone_matrix[i * n + j] += delta;
-                pheromone_matrix[j * n + i] += delta;
+                if self.symmetric {
+                    pheromone_matrix[j * n + i] += delta;
+                }
             }
         }
     }
@@ -120,6 +161,21 @@ impl AntColonyOptimization {
         pheromone_matrix
     }
 
+    // Same deposit as the best ranked ant of an iteration
+    fn deposit_initial_tour(&self, pheromone_matrix: &mut [f64], tour: &[u16]) {
+        let n = self.cities.len();
+        let lambda = (self.num_ants as f64 * 0.2).ceil();
+        let delta = lambda * self.q / self.best_cost;
+        for w in tour.windows(2) {
+            let i = w[0] as usize;
+            let j = w[1] as usize;
+            pheromone_matrix[i * n + j] += delta;
+            if self.symmetric {
+                pheromone_matrix[j * n + i] += delta;
+            }
+        }
+    }
+
     pub fn use_candidate_lists(&self, current: usize) -> &Vec<usize> {
         &self.candidate_lists[current]
     }
@@ -132,8 +188,8 @@ impl AntColonyOptimization {
         &mut self,
         current: usize,
         visited: &HashSet<u16>,
-        pheromone_matrix: &Vec<f64>,
-        distance_matrix: &Vec<f64>,
+        pheromone_matrix: &[f64],
+        distance_matrix: &[f64],
     ) -> usize {
         let n = self.cities.len();
         let mut probabilities = Vec::new();
@@ -183,7 +239,7 @@ impl AntColonyOptimization {
         probabilities.last().unwrap().0
     }
 
-    fn compute_entropy(&self, pheromone_matrix: &Vec<f64>) -> f64 {
+    fn compute_entropy(&self, pheromone_matrix: &[f64]) -> f64 {
         let total: f64 = pheromone_matrix.iter().sum();
         pheromone_matrix
             .iter()
@@ -195,7 +251,7 @@ impl AntColonyOptimization {
             .sum()
     }
 
-    fn update_beta_by_entropy(&mut self, pheromone_matrix: &Vec<f64>) {
+    fn update_beta_by_entropy(&mut self, pheromone_matrix: &[f64]) {
         let entropy = self.compute_entropy(pheromone_matrix);
         let max_entropy = (self.cities.len() * (self.cities.len() - 1) / 2) as f64;
         let e_prime = 1.0 - (entropy / max_entropy);
@@ -215,15 +271,15 @@ impl AntColonyOptimization {
         &mut self,
         path: &mut Vec<u16>,
         visited: &mut HashSet<u16>,
-        distance_matrix: &Vec<f64>,
-        pheromone_matrix: &Vec<f64>,
+        distance_matrix: &[f64],
+        pheromone_matrix: &[f64],
     ) {
         let n = self.cities.len();
         let start_city = self.rng.random_range(0..n - 1) as u16;
         path.push(start_city);
         visited.insert(start_city);
         while path.len() < n {
-            let current_city = path.last().unwrap().clone();
+            let current_city = *path.last().unwrap();
             let next_city = self.chose_next_city(
                 current_city as usize,
                 visited,
@@ -255,79 +311,90 @@ impl AntColonyOptimization {
         self.beta = beta_amplitude * (r2 * t * std::f64::consts::PI / (2.0 * t_max)).sin() + b;
     }
 
-    fn two_opt(&mut self, path: &mut Vec<u16>, distance_matrix: &Vec<f64>, n: usize) -> f64 {
-        let mut improved = true;
-        let mut total_cost = Self::calculate_path_distance(&path, &distance_matrix);
-
-        while improved {
-            improved = false;
-            for i in 1..path.len() - 2 {
-                for j in i + 1..path.len() - 1 {
-                    let a = path[i - 1] as usize;
-                    let b = path[i] as usize;
-                    let c = path[j] as usize;
-                    let d = path[j + 1] as usize;
-
-                    let current = distance_matrix[a * n + b] + distance_matrix[c * n + d];
-                    let new = distance_matrix[a * n + c] + distance_matrix[b * n + d];
-
-                    if new < current {
-                        path[i..=j].reverse();
-                        total_cost = total_cost - current + new;
-                        improved = true;
-                    }
-                }
-            }
-        }
-
-        total_cost
-    }
-
     pub fn optimize_best_paths(
         &mut self,
-        paths: &mut Vec<(Vec<u16>, f64)>,
-        distance_matrix: &Vec<f64>,
+        paths: &mut [(Vec<u16>, f64)],
+        distance_matrix: &[f64],
         top_k: usize,
-        n: usize,
     ) {
         paths.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
 
-        for i in 0..top_k.min(paths.len()) {
-            let (ref mut path, ref mut cost) = paths[i];
-            *cost = self.two_opt(path, distance_matrix, n);
+        for (path, cost) in paths.iter_mut().take(top_k) {
+            *cost = self
+                .local_search
+                .improve(distance_matrix, path, &mut self.termination);
+            // the improved tour is found by the run too, not only a pheromone trail
+            if *cost < self.best_cost {
+                self.best_cost = *cost;
+                self.best_path = path.clone();
+                self.termination.report_best(*cost, path);
+            }
         }
     }
 
-    fn build_candidate_lists(cities: &Vec<City>) -> Vec<Vec<usize>> {
-        let n: usize = cities.len();
-        let mut lists = vec![vec![]; n];
-
-        for i in 0..n {
-            let mut distances: Vec<(usize, f64)> = (0..n)
-                .filter(|&j| i != j)
-                .map(|j| {
-                    (
-                        j,
-                        Self::calculate_distance_between_cities(&cities[i], &cities[j]),
-                    )
-                })
-                .collect();
+    fn build_candidate_lists(distance_matrix: &[f64], n: usize) -> Vec<Vec<usize>> {
+        let list_size = (n as f64 / 4.0).ceil() as usize;
 
-            distances.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
-            let list_size = (n as f64 / 4.0).ceil() as usize;
-            lists[i] = distances.iter().take(list_size).map(|&(j, _)| j).collect();
-        }
+        (0..n)
+            .map(|i| Self::find_n_best_neighbours(distance_matrix, i, n, list_size))
+            .collect()
+    }
 
-        lists
+    fn write_checkpoint(
+        &mut self,
+        pheromone_matrix: &[f64],
+        iteration: usize,
+        iterations_without_improvement: usize,
+    ) {
+        let state = ColonyState {
+            pheromone_matrix: pheromone_matrix.to_vec(),
+            best_path: self.best_path.clone(),
+            best_cost: self.best_cost,
+            iteration,
+            iterations_without_improvement,
+            s: self.s,
+            vaporation_rate: self.vaporation_rate,
+            alpha: self.alpha,
+            beta: self.beta,
+            rng: self.rng.clone(),
+        };
+        self.termination
+            .write_checkpoint("ACO", self.cities.len(), state);
     }
 
     pub fn start(&mut self) -> (Vec<u16>, f64) {
         let size = self.cities.len();
-        let distance_matrix = Self::create_distance_matrix(&self.cities);
-        let mut pheromone_matrix = self.create_pheromone_matrix(size);
-
-        let mut iterations_without_improvement = 0;
-        let mut iteration = 0;
+        let distance_matrix = std::mem::take(&mut self.distance_matrix);
+
+        let (mut pheromone_matrix, mut iterations_without_improvement, mut iteration) =
+            match self.resume_state.take() {
+                Some(state) => {
+                    self.best_path = state.best_path;
+                    self.best_cost = state.best_cost;
+                    self.s = state.s;
+                    self.vaporation_rate = state.vaporation_rate;
+                    self.alpha = state.alpha;
+                    self.beta = state.beta;
+                    self.rng = state.rng;
+                    self.termination
+                        .report_best(self.best_cost, &self.best_path);
+                    (
+                        state.pheromone_matrix,
+                        state.iterations_without_improvement,
+                        state.iteration,
+                    )
+                }
+                None => {
+                    let mut pheromone_matrix = self.create_pheromone_matrix(size);
+                    if let Some(tour) = self.initial_tour.take() {
+                        self.best_cost = Self::calculate_path_distance(&tour, &distance_matrix);
+                        self.termination.report_best(self.best_cost, &tour);
+                        self.deposit_initial_tour(&mut pheromone_matrix, &tour);
+                        self.best_path = tour;
+                    }
+                    (pheromone_matrix, 0, 0)
+                }
+            };
         loop {
             let mut paths: Vec<(Vec<u16>, f64)> = vec![];
             let mut improved = false;
@@ -343,8 +410,13 @@ impl AntColonyOptimization {
                 if cost < self.best_cost {
                     self.best_cost = cost;
                     self.best_path = path.clone();
+                    self.termination.report_best(cost, &path);
                     improved = true;
                 }
+                self.termination.add_evaluations(1);
+                if self.termination.should_stop(self.best_cost) {
+                    break;
+                }
             }
 
             // self.update_alpha_beta(iteration, self.stall_limit); // AACO-LST
@@ -358,6 +430,10 @@ impl AntColonyOptimization {
                 self.s += 1;
             }
 
+            if self.termination.next_iteration(self.best_cost) {
+                break;
+            }
+
             if iterations_without_improvement >= self.stall_limit {
                 // println!(
                 //     "Converged after {} iterations without improvement (best cost: {:.4})",
@@ -367,19 +443,26 @@ impl AntColonyOptimization {
             }
 
             self.update_rho(iteration);
-            self.update_pheromone(&mut pheromone_matrix, &distance_matrix, &mut paths, 10.0);
+            self.update_pheromone(&mut pheromone_matrix, &distance_matrix, &paths, self.q);
 
             if iteration % 5 == 0 {
                 println!("Iteration {}: Best Cost = {:.4}", iteration, self.best_cost);
             }
             iteration += 1;
+
+            if self.termination.checkpoint_due() {
+                self.write_checkpoint(&pheromone_matrix, iteration, iterations_without_improvement);
+            }
         }
+        self.write_checkpoint(&pheromone_matrix, iteration, iterations_without_improvement);
 
         // println!(
         //     "Final best path: {:?} | cost = {:.4}",
         //     self.best_path, self.best_cost
         // );
 
+        self.distance_matrix = distance_matrix;
+
         (self.best_path.clone(), self.best_cost)
     }
 }
@@ -387,17 +470,35 @@ impl AntColonyOptimization {
 impl Algorithm for AntColonyOptimization {
     fn execute(&mut self) -> ExecuteResponse {
         println!("Execute AntColonyOptimization");
-        let start_time = Instant::now();
+        self.termination.start();
         let result = self.start();
         let best_path = result.0;
         let best_cost = result.1;
 
-        ExecuteResponse::new(
+        let mut response = ExecuteResponse::new(
             vec![],
             best_path,
             best_cost,
-            start_time.elapsed(),
-            String::new(),
-        )
+            self.termination.get_elapsed(),
+            format!("Parameters: {}", describe_parameters(&self.parameters)),
+        );
+        response.set_termination(&self.termination);
+        response
+    }
+
+    fn get_termination(&mut self) -> &mut Termination {
+        &mut self.termination
+    }
+
+    fn set_initial_tour(&mut self, tour: &[u16]) {
+        self.initial_tour = Some(tour.to_vec());
+    }
+
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let checkpoint: Checkpoint<ColonyState> =
+            Checkpoint::read_from_file(path, "ACO", self.cities.len())?;
+        self.termination.restore(&checkpoint);
+        self.resume_state = Some(checkpoint.into_state());
+        Ok(())
     }
 }
@@ -1,113 +1,356 @@
-use std::time::Instant;
+use std::{cmp::Ordering, collections::BinaryHeap, error::Error};
+
+use serde::{Deserialize, Serialize};
 
 use super::algorithm::{Algorithm, ExecuteResponse};
+use super::checkpoint::Checkpoint;
+use super::local_search::{DEFAULT_NEIGHBOURS, LocalSearch, LocalSearchMethod};
+use super::lower_bound::{held_karp_penalties, spanning_tree_cost};
+use super::parameters::{BranchAndBoundParameters, describe_parameters};
+use super::stopping_criteria::{StopReason, Termination};
 use crate::models::city::City;
 
+// A bound this close to the best cost cannot lead to a shorter tour
+const TOLERANCE: f64 = 1e-9;
+
+// Tour prefix starting at city 0, with its cost and a bound on every tour that extends it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SearchNode {
+    path: Vec<u16>,
+    cost: f64,
+    bound: f64,
+}
+
+// BinaryHeap is a max-heap: the lowest bound comes first, then the deepest node
+impl Ord for SearchNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .bound
+            .total_cmp(&self.bound)
+            .then(self.path.len().cmp(&other.path.len()))
+    }
+}
+
+impl PartialOrd for SearchNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SearchNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for SearchNode {}
+
+// Everything a run needs to continue from a checkpoint
+#[derive(Serialize, Deserialize)]
+struct SearchState {
+    best_cost: f64,
+    best_path: Vec<u16>,
+    initial_path: Vec<u16>,
+    penalties: Vec<f64>,
+    calls_without_improvement: usize,
+    open_nodes: Vec<SearchNode>,
+    optimal: bool,
+}
+
+// Exact search: the incumbent starts from a nearest neighbour tour improved by local search,
+// the nodes are bounded by a penalized spanning tree of the cities left, the open node with
+// the lowest bound is dived depth-first through its nearest cities
 pub struct BranchAndBound {
     cities: Vec<City>,
     size: usize,
     distance_matrix: Vec<f64>,
     best_cost: f64,
     best_path: Vec<u16>,
-    call_count: usize,
+    initial_path: Vec<u16>,
+    // incumbent to start from in place of the nearest neighbour tour
+    initial_tour: Option<Vec<u16>>,
+    // Held-Karp penalties of the cities, they tighten the bound of every node
+    penalties: Vec<f64>,
+    // nodes expanded since the last new best, the search gives up at max_call_count like the
+    // original recursive search did
+    calls_without_improvement: usize,
     max_call_count: usize,
+    parameters: BranchAndBoundParameters,
+    termination: Termination,
+    // open nodes, best-first
+    queue: BinaryHeap<SearchNode>,
+    // open nodes searched depth-first once the queue is full
+    stack: Vec<SearchNode>,
+    optimal: bool,
+    resumed: bool,
 }
 
 impl BranchAndBound {
-    pub fn new(cities: &Vec<City>) -> Self {
-        let bb = BranchAndBound {
-            cities: cities.clone(),
+    pub fn new(
+        cities: &[City],
+        distance_matrix: &[f64],
+        parameters: &BranchAndBoundParameters,
+    ) -> Self {
+        BranchAndBound {
+            cities: cities.to_vec(),
             size: cities.len(),
-            distance_matrix: Self::create_distance_matrix(cities),
+            distance_matrix: distance_matrix.to_vec(),
             best_cost: f64::MAX,
             best_path: vec![0; cities.len() + 1],
-            call_count: 0,
-            max_call_count: 500_000_000,
-        };
+            initial_path: vec![],
+            initial_tour: None,
+            penalties: vec![0.0; cities.len()],
+            calls_without_improvement: 0,
+            max_call_count: parameters.max_call_count,
+            parameters: parameters.clone(),
+            termination: Termination::default(),
+            queue: BinaryHeap::new(),
+            stack: vec![],
+            optimal: false,
+            resumed: false,
+        }
+    }
 
-        bb
+    fn get_distance(&self, from: usize, to: usize) -> f64 {
+        Self::get_in_matrix(&self.distance_matrix, self.size, from, to)
     }
 
-    fn uptade_best_path(&mut self, path: &mut Vec<u16>) {
-        for i in 0..self.size {
-            self.best_path[i] = path[i];
-        }
-        self.best_path[self.size] = path[0];
+    // Shortest way from `last` through every city left back to city 0: an edge out of
+    // `last`, a spanning tree of the cities left and an edge into city 0, with the penalties
+    fn completion_bound(&self, last: usize, remaining: &[usize]) -> f64 {
+        let penalties = &self.penalties;
+        let tree = spanning_tree_cost(&self.distance_matrix, self.size, remaining, penalties);
+        let out_of_last = remaining
+            .iter()
+            .map(|&city| self.get_distance(last, city) + penalties[last] + penalties[city])
+            .fold(f64::MAX, f64::min);
+        let into_first = remaining
+            .iter()
+            .map(|&city| self.get_distance(city, 0) + penalties[city] + penalties[0])
+            .fold(f64::MAX, f64::min);
+        // the cities left have degree 2 in the completion, `last` and city 0 degree 1
+        let penalty_sum = 2.0 * remaining.iter().map(|&city| penalties[city]).sum::<f64>()
+            + penalties[last]
+            + penalties[0];
+        tree + out_of_last + into_first - penalty_sum
+    }
 
-        self.call_count = 0;
+    fn update_best_path(&mut self, path: &[u16], cost: f64) {
+        self.best_cost = cost;
+        self.best_path[..self.size].copy_from_slice(path);
+        self.best_path[self.size] = path[0];
+        self.termination
+            .report_best(self.best_cost, &self.best_path[..self.size]);
+        self.calls_without_improvement = 0;
     }
 
-    pub fn test(&mut self, path: &mut Vec<u16>, visited: &mut Vec<bool>, level: usize, cost: f64) {
-        if self.call_count >= self.max_call_count {
+    fn create_root(&mut self) {
+        let mut tour = match self.initial_tour.take() {
+            Some(tour) => tour,
+            None => Self::create_nearest_neighbour_path(&self.distance_matrix, self.size),
+        };
+        let local_search = LocalSearch::new(
+            &self.distance_matrix,
+            self.size,
+            LocalSearchMethod::LinKernighan,
+            DEFAULT_NEIGHBOURS,
+        );
+        let cost = local_search.improve(&self.distance_matrix, &mut tour, &mut self.termination);
+        self.termination.add_evaluations(1);
+        self.initial_path = tour.clone();
+        self.update_best_path(&tour, cost);
+        if self.size < 3 {
             return;
         }
-        self.call_count += 1;
-        if self.call_count % 10_000_000 == 0 && self.call_count > 0 {
-            println!("Call count: {}", self.call_count);
+        let (_, penalties) = held_karp_penalties(
+            &self.distance_matrix,
+            self.size,
+            cost,
+            self.parameters.held_karp_iterations,
+        );
+        self.penalties = penalties;
+        let remaining: Vec<usize> = (1..self.size).collect();
+        let bound = self.completion_bound(0, &remaining);
+        self.push_open(SearchNode {
+            path: vec![0],
+            cost: 0.0,
+            bound,
+        });
+    }
+
+    fn push_open(&mut self, node: SearchNode) {
+        if self.queue.len() < self.parameters.max_open_nodes {
+            self.queue.push(node);
+        } else {
+            self.stack.push(node);
         }
+    }
 
-        let curr_path = path.clone();
+    // The depth-first nodes come first, the queue only grows again once they are done
+    fn next_open(&mut self) -> Option<SearchNode> {
+        self.stack.pop().or_else(|| self.queue.pop())
+    }
 
-        if level == self.size {
-            let total_cost = cost
-                + self.distance_matrix
-                    [Self::matrix_index(self.size, path[0].into(), path[level - 1].into())];
-            // [Self::matrix_index(self.size, path[level - 1].into(), path[0].into())];
-            if total_cost < self.best_cost {
-                self.best_cost = total_cost;
-                self.uptade_best_path(path);
-            }
+    // Children that may lead to a shorter tour, nearest city first. The complete tours
+    // are evaluated on the spot.
+    fn expand(&mut self, node: &SearchNode) -> Vec<SearchNode> {
+        let last = *node.path.last().unwrap() as usize;
+        let mut visited = vec![false; self.size];
+        for &city in &node.path {
+            visited[city as usize] = true;
         }
+        let mut remaining: Vec<usize> = (0..self.size).filter(|&city| !visited[city]).collect();
+        remaining.sort_by(|&a, &b| {
+            self.get_distance(last, a)
+                .total_cmp(&self.get_distance(last, b))
+        });
 
-        for i in 1..self.size {
-            if visited[i] {
+        let mut children = vec![];
+        for &city in &remaining {
+            let cost = node.cost + self.get_distance(last, city);
+            let mut path = node.path.clone();
+            path.push(city as u16);
+            self.termination.add_evaluations(1);
+            if remaining.len() == 1 {
+                let total_cost = cost + self.get_distance(city, 0);
+                if total_cost < self.best_cost - TOLERANCE {
+                    self.update_best_path(&path, total_cost);
+                }
                 continue;
-            };
+            }
+            let rest: Vec<usize> = remaining
+                .iter()
+                .copied()
+                .filter(|&other| other != city)
+                .collect();
+            let bound = cost + self.completion_bound(city, &rest);
+            if bound < self.best_cost - TOLERANCE {
+                children.push(SearchNode { path, cost, bound });
+            }
+        }
+        children
+    }
 
-            let new_cost = cost
-                + self.distance_matrix[Self::matrix_index(self.size, i, path[level - 1].into())];
+    // Pops the open node with the lowest bound and dives from it through the nearest
+    // child, the other children stay open. Proves the best path optimal when no node is left.
+    fn search(&mut self) {
+        while let Some(node) = self.next_open() {
+            if node.bound >= self.best_cost - TOLERANCE {
+                continue;
+            }
+            let mut current = node;
+            loop {
+                if self.calls_without_improvement >= self.max_call_count {
+                    self.termination.stop(StopReason::CallLimit);
+                    self.push_open(current);
+                    return;
+                }
+                if self.termination.next_iteration(self.best_cost) {
+                    self.push_open(current);
+                    return;
+                }
+                self.calls_without_improvement += 1;
+                if self.termination.checkpoint_due() {
+                    self.write_checkpoint(Some(&current));
+                }
 
-            if new_cost < self.best_cost {
-                visited[i] = true;
-                path[level] = i as u16;
-                self.test(path, visited, level + 1, new_cost);
-                visited[i] = false;
+                let mut children = self.expand(&current).into_iter();
+                let Some(next) = children.next() else {
+                    break;
+                };
+                for child in children {
+                    self.push_open(child);
+                }
+                current = next;
             }
         }
+        self.optimal = true;
+    }
 
-        // println!("Distance from {} to {}: {}", i, j, distance);
+    // Lowest bound of the open nodes: no tour is shorter
+    fn get_search_bound(&self) -> f64 {
+        self.queue
+            .iter()
+            .chain(&self.stack)
+            .map(|node| node.bound)
+            .fold(self.best_cost, f64::min)
+    }
 
-        // ExecuteResponse::new(vec![], vec![], 0.0, Instant::now().elapsed(), String::new())
+    fn write_checkpoint(&mut self, current: Option<&SearchNode>) {
+        let state = SearchState {
+            best_cost: self.best_cost,
+            best_path: self.best_path.clone(),
+            initial_path: self.initial_path.clone(),
+            penalties: self.penalties.clone(),
+            calls_without_improvement: self.calls_without_improvement,
+            open_nodes: self
+                .queue
+                .iter()
+                .chain(&self.stack)
+                .chain(current)
+                .cloned()
+                .collect(),
+            optimal: self.optimal,
+        };
+        self.termination.write_checkpoint("BB", self.size, state);
     }
 }
 
 impl Algorithm for BranchAndBound {
     fn execute(&mut self) -> ExecuteResponse {
-        // TBD
         println!("Execute BranchAndBound");
         println!("Size: {}", self.size);
 
-        let mut visited: Vec<bool> = vec![false; self.size];
-        let mut path: Vec<u16> = vec![0; self.size + 1];
-
-        visited[0] = true;
-        let start_time = Instant::now();
+        self.termination.start();
 
-        self.test(&mut path, &mut visited, 1, 0.0);
-
-        let end = Instant::now();
-
-        for i in 0..self.best_path.len() {
-            print!("{} ", self.best_path[i])
+        if !self.resumed {
+            self.create_root();
         }
-        println!("  | {}", self.best_cost);
+        self.search();
+        self.write_checkpoint(None);
 
-        ExecuteResponse::new(
-            vec![],
+        let mut response = ExecuteResponse::new(
+            self.initial_path.clone(),
             self.best_path.clone(),
             self.best_cost,
-            start_time.elapsed(),
-            format!("total calls: {}", self.call_count),
-        )
+            self.termination.get_elapsed(),
+            format!(
+                "Optimal: {}\nSearch Bound: {}\nOpen Nodes: {}\nCalls Without Improvement: {}\nParameters: {}",
+                self.optimal,
+                self.get_search_bound(),
+                self.queue.len() + self.stack.len(),
+                self.calls_without_improvement,
+                describe_parameters(&self.parameters)
+            ),
+        );
+        response.set_optimal(self.optimal);
+        response.set_termination(&self.termination);
+        response
+    }
+
+    fn get_termination(&mut self) -> &mut Termination {
+        &mut self.termination
+    }
+
+    fn set_initial_tour(&mut self, tour: &[u16]) {
+        self.initial_tour = Some(tour.to_vec());
+    }
+
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let checkpoint: Checkpoint<SearchState> =
+            Checkpoint::read_from_file(path, "BB", self.size)?;
+        self.termination.restore(&checkpoint);
+        let state = checkpoint.into_state();
+        self.best_cost = state.best_cost;
+        self.best_path = state.best_path;
+        self.initial_path = state.initial_path;
+        self.penalties = state.penalties;
+        self.calls_without_improvement = state.calls_without_improvement;
+        self.optimal = state.optimal;
+        for node in state.open_nodes {
+            self.push_open(node);
+        }
+        self.resumed = true;
+        Ok(())
     }
 }
@@ -0,0 +1,178 @@
+use std::{
+    error::Error,
+    fs::{File, read_to_string, rename},
+    io::{BufWriter, Write},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use super::progress::TracePoint;
+
+// Where and how often the running algorithm saves its state
+#[derive(Debug, Clone)]
+pub struct CheckpointSettings {
+    pub path: String,
+    pub interval: Duration,
+    pub instance: CheckpointInstance,
+}
+
+// What the checkpointed run was solving, a resume on anything else is refused
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CheckpointInstance {
+    name: String,
+    // the metric of the coordinates, or the description of the matrix that replaced it
+    metric: String,
+    // FNV-1a of the distances, tells apart two matrices of the same instance and size
+    checksum: u64,
+}
+
+impl CheckpointInstance {
+    pub fn new(name: &str, metric: &str, distance_matrix: &[f64]) -> Self {
+        let checksum = distance_matrix
+            .iter()
+            .flat_map(|distance| distance.to_bits().to_le_bytes())
+            .fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+        CheckpointInstance {
+            name: name.to_string(),
+            metric: metric.to_string(),
+            checksum,
+        }
+    }
+
+    // Fails when the checkpoint was written for another instance, metric or matrix
+    pub fn check(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let content = read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let header: CheckpointHeader =
+            serde_json::from_str(&content).map_err(|err| format!("{}: {}", path, err))?;
+        let saved = header.instance;
+        if saved.name != self.name || saved.metric != self.metric {
+            return Err(format!(
+                "{}: checkpoint of {} ({}), the instance is {} ({})",
+                path, saved.name, saved.metric, self.name, self.metric
+            )
+            .into());
+        }
+        if saved.checksum != self.checksum {
+            return Err(format!(
+                "{}: checkpoint of other distances for {} ({})",
+                path, self.name, self.metric
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+// JSON file with the run counters and the algorithm state. The state is specific to each
+// algorithm, the header tells which one wrote the file before the state is read.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint<T> {
+    algorithm: String,
+    cities_len: usize,
+    instance: CheckpointInstance,
+    // seconds already run, the stopping criteria and the trace continue from it
+    elapsed: f64,
+    iterations: u64,
+    evaluations: u64,
+    trace: Vec<TracePoint>,
+    state: T,
+}
+
+#[derive(Deserialize)]
+struct CheckpointHeader {
+    algorithm: String,
+    cities_len: usize,
+    instance: CheckpointInstance,
+}
+
+impl<T> Checkpoint<T> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        algorithm: &str,
+        cities_len: usize,
+        instance: CheckpointInstance,
+        elapsed: Duration,
+        iterations: u64,
+        evaluations: u64,
+        trace: Vec<TracePoint>,
+        state: T,
+    ) -> Self {
+        Checkpoint {
+            algorithm: algorithm.to_string(),
+            cities_len,
+            instance,
+            elapsed: elapsed.as_secs_f64(),
+            iterations,
+            evaluations,
+            trace,
+            state,
+        }
+    }
+
+    pub fn get_elapsed(&self) -> Duration {
+        Duration::from_secs_f64(self.elapsed)
+    }
+
+    pub fn get_iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    pub fn get_evaluations(&self) -> u64 {
+        self.evaluations
+    }
+
+    pub fn get_trace(&self) -> &Vec<TracePoint> {
+        &self.trace
+    }
+
+    pub fn get_state(&self) -> &T {
+        &self.state
+    }
+
+    pub fn into_state(self) -> T {
+        self.state
+    }
+}
+
+impl<T: Serialize> Checkpoint<T> {
+    // Written next to the target and renamed, an interrupted write keeps the previous file
+    pub fn write_to_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let temporary_path = format!("{}.tmp", path);
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.flush()?;
+        rename(&temporary_path, path)?;
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> Checkpoint<T> {
+    // Fails when the file was written by another algorithm or for another instance size
+    pub fn read_from_file(
+        path: &str,
+        algorithm: &str,
+        cities_len: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let content = read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+        let header: CheckpointHeader =
+            serde_json::from_str(&content).map_err(|err| format!("{}: {}", path, err))?;
+        if header.algorithm != algorithm {
+            return Err(format!(
+                "{}: checkpoint of {}, cannot resume {}",
+                path, header.algorithm, algorithm
+            )
+            .into());
+        }
+        if header.cities_len != cities_len {
+            return Err(format!(
+                "{}: checkpoint of {} cities, the instance has {}",
+                path, header.cities_len, cities_len
+            )
+            .into());
+        }
+        Ok(serde_json::from_str(&content).map_err(|err| format!("{}: {}", path, err))?)
+    }
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use super::operator_selector::OperatorSelector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Crossover {
+    Order,
+    OrderBased,
+    Cycle,
+    CycleV2,
+    PositionBased,
+    Heuristic,
+    EdgeRecombination,
+    MaximalPreservative,
+    #[default]
+    PartiallyMapped,
+    AlternatingPosition,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossoverSelection {
+    // always the configured crossover
+    #[default]
+    Fixed,
+    // uniformly among the pool
+    Random,
+    // among the pool, favouring the crossovers whose children enter the population
+    Adaptive,
+}
+
+impl Crossover {
+    pub fn get_all() -> Vec<Self> {
+        vec![
+            Self::Order,
+            Self::OrderBased,
+            Self::Cycle,
+            Self::CycleV2,
+            Self::PositionBased,
+            Self::Heuristic,
+            Self::EdgeRecombination,
+            Self::MaximalPreservative,
+            Self::PartiallyMapped,
+            Self::AlternatingPosition,
+        ]
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Order => "order_crossover",
+            Self::OrderBased => "order_based_crossover",
+            Self::Cycle => "cycle_crossover",
+            Self::CycleV2 => "cycle_crossover_v2",
+            Self::PositionBased => "position_based_crossover",
+            Self::Heuristic => "heuristic_crossover",
+            Self::EdgeRecombination => "genetic_edge_recombination_crossover",
+            Self::MaximalPreservative => "maximal_preservative_crossover",
+            Self::PartiallyMapped => "partially_mapped_crossover",
+            Self::AlternatingPosition => "alternating_position_crossover",
+        }
+    }
+
+    // Smallest instance the operator can cut into segments
+    pub fn get_min_cities(&self) -> usize {
+        match self {
+            Self::MaximalPreservative => 11,
+            Self::PositionBased => 10,
+            _ => 3,
+        }
+    }
+}
+
+impl CrossoverSelection {
+    // Crossovers to choose from, an empty pool means every crossover that fits the instance
+    pub fn get_crossovers(
+        &self,
+        crossover: Crossover,
+        pool: &[Crossover],
+        cities_len: usize,
+    ) -> Vec<Crossover> {
+        match self {
+            Self::Fixed => vec![crossover],
+            _ if pool.is_empty() => Crossover::get_all()
+                .into_iter()
+                .filter(|c| c.get_min_cities() <= cities_len)
+                .collect(),
+            _ => pool.to_vec(),
+        }
+    }
+
+    pub fn create_selector(&self, crossovers: &[Crossover]) -> OperatorSelector {
+        OperatorSelector::new(
+            "crossover",
+            crossovers
+                .iter()
+                .map(|c| c.get_name().to_string())
+                .collect(),
+            &vec![1.0; crossovers.len()],
+            *self == Self::Adaptive,
+        )
+    }
+}
@@ -1,27 +1,34 @@
-use std::{
-    collections::{HashMap, HashSet},
-    time::Instant,
-    usize,
-};
+use std::collections::{HashMap, HashSet};
 
 use super::algorithm::{Algorithm, ExecuteResponse};
-use crate::models::city::City;
+use super::checkpoint::Checkpoint;
+use super::crossover::{Crossover, CrossoverSelection};
+use super::local_search::{DEFAULT_NEIGHBOURS, LocalSearch, LocalSearchMethod};
+use super::mutation::{Mutation, MutationSelection};
+use super::operator_selector::{OperatorSelector, OperatorStats};
+use super::parameters::{GeneticParameters, describe_parameters};
+use super::stopping_criteria::Termination;
+use crate::models::{city::City, distance_metric::DistanceMetric};
 use kiddo::KdTree;
 use plotters::prelude::LogScalable;
 use rand::{
-    Rng,
-    distr::{Distribution, weighted::WeightedIndex},
-    rng,
-    rngs::ThreadRng,
+    Rng, SeedableRng,
+    distr::{
+        Distribution,
+        weighted::{self, WeightedIndex},
+    },
     seq::{IndexedRandom, SliceRandom},
 };
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
-struct Chromossome {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Chromossome {
     path: Vec<u16>,
     distance: f64,
     mutation: String,
-    rng: ThreadRng,
 }
 
 impl PartialEq for Chromossome {
@@ -38,15 +45,14 @@ impl Chromossome {
             path,
             distance,
             mutation: "".to_string(),
-            rng: rng(),
         }
     }
 
-    fn get_path(&self) -> &Vec<u16> {
+    pub fn get_path(&self) -> &Vec<u16> {
         &self.path
     }
 
-    fn get_distance(&self) -> &f64 {
+    pub fn get_distance(&self) -> &f64 {
         &self.distance
     }
 
@@ -56,7 +62,7 @@ impl Chromossome {
 
     fn update_distance(mut self, path: Vec<u16>, distance_matrix: &[f64], mutation: &str) -> Self {
         let updated_distance = Genetic::calculate_path_distance(&path, distance_matrix);
-        if &updated_distance < &self.distance {
+        if updated_distance < self.distance {
             self.distance = updated_distance;
             self.path = path;
             self.mutation = mutation.to_string();
@@ -64,31 +70,30 @@ impl Chromossome {
         self
     }
 
-    fn mutate(mut self, distance_matrix: &[f64], swaps: usize) -> Self {
-        let prob = self.rng.random_range(0.0..1.0);
-        if prob <= 0.18 {
-            self.swap_mutation(distance_matrix, swaps)
-        } else if prob <= 0.36 {
-            self.displacement_mutation(distance_matrix)
-        } else if prob <= 0.54 {
-            self.insertion_mutation(distance_matrix)
-        } else if prob <= 0.72 {
-            self.simple_inversion_mutation(distance_matrix)
-        } else if prob <= 0.96 {
-            self.inversion_mutation(distance_matrix)
-        } else if prob <= 0.98 {
-            self.greedy_sub_tour_mutation(distance_matrix)
-        } else {
-            self.greedy_insertion_mutation(distance_matrix)
+    fn mutate(
+        self,
+        mutation: Mutation,
+        distance_matrix: &[f64],
+        swaps: usize,
+        rng: &mut ChaCha12Rng,
+    ) -> Self {
+        match mutation {
+            Mutation::Swap => self.swap_mutation(distance_matrix, swaps, rng),
+            Mutation::Displacement => self.displacement_mutation(distance_matrix, rng),
+            Mutation::Insertion => self.insertion_mutation(distance_matrix, rng),
+            Mutation::SimpleInversion => self.simple_inversion_mutation(distance_matrix, rng),
+            Mutation::Inversion => self.inversion_mutation(distance_matrix, rng),
+            Mutation::GreedySubTour => self.greedy_sub_tour_mutation(distance_matrix, rng),
+            Mutation::GreedyInsertion => self.greedy_insertion_mutation(distance_matrix, rng),
         }
     }
 
-    fn swap_mutation(mut self, distance_matrix: &[f64], swaps: usize) -> Self {
+    fn swap_mutation(self, distance_matrix: &[f64], swaps: usize, rng: &mut ChaCha12Rng) -> Self {
         let n = &self.path.len();
         let mut path = self.path.clone();
         for i in 0..swaps {
-            let first = self.rng.random_range(0..n - 1);
-            let second = self.rng.random_range(0..n - 1);
+            let first = rng.random_range(0..n - 1);
+            let second = rng.random_range(0..n - 1);
 
             path.swap(first, second);
         }
@@ -96,13 +101,13 @@ impl Chromossome {
         self.update_distance(path, distance_matrix, "swap_mutation")
     }
 
-    fn displacement_mutation(mut self, distance_matrix: &[f64]) -> Self {
+    fn displacement_mutation(self, distance_matrix: &[f64], rng: &mut ChaCha12Rng) -> Self {
         let n = &self.path.len();
         let mut path = self.path.clone();
-        let shift_size = self.rng.random_range(2..n - 1);
-        let distance2 = self.rng.random_range(0..n - 1);
+        let shift_size = rng.random_range(2..n - 1);
+        let distance2 = rng.random_range(0..n - 1);
 
-        let shift_position = self.rng.random_range(0..n - shift_size - 1);
+        let shift_position = rng.random_range(0..n - shift_size - 1);
         let displaced_part: Vec<u16> = path
             .drain(shift_position..shift_position + shift_size)
             .collect();
@@ -113,13 +118,13 @@ impl Chromossome {
         self.update_distance(path, distance_matrix, "displacement_mutation")
     }
 
-    fn insertion_mutation(mut self, distance_matrix: &[f64]) -> Self {
+    fn insertion_mutation(self, distance_matrix: &[f64], rng: &mut ChaCha12Rng) -> Self {
         let n = &self.path.len();
         let mut path = self.path.clone();
-        let old_pos = self.rng.random_range(1..n - 1);
+        let old_pos = rng.random_range(1..n - 1);
         let mut new_pos = old_pos;
         while new_pos == old_pos {
-            new_pos = self.rng.random_range(1..n - 1);
+            new_pos = rng.random_range(1..n - 1);
         }
         let city = path.remove(old_pos);
         path.insert(new_pos, city);
@@ -127,25 +132,25 @@ impl Chromossome {
         self.update_distance(path, distance_matrix, "insertion_mutation")
     }
 
-    fn simple_inversion_mutation(mut self, distance_matrix: &[f64]) -> Self {
+    fn simple_inversion_mutation(self, distance_matrix: &[f64], rng: &mut ChaCha12Rng) -> Self {
         let n = &self.path.len();
         let mut path = self.path.clone();
 
-        let start = self.rng.random_range(0..n - 3);
-        let size = self.rng.random_range(2..n - start);
+        let start = rng.random_range(0..n - 3);
+        let size = rng.random_range(2..n - start);
 
         path[start..start + size].reverse();
 
         self.update_distance(path, distance_matrix, "simple_inversion_mutation")
     }
 
-    fn inversion_mutation(mut self, distance_matrix: &[f64]) -> Self {
+    fn inversion_mutation(self, distance_matrix: &[f64], rng: &mut ChaCha12Rng) -> Self {
         let n = &self.path.len();
         let mut path = self.path.clone();
-        let shift_size = self.rng.random_range(2..n - 1);
-        let distance2 = self.rng.random_range(0..n - 1);
+        let shift_size = rng.random_range(2..n - 1);
+        let distance2 = rng.random_range(0..n - 1);
 
-        let shift_position = self.rng.random_range(0..n - shift_size - 1);
+        let shift_position = rng.random_range(0..n - shift_size - 1);
         let displaced_reversed_part: Vec<u16> = path
             .drain(shift_position..shift_position + shift_size)
             .rev()
@@ -157,22 +162,20 @@ impl Chromossome {
         self.update_distance(path, distance_matrix, "inversion_mutation")
     }
 
-    fn greedy_sub_tour_mutation(mut self, distance_matrix: &[f64]) -> Self {
+    fn greedy_sub_tour_mutation(self, distance_matrix: &[f64], rng: &mut ChaCha12Rng) -> Self {
         let n = &self.path.len();
         let mut path = self.path.clone();
 
         let min_sub_tour = 2;
         let max_sub_tour = (*n as f64).sqrt() as usize;
 
-        let start = self.rng.random_range(0..n - max_sub_tour);
-        let size = self
-            .rng
-            .random_range(min_sub_tour..max_sub_tour.max(min_sub_tour));
+        let start = rng.random_range(0..n - max_sub_tour);
+        let size = rng.random_range(min_sub_tour..max_sub_tour.max(min_sub_tour));
 
         let sub_tour: Vec<u16> = path.drain(start..start + size).collect();
 
         let sub_tour_usize: Vec<usize> = sub_tour.iter().map(|&x| x as usize).collect();
-        let first_best = Genetic::find_best_neighbour(
+        let first_best = Genetic::find_best_predecessor(
             distance_matrix,
             sub_tour[0] as usize,
             *n,
@@ -211,7 +214,7 @@ impl Chromossome {
         self.update_distance(path, distance_matrix, "greedy_sub_tour_mutation")
     }
 
-    fn greedy_insertion_mutation(mut self, distance_matrix: &[f64]) -> Self {
+    fn greedy_insertion_mutation(self, distance_matrix: &[f64], rng: &mut ChaCha12Rng) -> Self {
         let n = self.path.len();
         let mut path = self.path.clone();
 
@@ -219,15 +222,15 @@ impl Chromossome {
         let max_neighbour = (n as f64).sqrt() as usize;
         let size = min_neighbour.max(max_neighbour);
 
-        let city = self.rng.random_range(0..n - 1);
+        let city = rng.random_range(0..n - 1);
         path.retain(|&x| x != city as u16);
 
         let near_neighbours = Genetic::find_n_best_neighbours(distance_matrix, city, n, size);
 
-        let chosen = near_neighbours.choose(&mut self.rng).unwrap();
+        let chosen = near_neighbours.choose(rng).unwrap();
         let chosen_i = path.iter().position(|&x| x == *chosen as u16).unwrap();
 
-        if self.rng.random_bool(0.5) {
+        if rng.random_bool(0.5) {
             path.insert(chosen_i + 1, city as u16);
         } else {
             path.insert(chosen_i, city as u16);
@@ -237,26 +240,114 @@ impl Chromossome {
     }
 }
 
+// Counters that drive the number of swaps and the stopping breakpoint
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Evolution {
+    swap: usize,
+    gen_not_changed_best: usize,
+    gen_not_changed_best_limit: usize,
+}
+
+impl Evolution {
+    pub fn new(cities_len: usize) -> Self {
+        Evolution {
+            swap: 1,
+            gen_not_changed_best: 0,
+            gen_not_changed_best_limit: cities_len,
+        }
+    }
+
+    // e.g. an island that received a better chromossome
+    pub fn restart_stagnation(&mut self) {
+        self.gen_not_changed_best = 0;
+    }
+}
+
+// Everything a run needs to continue from a checkpoint
+#[derive(Serialize, Deserialize)]
+struct GeneticState {
+    population: Vec<Chromossome>,
+    initial_path: Vec<u16>,
+    generations: u32,
+    evolution: Evolution,
+    crossover_selector: OperatorSelector,
+    mutation_selector: OperatorSelector,
+    // the random sequence goes on where it stopped
+    rng: ChaCha12Rng,
+}
+
 pub struct Genetic {
-    distance_matrix: Vec<f64>,
-    kd_tree: KdTree<f32, 2>,
+    // shared by the islands of the island model
+    distance_matrix: Arc<[f64]>,
+    kd_tree: KdTree<f32, 3>,
     cities: Vec<City>,
-    crossover: String,
-    mutations: HashSet<String>,
+    metric: DistanceMetric,
+    crossovers: Vec<Crossover>,
+    crossover_selector: OperatorSelector,
+    mutation_operators: Vec<Mutation>,
+    mutation_selector: OperatorSelector,
+    // memetic mode, applied to the mutated children
+    local_search: LocalSearch,
     generations: u32,
-    rng: ThreadRng,
+    parameters: GeneticParameters,
+    rng: ChaCha12Rng,
+    termination: Termination,
+    // best path of the first population
+    initial_path: Vec<u16>,
+    // takes the place of the first random chromossome
+    initial_tour: Option<Vec<u16>>,
+    resume_state: Option<GeneticState>,
 }
 
 impl Genetic {
-    pub fn new(cities: &Vec<City>) -> Self {
+    pub fn new(
+        cities: &[City],
+        distance_matrix: &[f64],
+        metric: &DistanceMetric,
+        parameters: &GeneticParameters,
+        seed: u64,
+    ) -> Self {
+        Self::with_shared_matrix(cities, Arc::from(distance_matrix), metric, parameters, seed)
+    }
+
+    pub fn with_shared_matrix(
+        cities: &[City],
+        distance_matrix: Arc<[f64]>,
+        metric: &DistanceMetric,
+        parameters: &GeneticParameters,
+        seed: u64,
+    ) -> Self {
+        let crossovers = parameters.crossover_selection.get_crossovers(
+            parameters.crossover,
+            &parameters.crossover_pool,
+            cities.len(),
+        );
+        let mutation_operators = parameters.mutation_weights.get_mutations(cities.len());
+        let local_search = LocalSearch::new(
+            &distance_matrix,
+            cities.len(),
+            parameters.local_search,
+            DEFAULT_NEIGHBOURS,
+        );
         Genetic {
-            distance_matrix: vec![],
-            cities: cities.clone(),
-            kd_tree: Genetic::create_kd_tree(cities.clone()),
-            crossover: String::new(),
-            mutations: HashSet::new(),
+            distance_matrix,
+            cities: cities.to_vec(),
+            metric: metric.clone(),
+            kd_tree: Genetic::create_kd_tree(cities, metric),
+            crossover_selector: parameters.crossover_selection.create_selector(&crossovers),
+            crossovers,
+            mutation_selector: parameters
+                .mutation_selection
+                .create_selector(&mutation_operators, &parameters.mutation_weights),
+            mutation_operators,
+            local_search,
             generations: 0,
-            rng: rng(),
+            parameters: parameters.resolve(cities.len()),
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            termination: Termination::default(),
+            initial_path: vec![],
+            initial_tour: None,
+            resume_state: None,
         }
     }
 
@@ -291,11 +382,12 @@ impl Genetic {
                     &self.cities[current_city],
                     greedy_n,
                     &visited_cities,
+                    &self.metric,
                 );
 
                 let chosen = near_neighbours.choose(&mut self.rng).unwrap();
 
-                current_city = chosen.clone();
+                current_city = *chosen;
                 path.push(current_city);
                 visited_cities.push(&self.cities[current_city]);
             }
@@ -330,17 +422,16 @@ impl Genetic {
 
     fn select_parents(&mut self, population: &[Chromossome]) -> (Chromossome, Chromossome) {
         let n = population.len();
-        let parent_1 = self.rng.random_range(0..n - 1);
-        let mut parent_2 = parent_1;
-        while parent_1 == parent_2 {
-            parent_2 = self.rng.random_range(0..n - 1);
+        let parent_1 = self.rng.random_range(0..n);
+        // drawn among the others, two chromossomes are enough
+        let mut parent_2 = self.rng.random_range(0..n - 1);
+        if parent_2 >= parent_1 {
+            parent_2 += 1;
         }
         (population[parent_1].clone(), population[parent_2].clone())
     }
 
     fn order_crossover(&mut self, parent_1: &Chromossome, parent_2: &Chromossome) -> Chromossome {
-        self.crossover = "order_crossover".to_string();
-
         let n = parent_1.get_path().len();
 
         let mut start = self.rng.random_range(0..n - 2);
@@ -350,7 +441,7 @@ impl Genetic {
         path[start..end].copy_from_slice(&parent_1.get_path()[start..end]);
         for &city in parent_2.get_path() {
             if !path.contains(&city) {
-                if end <= n - 1 {
+                if end < n {
                     path[end] = city;
                     end += 1;
                 } else {
@@ -369,8 +460,6 @@ impl Genetic {
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "order_based_crossover".to_string();
-
         let n = parent_1.get_path().len();
 
         let mut start = self.rng.random_range(0..n - 2);
@@ -394,7 +483,6 @@ impl Genetic {
     }
 
     fn cycle_crossover(&mut self, parent_1: &Chromossome, parent_2: &Chromossome) -> Chromossome {
-        self.crossover = "cycle_crossover".to_string();
         let parent_1_path = parent_1.get_path();
         let parent_2_path = parent_2.get_path();
 
@@ -414,9 +502,9 @@ impl Genetic {
         }
 
         let mut av_i = 0;
-        for i in 0..n {
-            if !cycle.contains(&parent_2_path[i]) {
-                path[availiable_pos[av_i]] = parent_2_path[i];
+        for &city in parent_2_path {
+            if !cycle.contains(&city) {
+                path[availiable_pos[av_i]] = city;
                 av_i += 1;
             }
         }
@@ -430,7 +518,6 @@ impl Genetic {
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "position_based_crossover".to_string();
         let parent_2_path = parent_2.get_path();
 
         let n = parent_2_path.len();
@@ -460,7 +547,6 @@ impl Genetic {
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "heuristic_crossover".to_string();
         let parent_1_path = parent_1.get_path();
         let parent_2_path = parent_2.get_path();
 
@@ -480,10 +566,11 @@ impl Genetic {
                 if i > 0 && !path.contains(&parent[i - 1]) {
                     edges.push((
                         parent[i - 1],
-                        Self::calculate_distance_between_cities_ids(
+                        Self::get_in_matrix(
+                            &self.distance_matrix,
+                            n,
                             current as usize,
                             parent[i - 1] as usize,
-                            &self.cities,
                         ),
                     ));
                 }
@@ -491,10 +578,11 @@ impl Genetic {
                 if i + 1 < parent.len() && !path.contains(&parent[i + 1]) {
                     edges.push((
                         parent[i + 1],
-                        Self::calculate_distance_between_cities_ids(
+                        Self::get_in_matrix(
+                            &self.distance_matrix,
+                            n,
                             current as usize,
                             parent[i + 1] as usize,
-                            &self.cities,
                         ),
                     ));
                 }
@@ -502,13 +590,15 @@ impl Genetic {
 
             if edges.len() == 1 {
                 current = edges[0].0;
-            } else if edges.len() > 0 {
-                let mut probabilities = vec![];
-                for edge in &edges {
-                    probabilities.push((1.0 / edge.1) / edges.len().as_f64());
-                }
-                let dist = WeightedIndex::new(&probabilities).unwrap();
-                current = edges[dist.sample(&mut self.rng)].0;
+            } else if let Some(&(city, _)) = edges.iter().find(|edge| edge.1 == 0.0) {
+                // a city at the same place is the shortest edge there is, 1 / 0 is no weight
+                current = city;
+            } else if !edges.is_empty() {
+                // the weights fail only when every edge is infinite or NaN, any of them will do
+                current = match self.choose_weighted_edge(&edges) {
+                    Ok(city) => city,
+                    Err(_) => edges[self.rng.random_range(0..edges.len())].0,
+                };
             } else {
                 let random_pos = self.rng.random_range(0..unvisited.len());
                 current = unvisited[random_pos];
@@ -522,12 +612,21 @@ impl Genetic {
         Chromossome::new(path, distance)
     }
 
+    // Edge drawn with a probability inversely proportional to its distance
+    fn choose_weighted_edge(&mut self, edges: &[(u16, f64)]) -> Result<u16, weighted::Error> {
+        let probabilities: Vec<f64> = edges
+            .iter()
+            .map(|edge| (1.0 / edge.1) / edges.len().as_f64())
+            .collect();
+        let dist = WeightedIndex::new(&probabilities)?;
+        Ok(edges[dist.sample(&mut self.rng)].0)
+    }
+
     fn genetic_edge_recombination_crossover(
         &mut self,
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "genetic_edge_recombination_crossover".to_string();
         let parent_1_path = parent_1.get_path();
         let parent_2_path = parent_2.get_path();
         let n = parent_1_path.len();
@@ -543,7 +642,7 @@ impl Genetic {
         for parent in [parent_1_path, parent_2_path] {
             for i in 0..parent.len() {
                 let city = parent[i];
-                let set = edge_map.entry(city).or_insert_with(HashSet::new);
+                let set = edge_map.entry(city).or_default();
 
                 if i + 1 == parent.len() {
                     set.insert(parent[0]);
@@ -576,7 +675,7 @@ impl Genetic {
                             .cmp(&edge_map.get(c2).unwrap().len())
                     })
                     .unwrap_or(&unvisited[self.rng.random_range(0..unvisited.len())]);
-                current = less_cities.clone();
+                current = *less_cities;
             } else {
                 current = unvisited[self.rng.random_range(0..unvisited.len())];
             }
@@ -593,17 +692,14 @@ impl Genetic {
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "maximal_preservative_crossover".to_string();
-
         let n = parent_1.get_path().len();
 
         let start = self.rng.random_range(0..n - 10);
-        let end;
-        if start + 10 < n / 2 {
-            end = self.rng.random_range(start + 10..n / 2);
+        let end = if start + 10 < n / 2 {
+            self.rng.random_range(start + 10..n / 2)
         } else {
-            end = n - 1;
-        }
+            n - 1
+        };
 
         let mut path = parent_1.get_path()[start..end].to_vec();
 
@@ -622,8 +718,6 @@ impl Genetic {
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "partially_mapped_crossover".to_string();
-
         let n = parent_1.get_path().len();
 
         let start = self.rng.random_range(0..n - 2);
@@ -648,12 +742,10 @@ impl Genetic {
             .cloned()
             .collect();
 
-        let mut i = 0;
-        for city in path.clone() {
+        for (i, city) in path.clone().into_iter().enumerate() {
             if duplicated.contains(&city) && !(start..end).contains(&i) {
                 path[i] = to_replace.pop().unwrap();
             }
-            i += 1;
         }
 
         let distance = Self::calculate_path_distance(&path, &self.distance_matrix);
@@ -665,8 +757,6 @@ impl Genetic {
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "alternating_position_crossover".to_string();
-
         let n = parent_1.get_path().len();
         let mut path = vec![];
         let mut i = 0;
@@ -691,7 +781,6 @@ impl Genetic {
         parent_1: &Chromossome,
         parent_2: &Chromossome,
     ) -> Chromossome {
-        self.crossover = "cycle_crossover_v2".to_string();
         let parent_1_path = parent_1.get_path();
         let parent_2_path = parent_2.get_path();
 
@@ -702,7 +791,7 @@ impl Genetic {
 
         let mut current;
 
-        while availiable.len() > 0 {
+        while !availiable.is_empty() {
             let mut turn = 2;
             let mut cycle = vec![];
             current = availiable[0];
@@ -713,7 +802,7 @@ impl Genetic {
                         break;
                     }
                     cycle.push(current);
-                    availiable.retain(|&x| x != current as u16);
+                    availiable.retain(|&x| x != current);
                     path.push(current);
                 }
                 let i = parent_1_path.iter().position(|&x| x == current).unwrap();
@@ -726,24 +815,134 @@ impl Genetic {
         Chromossome::new(path, distance)
     }
 
+    fn apply_crossover(
+        &mut self,
+        crossover: Crossover,
+        parent_1: &Chromossome,
+        parent_2: &Chromossome,
+    ) -> Chromossome {
+        match crossover {
+            Crossover::Order => self.order_crossover(parent_1, parent_2),
+            Crossover::OrderBased => self.order_based_crossover(parent_1, parent_2),
+            Crossover::Cycle => self.cycle_crossover(parent_1, parent_2),
+            Crossover::CycleV2 => self.cycle_crossover_v2(parent_1, parent_2),
+            Crossover::PositionBased => self.position_based_crossover(parent_1, parent_2),
+            Crossover::Heuristic => self.heuristic_crossover(parent_1, parent_2),
+            Crossover::EdgeRecombination => {
+                self.genetic_edge_recombination_crossover(parent_1, parent_2)
+            }
+            Crossover::MaximalPreservative => {
+                self.maximal_preservative_crossover(parent_1, parent_2)
+            }
+            Crossover::PartiallyMapped => self.partially_mapped_crossover(parent_1, parent_2),
+            Crossover::AlternatingPosition => {
+                self.alternating_position_crossover(parent_1, parent_2)
+            }
+        }
+    }
+
+    // Mutates with the operator chosen by the selector, rewarded when the chromossome improves.
+    // Returns the operator index and whether it improved the chromossome.
+    fn apply_mutation(
+        &mut self,
+        chromossome: Chromossome,
+        swap: usize,
+    ) -> (Chromossome, usize, bool) {
+        let index = self.mutation_selector.choose(&mut self.rng);
+        let distance = *chromossome.get_distance();
+        let mutated = chromossome.mutate(
+            self.mutation_operators[index],
+            &self.distance_matrix,
+            swap,
+            &mut self.rng,
+        );
+        let improved = *mutated.get_distance() < distance;
+        self.mutation_selector.reward(index, improved);
+        (mutated, index, improved)
+    }
+
+    // Memetic step, on a share of the chromossomes given by local_search_rate
+    fn apply_local_search(&mut self, mut chromossome: Chromossome) -> Chromossome {
+        if self.local_search.get_method() == LocalSearchMethod::None
+            || (self.parameters.local_search_rate < 1.0
+                && !self.rng.random_bool(self.parameters.local_search_rate))
+        {
+            return chromossome;
+        }
+        let mut path = chromossome.path.clone();
+        let distance =
+            self.local_search
+                .improve(&self.distance_matrix, &mut path, &mut self.termination);
+        self.termination.add_evaluations(1);
+        if distance < chromossome.distance {
+            chromossome.path = path;
+            chromossome.distance = distance;
+            chromossome.mutation = self.local_search.get_method().get_name().to_string();
+        }
+        chromossome
+    }
+
+    // Crossover statistics only when the population crosses over
+    pub fn get_operator_stats(&self) -> Vec<OperatorStats> {
+        let mut stats = vec![];
+        if self.parameters.population_size > 1 {
+            stats.extend(self.crossover_selector.get_stats());
+        }
+        stats.extend(self.mutation_selector.get_stats());
+        stats
+    }
+
+    fn write_checkpoint(&mut self, population: &[Chromossome], evolution: Evolution) {
+        let state = GeneticState {
+            population: population.to_vec(),
+            initial_path: self.initial_path.clone(),
+            generations: self.generations,
+            evolution,
+            crossover_selector: self.crossover_selector.clone(),
+            mutation_selector: self.mutation_selector.clone(),
+            rng: self.rng.clone(),
+        };
+        self.termination
+            .write_checkpoint("G", self.cities.len(), state);
+    }
+
     fn execute_for_population(
         &mut self,
         mut population: Vec<Chromossome>,
-        initial_swap: usize,
+        evolution: &mut Evolution,
+        last_generation: u32,
     ) -> Vec<Chromossome> {
         let mut worst = self.get_worst_chromossome(&population);
         let mut best = self.get_best_chromossome(&population);
-        let mut gen_not_changed_best = 0;
-        let mut gen_not_changed_best_limit = self.cities.len();
-        let gen_not_changed_best_breakpoint = 200000;
-        let mut swap = initial_swap;
-        while gen_not_changed_best < gen_not_changed_best_breakpoint {
+        self.termination
+            .report_best(*best.get_distance(), best.get_path());
+        let Evolution {
+            mut swap,
+            mut gen_not_changed_best,
+            mut gen_not_changed_best_limit,
+        } = *evolution;
+        let gen_not_changed_best_breakpoint = self.parameters.gen_not_changed_best_breakpoint;
+        while gen_not_changed_best < gen_not_changed_best_breakpoint
+            && self.generations < last_generation
+        {
             let (parent_1, parent_2) = self.select_parents(&population);
-            let children = self
-                .partially_mapped_crossover(&parent_1, &parent_2)
-                .mutate(&self.distance_matrix, swap);
-
-            if children.get_distance() < worst.get_distance() {
+            let crossover_index = self.crossover_selector.choose(&mut self.rng);
+            let crossover = self.crossovers[crossover_index];
+            let children = self.apply_crossover(crossover, &parent_1, &parent_2);
+            let (children, mutation_index, mutation_improved) = self.apply_mutation(children, swap);
+            let children = self.apply_local_search(children);
+            self.termination.add_evaluations(2);
+
+            let entered_population = children.get_distance() < worst.get_distance();
+            self.crossover_selector
+                .reward(crossover_index, entered_population);
+
+            if entered_population {
+                // the mutation only shares the credit when it improved the child
+                self.crossover_selector.record_accepted(crossover_index);
+                if mutation_improved {
+                    self.mutation_selector.record_accepted(mutation_index);
+                }
                 let i = population.iter().position(|x| x == &worst).unwrap();
 
                 population.remove(i);
@@ -752,9 +951,16 @@ impl Genetic {
                 worst = self.get_worst_chromossome(&population);
 
                 if children.get_distance() < best.get_distance() {
+                    let gain = best.get_distance() - children.get_distance();
+                    self.crossover_selector
+                        .record_new_best(crossover_index, gain);
+                    if mutation_improved {
+                        self.mutation_selector.record_new_best(mutation_index, gain);
+                    }
                     best = children;
+                    self.termination
+                        .report_best(*best.get_distance(), best.get_path());
                     let mutation = best.get_mutation();
-                    self.mutations.insert(mutation.clone());
                     // println!(
                     //     "{} {} {} {}",
                     //     &best.get_distance(),
@@ -783,31 +989,61 @@ impl Genetic {
             }
 
             self.generations += 1;
+            if self.termination.next_iteration(*best.get_distance()) {
+                break;
+            }
+            if self.termination.checkpoint_due() {
+                let evolution = Evolution {
+                    swap,
+                    gen_not_changed_best,
+                    gen_not_changed_best_limit,
+                };
+                self.write_checkpoint(&population, evolution);
+            }
         }
 
+        *evolution = Evolution {
+            swap,
+            gen_not_changed_best,
+            gen_not_changed_best_limit,
+        };
         population
     }
 
     fn execute_for_one_population_army(
         &mut self,
         first_gen: &Chromossome,
-        initial_swap: usize,
+        evolution: &mut Evolution,
+        last_generation: u32,
     ) -> Chromossome {
-        let mut previous_distance = first_gen.get_distance().clone();
-        let mut gen_not_changed_best = 0;
-        let mut gen_not_changed_best_limit = self.cities.len();
-        let gen_not_changed_best_breakpoint = 500000;
-        let mut swap = initial_swap;
+        let mut previous_distance = *first_gen.get_distance();
+        let Evolution {
+            mut swap,
+            mut gen_not_changed_best,
+            mut gen_not_changed_best_limit,
+        } = *evolution;
+        let gen_not_changed_best_breakpoint = self.parameters.gen_not_changed_best_breakpoint;
         let mut current_gen = first_gen.clone();
-
-        while gen_not_changed_best < gen_not_changed_best_breakpoint {
-            let new_gen = current_gen.clone().mutate(&self.distance_matrix, swap);
+        self.termination
+            .report_best(previous_distance, current_gen.get_path());
+
+        while gen_not_changed_best < gen_not_changed_best_breakpoint
+            && self.generations < last_generation
+        {
+            let (new_gen, mutation_index, _) = self.apply_mutation(current_gen.clone(), swap);
+            let new_gen = self.apply_local_search(new_gen);
+            self.termination.add_evaluations(1);
             let new_distance = new_gen.get_distance();
             if previous_distance > *new_distance {
-                previous_distance = new_distance.clone();
+                // the single chromossome is the population and the best at once
+                self.mutation_selector.record_accepted(mutation_index);
+                self.mutation_selector
+                    .record_new_best(mutation_index, previous_distance - new_distance);
+                previous_distance = *new_distance;
                 current_gen = new_gen.clone();
+                self.termination
+                    .report_best(previous_distance, current_gen.get_path());
                 let mutation = new_gen.get_mutation();
-                self.mutations.insert(mutation.clone());
                 // println!(
                 //     "{} {} {} {}",
                 //     &new_distance, self.generations, swap, mutation
@@ -828,41 +1064,121 @@ impl Genetic {
                 }
             }
             self.generations += 1;
+            if self.termination.next_iteration(previous_distance) {
+                break;
+            }
+            if self.termination.checkpoint_due() {
+                let evolution = Evolution {
+                    swap,
+                    gen_not_changed_best,
+                    gen_not_changed_best_limit,
+                };
+                self.write_checkpoint(&[current_gen.clone()], evolution);
+            }
         }
+        *evolution = Evolution {
+            swap,
+            gen_not_changed_best,
+            gen_not_changed_best_limit,
+        };
         current_gen
     }
+
+    // Random population, with the initial tour in place of the first chromossome
+    pub fn create_first_population(&mut self) -> Vec<Chromossome> {
+        let population_size = self.parameters.population_size;
+        let mut population = self.create_random_population(population_size);
+        self.termination.add_evaluations(population_size as u64);
+        if let Some(tour) = self.initial_tour.take() {
+            let distance = Self::calculate_path_distance(&tour, &self.distance_matrix);
+            population[0] = Chromossome::new(tour, distance);
+        }
+        // let population = self.create_greedy_population(population_size, greedy_range);
+        self.initial_path = population[0].get_path().clone();
+        population
+    }
+
+    // At most `generations` more generations, less when the breakpoint or a stopping
+    // criterion is reached
+    pub fn evolve(
+        &mut self,
+        mut population: Vec<Chromossome>,
+        evolution: &mut Evolution,
+        generations: u32,
+    ) -> Vec<Chromossome> {
+        let last_generation = self.generations.saturating_add(generations);
+        if population.len() == 1 {
+            population[0] =
+                self.execute_for_one_population_army(&population[0], evolution, last_generation);
+            population
+        } else {
+            self.execute_for_population(population, evolution, last_generation)
+        }
+    }
+
+    // The breakpoint of generations without a new best was reached
+    pub fn has_converged(&self, evolution: &Evolution) -> bool {
+        evolution.gen_not_changed_best >= self.parameters.gen_not_changed_best_breakpoint
+    }
+
+    // a single chromossome only mutates
+    pub fn describe_crossover(&self, population_size: usize) -> String {
+        if population_size == 1 {
+            String::new()
+        } else if self.parameters.crossover_selection == CrossoverSelection::Fixed {
+            self.crossovers[0].get_name().to_string()
+        } else {
+            format!("{:?}", self.parameters.crossover_selection)
+        }
+    }
+
+    pub fn get_generations(&self) -> u32 {
+        self.generations
+    }
+
+    pub fn get_initial_path(&self) -> &Vec<u16> {
+        &self.initial_path
+    }
 }
 
 impl Algorithm for Genetic {
     fn execute(&mut self) -> ExecuteResponse {
         println!("Execute Genetic");
-        let start_time = Instant::now();
+        self.termination.start();
         let len_cities = self.cities.len();
         let greedy_range = ((len_cities as f32).sqrt() as usize).max(10);
-        let population_size;
-        if len_cities > 1000 {
-            population_size = 1;
-        } else {
-            population_size = 100;
-        }
-        self.distance_matrix = Self::create_distance_matrix(&self.cities);
-
-        let mut population = self.create_random_population(population_size);
-        // let mut population = self.create_greedy_population(population_size, greedy_range);
-
-        let first_gen_best_path = population[0].get_path().clone();
 
-        let swap = 1;
+        let (mut population, mut evolution) = match self.resume_state.take() {
+            Some(state) => {
+                self.initial_path = state.initial_path;
+                self.generations = state.generations;
+                self.crossover_selector = state.crossover_selector;
+                self.mutation_selector = state.mutation_selector;
+                self.rng = state.rng;
+                (state.population, state.evolution)
+            }
+            None => (self.create_first_population(), Evolution::new(len_cities)),
+        };
+        // a resumed run keeps the population size of its checkpoint
+        let population_size = population.len();
 
-        if population_size == 1 {
-            population[0] = self.execute_for_one_population_army(&population[0], swap);
-        } else {
-            population = self.execute_for_population(population, swap);
-        }
+        population = self.evolve(population, &mut evolution, u32::MAX);
+        self.write_checkpoint(&population, evolution);
 
+        let crossover = self.describe_crossover(population_size);
+        let operator_stats = self.get_operator_stats();
         let metadata = format!(
-            "Population Size: {}\nGenerations: {}\nCrossover: {}\nMutations: {:?}\n",
-            population_size, self.generations, self.crossover, self.mutations
+            "Population Size: {}\nGenerations: {}\nCrossover: {}\nMutation Selection: {:?}\nOperators:\n{}\nParameters: {}\n",
+            population_size,
+            self.generations,
+            crossover,
+            self.parameters.mutation_selection,
+            operator_stats
+                .iter()
+                .map(|stats| format!("  {}", stats.describe()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            describe_parameters(&self.parameters)
         );
         // let metadata = format!(
         //     "Population Size: {}\nGenerations: {}\nCrossover: {}\nMutations: {:?}\nGreedy Start Range: {}",
@@ -871,12 +1187,162 @@ impl Algorithm for Genetic {
 
         let best = self.get_best_chromossome(&population);
 
-        ExecuteResponse::new(
-            first_gen_best_path,
+        let mut response = ExecuteResponse::new(
+            self.initial_path.clone(),
             best.get_path().clone(),
-            best.get_distance().clone(),
-            start_time.elapsed(),
+            *best.get_distance(),
+            self.termination.get_elapsed(),
             metadata,
+        );
+        response.set_operator_stats(operator_stats);
+        response.set_termination(&self.termination);
+        response
+    }
+
+    fn get_termination(&mut self) -> &mut Termination {
+        &mut self.termination
+    }
+
+    fn set_initial_tour(&mut self, tour: &[u16]) {
+        self.initial_tour = Some(tour.to_vec());
+    }
+
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let checkpoint: Checkpoint<GeneticState> =
+            Checkpoint::read_from_file(path, "G", self.cities.len())?;
+        let state = checkpoint.get_state();
+        if !(state
+            .crossover_selector
+            .has_same_operators(&self.crossover_selector)
+            && state
+                .mutation_selector
+                .has_same_operators(&self.mutation_selector))
+        {
+            return Err(format!(
+                "{}: the checkpoint uses other crossovers or mutations than the parameters",
+                path
+            )
+            .into());
+        }
+        self.termination.restore(&checkpoint);
+        self.resume_state = Some(checkpoint.into_state());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::coordinates::Coordinates;
+
+    // Cities on a circle, the Euclidean matrix between them
+    fn create_instance(n: usize) -> (Vec<City>, Vec<f64>) {
+        let cities: Vec<City> = (0..n)
+            .map(|i| {
+                let angle = i as f32 * std::f32::consts::TAU / n as f32;
+                let coordinates = Coordinates::new(angle.sin() * 10.0, angle.cos() * 10.0);
+                City::new(
+                    i as u16,
+                    i as u32,
+                    i.to_string(),
+                    None,
+                    None,
+                    false,
+                    None,
+                    coordinates,
+                )
+            })
+            .collect();
+        let distance_matrix = DistanceMetric::Euclidean.create_distance_matrix(&cities);
+        (cities, distance_matrix)
+    }
+
+    fn create_genetic(n: usize, population_size: usize) -> Genetic {
+        let (cities, distance_matrix) = create_instance(n);
+        let parameters = GeneticParameters {
+            population_size,
+            gen_not_changed_best_breakpoint: 100,
+            ..GeneticParameters::default()
+        };
+        Genetic::new(
+            &cities,
+            &distance_matrix,
+            &DistanceMetric::Euclidean,
+            &parameters,
+            1,
         )
     }
+
+    #[test]
+    fn two_chromossomes_are_both_parents() {
+        let mut genetic = create_genetic(12, 2);
+        let population = genetic.create_first_population();
+        assert_eq!(population.len(), 2);
+        let mut seen = [false; 2];
+        for _ in 0..100 {
+            let (parent_1, parent_2) = genetic.select_parents(&population);
+            assert_ne!(parent_1, parent_2);
+            let first = population.iter().position(|c| c == &parent_1).unwrap();
+            seen[first] = true;
+        }
+        // the last chromossome is a first parent too
+        assert_eq!(seen, [true, true]);
+    }
+
+    #[test]
+    fn two_chromossomes_evolve() {
+        let mut genetic = create_genetic(12, 2);
+        let population = genetic.create_first_population();
+        let mut evolution = Evolution::new(12);
+        let population = genetic.evolve(population, &mut evolution, 500);
+        assert_eq!(population.len(), 2);
+        assert!(genetic.get_generations() > 0);
+    }
+
+    #[test]
+    fn heuristic_crossover_takes_zero_edges() {
+        let (mut cities, _) = create_instance(10);
+        // every city has a twin at the same place
+        let twins: Vec<City> = cities
+            .iter()
+            .map(|city| {
+                let id = city.get_id() + 10;
+                let coordinates = city.get_coordinates().clone();
+                City::new(
+                    id,
+                    id as u32,
+                    id.to_string(),
+                    None,
+                    None,
+                    false,
+                    None,
+                    coordinates,
+                )
+            })
+            .collect();
+        cities.extend(twins);
+        let distance_matrix = DistanceMetric::Euclidean.create_distance_matrix(&cities);
+        let parameters = GeneticParameters {
+            population_size: 10,
+            ..GeneticParameters::default()
+        };
+        let metric = DistanceMetric::Euclidean;
+        let mut genetic = Genetic::new(&cities, &distance_matrix, &metric, &parameters, 1);
+        let population = genetic.create_first_population();
+        for _ in 0..100 {
+            let (parent_1, parent_2) = genetic.select_parents(&population);
+            let child = genetic.heuristic_crossover(&parent_1, &parent_2);
+            let mut path = child.get_path().clone();
+            path.sort_unstable();
+            assert_eq!(path, (0..20).collect::<Vec<u16>>());
+        }
+    }
+
+    #[test]
+    fn infinite_edges_have_no_weights() {
+        let mut genetic = create_genetic(12, 2);
+        let edges = [(1, f64::INFINITY), (2, f64::INFINITY)];
+        assert!(genetic.choose_weighted_edge(&edges).is_err());
+        assert!([1, 2].contains(&genetic.choose_weighted_edge(&[(1, 2.0), (2, 3.0)]).unwrap()));
+    }
 }
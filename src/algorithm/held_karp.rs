@@ -0,0 +1,263 @@
+use std::{error::Error, mem::size_of};
+
+use super::algorithm::{Algorithm, ExecuteResponse};
+use super::parameters::{HeldKarpParameters, describe_parameters};
+use super::stopping_criteria::Termination;
+use crate::models::city::City;
+
+// Subsets computed between two checks of the stopping criteria
+const CHECK_INTERVAL: usize = 1 << 12;
+
+// Exact dynamic programming, O(n² · 2ⁿ) time and O(n · 2ⁿ) memory. The table holds, for
+// every subset of the cities but 0 (a bitset) and every city j in it, the shortest path
+// from city 0 through the subset ending at j, and the city before j on that path.
+pub struct HeldKarp {
+    cities: Vec<City>,
+    size: usize,
+    distance_matrix: Vec<f64>,
+    // reported first and returned when the run stops early, nearest neighbour by default
+    initial_path: Option<Vec<u16>>,
+    parameters: HeldKarpParameters,
+    termination: Termination,
+}
+
+impl HeldKarp {
+    pub fn new(cities: &[City], distance_matrix: &[f64], parameters: &HeldKarpParameters) -> Self {
+        HeldKarp {
+            cities: cities.to_vec(),
+            size: cities.len(),
+            distance_matrix: distance_matrix.to_vec(),
+            initial_path: None,
+            parameters: parameters.clone(),
+            termination: Termination::default(),
+        }
+    }
+
+    // Bytes of the DP table and its predecessors, None when they cannot even be addressed
+    pub fn get_table_size(cities_len: usize) -> Option<usize> {
+        let m = cities_len.saturating_sub(1);
+        1usize
+            .checked_shl(m.try_into().ok()?)?
+            .checked_mul(m)?
+            .checked_mul(size_of::<f64>() + size_of::<u16>())
+    }
+
+    // The optimal tour and its cost, None when a stopping criterion ends the run first
+    fn solve(&mut self, fallback_cost: f64) -> Option<(Vec<u16>, f64)> {
+        let n = self.size;
+        if n < 2 {
+            return Some(((0..n as u16).collect(), 0.0));
+        }
+        // city i + 1 of the instance is bit i of the subsets
+        let m = n - 1;
+        let distance = |from: usize, to: usize| self.distance_matrix[from * n + to];
+        let full = (1usize << m) - 1;
+        let mut table = vec![f64::INFINITY; (full + 1) * m];
+        // bit of the city before j, unused for the single-city subsets
+        let mut parents = vec![0u16; (full + 1) * m];
+        for j in 0..m {
+            table[(1 << j) * m + j] = distance(0, j + 1);
+        }
+
+        for mask in 1..=full {
+            // subsets come after all their own subsets, whose rows are final
+            if !mask.is_power_of_two() {
+                let mut ends = mask;
+                while ends != 0 {
+                    let j = ends.trailing_zeros() as usize;
+                    ends &= ends - 1;
+                    let previous = mask ^ (1 << j);
+                    let (mut best, mut parent) = (f64::INFINITY, 0);
+                    let mut others = previous;
+                    while others != 0 {
+                        let k = others.trailing_zeros() as usize;
+                        others &= others - 1;
+                        let cost = table[previous * m + k] + distance(k + 1, j + 1);
+                        if cost < best {
+                            (best, parent) = (cost, k);
+                        }
+                    }
+                    table[mask * m + j] = best;
+                    parents[mask * m + j] = parent as u16;
+                }
+            }
+            if mask % CHECK_INTERVAL == 0 {
+                self.termination
+                    .add_evaluations((CHECK_INTERVAL * m) as u64);
+                if self.termination.should_stop(fallback_cost) {
+                    return None;
+                }
+            }
+        }
+        self.termination
+            .add_evaluations(((full % CHECK_INTERVAL) * m) as u64);
+
+        let (mut last, cost) = (0..m)
+            .map(|j| (j, table[full * m + j] + distance(j + 1, 0)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+
+        // walks the predecessors back from the last city to the first one
+        let mut path = vec![last as u16 + 1];
+        let mut mask = full;
+        while mask != 1 << last {
+            let previous = mask ^ (1 << last);
+            last = parents[mask * m + last] as usize;
+            mask = previous;
+            path.push(last as u16 + 1);
+        }
+        path.push(0);
+        path.reverse();
+        Some((path, cost))
+    }
+}
+
+impl Algorithm for HeldKarp {
+    fn execute(&mut self) -> ExecuteResponse {
+        println!("Execute HeldKarp");
+        self.termination.start();
+
+        let initial_path = match self.initial_path.take() {
+            Some(path) => path,
+            None => Self::create_nearest_neighbour_path(&self.distance_matrix, self.size),
+        };
+        let initial_cost = Self::calculate_path_distance(&initial_path, &self.distance_matrix);
+        self.termination.add_evaluations(1);
+        self.termination.report_best(initial_cost, &initial_path);
+
+        let fits = Self::get_table_size(self.size)
+            .is_some_and(|bytes| bytes <= self.parameters.max_memory_mb.saturating_mul(1 << 20));
+        let solution = if fits {
+            self.solve(initial_cost)
+        } else {
+            eprintln!(
+                "HK: {} cities need more than HK.max_memory_mb = {}",
+                self.size, self.parameters.max_memory_mb
+            );
+            None
+        };
+
+        let optimal = solution.is_some();
+        let (path, cost) = solution.unwrap_or((initial_path.clone(), initial_cost));
+        self.termination.report_best(cost, &path);
+
+        let mut response = ExecuteResponse::new(
+            initial_path,
+            path,
+            cost,
+            self.termination.get_elapsed(),
+            format!(
+                "Optimal: {}\nParameters: {}",
+                optimal,
+                describe_parameters(&self.parameters)
+            ),
+        );
+        response.set_optimal(optimal);
+        response.set_termination(&self.termination);
+        response
+    }
+
+    fn get_termination(&mut self) -> &mut Termination {
+        &mut self.termination
+    }
+
+    fn set_initial_tour(&mut self, tour: &[u16]) {
+        self.initial_path = Some(tour.to_vec());
+    }
+
+    // The table is the whole state and can take gigabytes, a stopped run starts over
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        Err(format!("{}: HK runs cannot be resumed, run them again", path).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::coordinates::Coordinates;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    const TOLERANCE: f64 = 1e-9;
+    const INSTANCES: u64 = 5;
+
+    // Cities only give the size, the distances are random and one way apart when asymmetric
+    fn create_instance(n: usize, symmetric: bool, seed: u64) -> (Vec<City>, Vec<f64>) {
+        let cities: Vec<City> = (0..n)
+            .map(|i| {
+                City::new(
+                    i as u16,
+                    i as u32,
+                    i.to_string(),
+                    None,
+                    None,
+                    false,
+                    None,
+                    Coordinates::new(0.0, 0.0),
+                )
+            })
+            .collect();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut matrix = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..n {
+                if i < j || !symmetric && i != j {
+                    matrix[i * n + j] = rng.random_range(1..100) as f64;
+                }
+                if symmetric && i > j {
+                    matrix[i * n + j] = matrix[j * n + i];
+                }
+            }
+        }
+        (cities, matrix)
+    }
+
+    // Shortest tour by trying every order of the cities after the first
+    fn brute_force(matrix: &[f64], n: usize) -> f64 {
+        fn visit(matrix: &[f64], n: usize, path: &mut Vec<usize>, cost: f64, best: &mut f64) {
+            let last = *path.last().unwrap();
+            if path.len() == n {
+                *best = best.min(cost + matrix[last * n]);
+                return;
+            }
+            for city in 1..n {
+                if !path.contains(&city) {
+                    path.push(city);
+                    visit(matrix, n, path, cost + matrix[last * n + city], best);
+                    path.pop();
+                }
+            }
+        }
+        let mut best = f64::INFINITY;
+        visit(matrix, n, &mut vec![0], 0.0, &mut best);
+        best
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        for n in 5..=8 {
+            for symmetric in [true, false] {
+                for seed in 0..INSTANCES {
+                    let (cities, matrix) = create_instance(n, symmetric, seed * 10 + n as u64);
+                    let optimum = brute_force(&matrix, n);
+                    let response =
+                        HeldKarp::new(&cities, &matrix, &HeldKarpParameters::default()).execute();
+                    let path = response.get_final_path();
+
+                    let mut sorted = path.clone();
+                    sorted.sort_unstable();
+                    assert_eq!(sorted, (0..n as u16).collect::<Vec<_>>());
+                    assert!(response.is_optimal());
+                    assert!((response.get_distance() - optimum).abs() < TOLERANCE);
+                    // the rebuilt tour has the cost of the table
+                    let length = HeldKarp::calculate_path_distance(path, &matrix);
+                    assert!(
+                        (length - optimum).abs() < TOLERANCE,
+                        "{} != {}",
+                        length,
+                        optimum
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,464 @@
+use std::{error::Error, sync::Arc, thread};
+
+use csv::Writer;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+use super::algorithm::{Algorithm, ExecuteResponse};
+use super::genetic::{Chromossome, Evolution, Genetic};
+use super::operator_selector::merge_operator_stats;
+use super::parameters::{GeneticParameters, describe_parameters};
+use super::stopping_criteria::{StoppingCriteria, Termination};
+use crate::models::{city::City, distance_metric::DistanceMetric};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrationTopology {
+    // every island sends to the next one
+    #[default]
+    Ring,
+    // every island sends to all the others
+    FullyConnected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MigrantSelection {
+    // the shortest chromossomes of the island
+    #[default]
+    Best,
+    // uniformly
+    Random,
+    // the shorter of two random chromossomes
+    Tournament,
+}
+
+impl MigrationTopology {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Ring => "ring",
+            Self::FullyConnected => "fully_connected",
+        }
+    }
+
+    // Islands whose emigrants reach the island
+    fn get_sources(&self, island: usize, islands: usize) -> Vec<usize> {
+        match self {
+            Self::Ring => vec![(island + islands - 1) % islands],
+            Self::FullyConnected => (0..islands).filter(|&source| source != island).collect(),
+        }
+    }
+}
+
+impl MigrantSelection {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Best => "best",
+            Self::Random => "random",
+            Self::Tournament => "tournament",
+        }
+    }
+}
+
+// Counters of one island, listed in the metadata and written to islands.csv
+#[derive(Debug, Clone, Serialize)]
+pub struct IslandStats {
+    island: usize,
+    seed: u64,
+    generations: u32,
+    evaluations: u64,
+    best: f64,
+    mean: f64,
+    emigrants: usize,
+    immigrants: usize,
+    // immigrants that took the place of a longer chromossome
+    accepted: usize,
+    // immigrants that became the best of the island
+    new_best: usize,
+}
+
+impl IslandStats {
+    pub fn describe(&self) -> String {
+        format!(
+            "island {}: best {}, mean {:.4}, generations {}, evaluations {}, sent {}, received {}, accepted {}, new best {}, seed {}",
+            self.island,
+            self.best,
+            self.mean,
+            self.generations,
+            self.evaluations,
+            self.emigrants,
+            self.immigrants,
+            self.accepted,
+            self.new_best,
+            self.seed
+        )
+    }
+}
+
+// One row per island: island,seed,generations,evaluations,best,mean,emigrants,immigrants,accepted,new_best
+pub fn write_island_stats_csv(path: &str, stats: &[IslandStats]) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::from_path(path)?;
+    for island in stats {
+        writer.serialize(island)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// One population of the model, evolved a migration interval at a time
+struct Island {
+    genetic: Genetic,
+    population: Vec<Chromossome>,
+    evolution: Evolution,
+    seed: u64,
+    // picks the random and tournament emigrants
+    rng: ChaCha12Rng,
+    emigrants: usize,
+    immigrants: usize,
+    accepted: usize,
+    new_best: usize,
+}
+
+impl Island {
+    fn new(mut genetic: Genetic, criteria: &StoppingCriteria, seed: u64) -> Self {
+        genetic.set_stopping_criteria(criteria);
+        genetic.get_termination().start();
+        let population = genetic.create_first_population();
+        let cities_len = population[0].get_path().len();
+        Island {
+            genetic,
+            population,
+            evolution: Evolution::new(cities_len),
+            seed,
+            // a stream apart from the one of the genetic algorithm
+            rng: ChaCha12Rng::seed_from_u64(!seed),
+            emigrants: 0,
+            immigrants: 0,
+            accepted: 0,
+            new_best: 0,
+        }
+    }
+
+    fn evolve(&mut self, generations: u32) {
+        let population = std::mem::take(&mut self.population);
+        self.population = self
+            .genetic
+            .evolve(population, &mut self.evolution, generations);
+    }
+
+    // Its breakpoint or a stopping criterion was reached
+    fn is_finished(&mut self) -> bool {
+        self.genetic.has_converged(&self.evolution) || self.genetic.get_termination().is_stopped()
+    }
+
+    fn get_best(&self) -> &Chromossome {
+        self.population
+            .iter()
+            .min_by(|a, b| a.get_distance().total_cmp(b.get_distance()))
+            .unwrap()
+    }
+
+    fn get_evaluations(&mut self) -> u64 {
+        self.genetic.get_termination().get_evaluations()
+    }
+
+    // Copies, the emigrants also stay in the island
+    fn select_emigrants(&mut self, count: usize, selection: MigrantSelection) -> Vec<Chromossome> {
+        let mut candidates: Vec<usize> = (0..self.population.len()).collect();
+        let mut emigrants = Vec::with_capacity(count);
+        while emigrants.len() < count && !candidates.is_empty() {
+            let distance = |position: usize| *self.population[candidates[position]].get_distance();
+            let position = match selection {
+                MigrantSelection::Best => (0..candidates.len())
+                    .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+                    .unwrap(),
+                MigrantSelection::Random => self.rng.random_range(0..candidates.len()),
+                MigrantSelection::Tournament => {
+                    let a = self.rng.random_range(0..candidates.len());
+                    let b = self.rng.random_range(0..candidates.len());
+                    if distance(a) <= distance(b) { a } else { b }
+                }
+            };
+            emigrants.push(self.population[candidates.swap_remove(position)].clone());
+        }
+        self.emigrants += emigrants.len();
+        emigrants
+    }
+
+    // Every immigrant takes the place of the longest chromossome when it is shorter and its
+    // path is not in the population yet
+    fn receive_immigrants(&mut self, immigrants: Vec<Chromossome>) {
+        for immigrant in immigrants {
+            self.immigrants += 1;
+            if self
+                .population
+                .iter()
+                .any(|chromossome| chromossome.get_path() == immigrant.get_path())
+            {
+                continue;
+            }
+            let worst = (0..self.population.len())
+                .max_by(|&a, &b| {
+                    self.population[a]
+                        .get_distance()
+                        .total_cmp(self.population[b].get_distance())
+                })
+                .unwrap();
+            if immigrant.get_distance() >= self.population[worst].get_distance() {
+                continue;
+            }
+            if immigrant.get_distance() < self.get_best().get_distance() {
+                self.new_best += 1;
+                self.evolution.restart_stagnation();
+            }
+            self.population[worst] = immigrant;
+            self.accepted += 1;
+        }
+    }
+
+    fn get_stats(&mut self, island: usize) -> IslandStats {
+        IslandStats {
+            island: island + 1,
+            seed: self.seed,
+            generations: self.genetic.get_generations(),
+            evaluations: self.get_evaluations(),
+            best: *self.get_best().get_distance(),
+            mean: self
+                .population
+                .iter()
+                .map(|chromossome| chromossome.get_distance())
+                .sum::<f64>()
+                / self.population.len() as f64,
+            emigrants: self.emigrants,
+            immigrants: self.immigrants,
+            accepted: self.accepted,
+            new_best: self.new_best,
+        }
+    }
+}
+
+// Island model of the genetic algorithm (G.islands above 1): every island is a Genetic with
+// its own population and seed, evolved on its own thread over the one distance matrix. After
+// every migration interval the islands send copies of some chromossomes to their neighbours in
+// the topology, where they replace the longest ones. The migrations are synchronous, so the
+// same seed gives the same run.
+pub struct IslandModel {
+    distance_matrix: Arc<[f64]>,
+    cities: Vec<City>,
+    metric: DistanceMetric,
+    parameters: GeneticParameters,
+    seed: u64,
+    termination: Termination,
+    // given to the first island
+    initial_tour: Option<Vec<u16>>,
+}
+
+impl IslandModel {
+    pub fn new(
+        cities: &[City],
+        distance_matrix: &[f64],
+        metric: &DistanceMetric,
+        parameters: &GeneticParameters,
+        seed: u64,
+    ) -> Self {
+        IslandModel {
+            distance_matrix: Arc::from(distance_matrix),
+            cities: cities.to_vec(),
+            metric: metric.clone(),
+            parameters: parameters.resolve(cities.len()),
+            seed,
+            termination: Termination::default(),
+            initial_tour: None,
+        }
+    }
+
+    // Island i is seeded with the seed + i, the first one starts as a single population run of
+    // the same seed. The islands are built in parallel as well.
+    fn create_islands(&mut self) -> Vec<Island> {
+        // the islands stop themselves on the time limit and the target, the evaluations and
+        // the stagnation are checked between the migrations
+        let criteria = self.termination.get_criteria();
+        let island_criteria = StoppingCriteria {
+            max_time: criteria
+                .max_time
+                .map(|max| max.saturating_sub(self.termination.get_elapsed())),
+            target_distance: criteria.target_distance,
+            ..StoppingCriteria::default()
+        };
+        let mut initial_tour = self.initial_tour.take();
+        let (cities, distance_matrix, metric, parameters) = (
+            &self.cities,
+            &self.distance_matrix,
+            &self.metric,
+            &self.parameters,
+        );
+        let island_criteria = &island_criteria;
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..parameters.islands)
+                .map(|i| {
+                    let seed = self.seed.wrapping_add(i as u64);
+                    let tour = initial_tour.take();
+                    scope.spawn(move || {
+                        let mut genetic = Genetic::with_shared_matrix(
+                            cities,
+                            Arc::clone(distance_matrix),
+                            metric,
+                            parameters,
+                            seed,
+                        );
+                        if let Some(tour) = &tour {
+                            genetic.set_initial_tour(tour);
+                        }
+                        Island::new(genetic, island_criteria, seed)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    // Passes the work and the best tour of the islands to the termination, returns the best
+    // distance
+    fn record_progress(&mut self, islands: &mut [Island], evaluations: &mut u64) -> f64 {
+        let total: u64 = islands.iter_mut().map(Island::get_evaluations).sum();
+        self.termination.add_evaluations(total - *evaluations);
+        *evaluations = total;
+        let best = islands
+            .iter()
+            .map(Island::get_best)
+            .min_by(|a, b| a.get_distance().total_cmp(b.get_distance()))
+            .unwrap();
+        self.termination
+            .report_best(*best.get_distance(), best.get_path());
+        *best.get_distance()
+    }
+
+    // The emigrants of every island are chosen before any of them arrives
+    fn migrate(&self, islands: &mut [Island]) {
+        if self.parameters.migrants == 0 {
+            return;
+        }
+        let emigrants: Vec<Vec<Chromossome>> = islands
+            .iter_mut()
+            .map(|island| {
+                island.select_emigrants(self.parameters.migrants, self.parameters.migrant_selection)
+            })
+            .collect();
+        let islands_len = islands.len();
+        for (i, island) in islands.iter_mut().enumerate() {
+            let immigrants = self
+                .parameters
+                .migration_topology
+                .get_sources(i, islands_len)
+                .into_iter()
+                .flat_map(|source| emigrants[source].clone())
+                .collect();
+            island.receive_immigrants(immigrants);
+        }
+    }
+}
+
+impl Algorithm for IslandModel {
+    fn execute(&mut self) -> ExecuteResponse {
+        println!("Execute Genetic");
+        self.termination.start();
+        let mut islands = self.create_islands();
+        let mut evaluations = 0;
+        self.record_progress(&mut islands, &mut evaluations);
+
+        let interval = self.parameters.migration_interval;
+        loop {
+            thread::scope(|scope| {
+                for island in islands.iter_mut() {
+                    if !island.is_finished() {
+                        scope.spawn(move || island.evolve(interval));
+                    }
+                }
+            });
+            let best_distance = self.record_progress(&mut islands, &mut evaluations);
+            if self.termination.next_iteration(best_distance) {
+                break;
+            }
+            if islands.iter_mut().all(|island| island.is_finished()) {
+                break;
+            }
+            self.migrate(&mut islands);
+        }
+
+        let island_stats: Vec<IslandStats> = islands
+            .iter_mut()
+            .enumerate()
+            .map(|(i, island)| island.get_stats(i))
+            .collect();
+        let operator_stats = merge_operator_stats(
+            &islands
+                .iter()
+                .map(|island| island.genetic.get_operator_stats())
+                .collect::<Vec<_>>(),
+        );
+        let population_size = islands[0].population.len();
+        let metadata = format!(
+            "Islands: {}\nMigration: {} every {} generations, {} {} migrants\nPopulation Size: {}\nGenerations: {}\nCrossover: {}\nMutation Selection: {:?}\nIsland Statistics:\n{}\nOperators:\n{}\nParameters: {}\n",
+            islands.len(),
+            self.parameters.migration_topology.get_name(),
+            self.parameters.migration_interval,
+            self.parameters.migrants,
+            self.parameters.migrant_selection.get_name(),
+            population_size,
+            island_stats
+                .iter()
+                .map(|stats| stats.generations as u64)
+                .sum::<u64>(),
+            islands[0].genetic.describe_crossover(population_size),
+            self.parameters.mutation_selection,
+            island_stats
+                .iter()
+                .map(|stats| format!("  {}", stats.describe()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            operator_stats
+                .iter()
+                .map(|stats| format!("  {}", stats.describe()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            describe_parameters(&self.parameters)
+        );
+
+        let best = islands
+            .iter()
+            .map(Island::get_best)
+            .min_by(|a, b| a.get_distance().total_cmp(b.get_distance()))
+            .unwrap();
+        let mut response = ExecuteResponse::new(
+            islands[0].genetic.get_initial_path().clone(),
+            best.get_path().clone(),
+            *best.get_distance(),
+            self.termination.get_elapsed(),
+            metadata,
+        );
+        response.set_operator_stats(operator_stats);
+        response.set_island_stats(island_stats);
+        response.set_termination(&self.termination);
+        response
+    }
+
+    fn get_termination(&mut self) -> &mut Termination {
+        &mut self.termination
+    }
+
+    fn set_initial_tour(&mut self, tour: &[u16]) {
+        self.initial_tour = Some(tour.to_vec());
+    }
+
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        Err(format!(
+            "{}: runs with G.islands above 1 write no checkpoints and cannot be resumed",
+            path
+        )
+        .into())
+    }
+}
@@ -0,0 +1,792 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::stopping_criteria::Termination;
+
+// A move must gain more than this, float noise would make the search cycle otherwise
+const TOLERANCE: f64 = 1e-9;
+// Longest segment moved by Or-opt
+const OR_OPT_MAX_SEGMENT: usize = 3;
+// Longest chain of 2-opt moves in a Lin-Kernighan step
+const LK_MAX_DEPTH: usize = 8;
+// Candidate cities of every move, for the algorithms that do not choose
+pub const DEFAULT_NEIGHBOURS: usize = 10;
+// Cities looked at between two checks of the stopping criteria
+const CHECK_INTERVAL: usize = 256;
+
+// Each method also runs the ones before it, until none of them improves the tour
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalSearchMethod {
+    #[default]
+    None,
+    // 2-opt on the neighbour lists, with don't-look bits
+    TwoOpt,
+    // segments of up to 3 cities moved next to one of their neighbours
+    OrOpt,
+    // exchange of two consecutive segments, the 3-opt move that keeps their direction
+    ThreeOpt,
+    // chains of 2-opt moves from every city (Lin-Kernighan style), 2-opt on the ATSP
+    LinKernighan,
+}
+
+impl LocalSearchMethod {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::None => "none",
+            Self::TwoOpt => "two_opt",
+            Self::OrOpt => "or_opt",
+            Self::ThreeOpt => "three_opt",
+            Self::LinKernighan => "lin_kernighan",
+        }
+    }
+}
+
+// Improves tours of one instance. The neighbour lists are built once, the distance matrix
+// is passed on every call as the algorithms already own it.
+#[derive(Debug, Clone)]
+pub struct LocalSearch {
+    method: LocalSearchMethod,
+    n: usize,
+    symmetric: bool,
+    // closest cities first, by min(d(i, j), d(j, i))
+    neighbours: Vec<Vec<usize>>,
+}
+
+impl LocalSearch {
+    pub fn new(
+        distance_matrix: &[f64],
+        n: usize,
+        method: LocalSearchMethod,
+        neighbours_len: usize,
+    ) -> Self {
+        let symmetric = (0..n)
+            .all(|i| (0..i).all(|j| distance_matrix[i * n + j] == distance_matrix[j * n + i]));
+        let neighbours = if method == LocalSearchMethod::None {
+            vec![]
+        } else {
+            Self::create_neighbour_lists(distance_matrix, n, neighbours_len)
+        };
+        LocalSearch {
+            method,
+            n,
+            symmetric,
+            neighbours,
+        }
+    }
+
+    fn create_neighbour_lists(distance_matrix: &[f64], n: usize, len: usize) -> Vec<Vec<usize>> {
+        let len = len.min(n.saturating_sub(1));
+        let cost = |i: usize, j: usize| distance_matrix[i * n + j].min(distance_matrix[j * n + i]);
+        (0..n)
+            .map(|i| {
+                let mut others: Vec<usize> = (0..n).filter(|&j| j != i).collect();
+                if len < others.len() {
+                    others.select_nth_unstable_by(len, |&a, &b| cost(i, a).total_cmp(&cost(i, b)));
+                    others.truncate(len);
+                }
+                others.sort_by(|&a, &b| cost(i, a).total_cmp(&cost(i, b)));
+                others
+            })
+            .collect()
+    }
+
+    pub fn get_method(&self) -> LocalSearchMethod {
+        self.method
+    }
+
+    // Improves the closed tour in place and returns its distance. A stopping criterion ends
+    // the search with the tour reached so far.
+    pub fn improve(
+        &self,
+        distance_matrix: &[f64],
+        path: &mut [u16],
+        termination: &mut Termination,
+    ) -> f64 {
+        let mut tour = Tour::new(distance_matrix, self.n, self.symmetric, path);
+        let mut stop = StopCheck::new(termination);
+        // smaller tours have no move that changes them
+        if self.method != LocalSearchMethod::None && self.n >= 5 {
+            while !stop.stopped {
+                if self.method == LocalSearchMethod::LinKernighan && self.symmetric {
+                    self.lin_kernighan(&mut tour, &mut stop);
+                } else {
+                    self.two_opt(&mut tour, &mut stop);
+                }
+                let mut improved = false;
+                if self.method != LocalSearchMethod::TwoOpt {
+                    improved |= self.or_opt(&mut tour, &mut stop);
+                }
+                if matches!(
+                    self.method,
+                    LocalSearchMethod::ThreeOpt | LocalSearchMethod::LinKernighan
+                ) {
+                    improved |= self.three_opt(&mut tour, &mut stop);
+                }
+                if !improved {
+                    break;
+                }
+            }
+        }
+        path.copy_from_slice(&tour.path);
+        tour.get_distance()
+    }
+
+    // New edge a -> c for every neighbour c of a, by reversing the path between them
+    fn two_opt(&self, tour: &mut Tour, stop: &mut StopCheck) -> bool {
+        let n = self.n;
+        let mut queue = ActiveCities::new(n);
+        let mut improved = false;
+        while let Some(a) = queue.pop() {
+            if stop.should_stop(tour) {
+                break;
+            }
+            for &c in &self.neighbours[a] {
+                let (i, j) = (tour.position[a], tour.position[c]);
+                // a -> c reverses (i, j], c -> a reverses (j, i]
+                let (x, y) = if j > i + 1 {
+                    (i + 1, j)
+                } else if i > j + 1 {
+                    (j + 1, i)
+                } else {
+                    continue;
+                };
+                if y - x + 1 > n - 2 {
+                    continue;
+                }
+                if tour.get_reversal_gain(x, y) > TOLERANCE {
+                    let ends = [tour.at(x + n - 1), tour.at(x), tour.at(y), tour.at(y + 1)];
+                    tour.reverse(x, y);
+                    queue.push_all(&ends);
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        improved
+    }
+
+    // Moves a segment of 1 to 3 cities, in either direction, between a neighbour of one of
+    // its ends and the city after it
+    fn or_opt(&self, tour: &mut Tour, stop: &mut StopCheck) -> bool {
+        let n = self.n;
+        let mut queue = ActiveCities::new(n);
+        let mut improved = false;
+        'cities: while let Some(first) = queue.pop() {
+            if stop.should_stop(tour) {
+                break;
+            }
+            for len in 1..=OR_OPT_MAX_SEGMENT.min(n - 3) {
+                let start = tour.position[first];
+                let last = tour.at(start + len - 1);
+                let (before, after) = (tour.at(start + n - 1), tour.at(start + len));
+                let removal_gain =
+                    tour.d(before, first) + tour.d(last, after) - tour.d(before, after);
+                if removal_gain <= TOLERANCE {
+                    continue;
+                }
+                let inside = |city: usize| (tour.position[city] + n - start) % n < len;
+                // the segment walked backwards costs this much more, only on the ATSP
+                let reversal_cost = (0..len - 1)
+                    .map(|t| {
+                        let (u, v) = (tour.at(start + t), tour.at(start + t + 1));
+                        tour.d(v, u) - tour.d(u, v)
+                    })
+                    .sum::<f64>();
+                let candidates = self.neighbours[first].iter().copied().chain(
+                    self.neighbours[last]
+                        .iter()
+                        .map(|&city| tour.at(tour.position[city] + n - 1)),
+                );
+                for c in candidates {
+                    let c_next = tour.at(tour.position[c] + 1);
+                    if inside(c) || inside(c_next) {
+                        continue;
+                    }
+                    let forward = tour.d(c, first) + tour.d(last, c_next) - tour.d(c, c_next);
+                    let backward =
+                        tour.d(c, last) + tour.d(first, c_next) - tour.d(c, c_next) + reversal_cost;
+                    let reversed = backward < forward;
+                    if removal_gain - forward.min(backward) > TOLERANCE {
+                        let ends = [before, after, first, last, c, c_next];
+                        tour.move_segment(start, len, c, reversed);
+                        queue.push_all(&ends);
+                        improved = true;
+                        continue 'cities;
+                    }
+                }
+            }
+        }
+        improved
+    }
+
+    // a -> a' ... b -> b' ... c -> c' becomes a -> b' ... c -> a' ... b -> c': the segments
+    // a'..b and b'..c swap places, b' a neighbour of a and c one of a'
+    fn three_opt(&self, tour: &mut Tour, stop: &mut StopCheck) -> bool {
+        let n = self.n;
+        let mut queue = ActiveCities::new(n);
+        let mut improved = false;
+        'cities: while let Some(a) = queue.pop() {
+            if stop.should_stop(tour) {
+                break;
+            }
+            let i = tour.position[a];
+            let a_next = tour.at(i + 1);
+            for &b_next in &self.neighbours[a] {
+                let j = (tour.position[b_next] + n - i) % n;
+                if j < 2 {
+                    continue;
+                }
+                let b = tour.at(i + j - 1);
+                let partial = tour.d(a, a_next) + tour.d(b, b_next) - tour.d(a, b_next);
+                if partial <= TOLERANCE {
+                    continue;
+                }
+                for &c in &self.neighbours[a_next] {
+                    let k = (tour.position[c] + n - i) % n;
+                    if k < j {
+                        continue;
+                    }
+                    let c_next = tour.at(i + k + 1);
+                    let gain = partial + tour.d(c, c_next) - tour.d(c, a_next) - tour.d(b, c_next);
+                    if gain > TOLERANCE {
+                        let ends = [a, a_next, b, b_next, c, c_next];
+                        tour.exchange_segments(i, j, k);
+                        queue.push_all(&ends);
+                        improved = true;
+                        continue 'cities;
+                    }
+                }
+            }
+        }
+        improved
+    }
+
+    // From t1, removes (t1, t2) and keeps chaining 2-opt moves that add (t2, t3) and remove
+    // (t3, t4), the next move starting from t4. The chain is cut back to its best closed tour.
+    fn lin_kernighan(&self, tour: &mut Tour, stop: &mut StopCheck) -> bool {
+        let mut queue = ActiveCities::new(self.n);
+        let mut improved = false;
+        while let Some(t1) = queue.pop() {
+            if stop.should_stop(tour) {
+                break;
+            }
+            for first in [tour.next(t1), tour.prev(t1)] {
+                if let Some(touched) = self.lin_kernighan_step(tour, t1, first) {
+                    queue.push_all(&touched);
+                    improved = true;
+                    break;
+                }
+            }
+        }
+        improved
+    }
+
+    fn lin_kernighan_step(&self, tour: &mut Tour, t1: usize, first: usize) -> Option<Vec<usize>> {
+        let mut t2 = first;
+        let mut gain = tour.d(t1, t2);
+        let mut moves = vec![];
+        let mut added = vec![];
+        let mut touched = vec![t1, t2];
+        let (mut best_gain, mut best_len) = (TOLERANCE, 0);
+        for _ in 0..LK_MAX_DEPTH {
+            let forward = tour.next(t1) == t2;
+            let mut choice = None;
+            for &t3 in &self.neighbours[t2] {
+                if gain - tour.d(t2, t3) <= TOLERANCE {
+                    break;
+                }
+                if t3 == t1 || t3 == tour.next(t2) || t3 == tour.prev(t2) {
+                    continue;
+                }
+                let t4 = if forward {
+                    tour.prev(t3)
+                } else {
+                    tour.next(t3)
+                };
+                if added.contains(&(t3.min(t4), t3.max(t4))) {
+                    continue;
+                }
+                let value = tour.d(t3, t4) - tour.d(t2, t3);
+                if choice.is_none_or(|(_, _, best)| value > best) {
+                    choice = Some((t3, t4, value));
+                }
+            }
+            let Some((t3, t4, value)) = choice else {
+                break;
+            };
+            gain += value;
+            let (from, to) = if forward {
+                (tour.position[t2], tour.position[t4])
+            } else {
+                (tour.position[t4], tour.position[t2])
+            };
+            moves.push(tour.reverse_cyclic(from, to));
+            added.push((t2.min(t3), t2.max(t3)));
+            touched.extend([t3, t4]);
+            let closed_gain = gain - tour.d(t4, t1);
+            if closed_gain > best_gain {
+                (best_gain, best_len) = (closed_gain, moves.len());
+            }
+            t2 = t4;
+        }
+        // reversing the same positions again undoes a move
+        while moves.len() > best_len {
+            let (from, to) = moves.pop().unwrap();
+            tour.reverse_cyclic(from, to);
+        }
+        (best_len > 0).then_some(touched)
+    }
+}
+
+// Checks the stopping criteria of the run every CHECK_INTERVAL cities, a run already
+// stopped does not search at all
+struct StopCheck<'a> {
+    termination: &'a mut Termination,
+    count: usize,
+    stopped: bool,
+}
+
+impl<'a> StopCheck<'a> {
+    fn new(termination: &'a mut Termination) -> Self {
+        let stopped = termination.is_stopped();
+        StopCheck {
+            termination,
+            count: 0,
+            stopped,
+        }
+    }
+
+    fn should_stop(&mut self, tour: &Tour) -> bool {
+        self.count += 1;
+        if !self.stopped && self.count.is_multiple_of(CHECK_INTERVAL) {
+            self.stopped = self.termination.should_stop(tour.get_distance());
+        }
+        self.stopped
+    }
+}
+
+// Queue of the cities whose edges changed, the others are not looked at again
+struct ActiveCities {
+    queue: VecDeque<usize>,
+    queued: Vec<bool>,
+}
+
+impl ActiveCities {
+    fn new(n: usize) -> Self {
+        ActiveCities {
+            queue: (0..n).collect(),
+            queued: vec![true; n],
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        let city = self.queue.pop_front()?;
+        self.queued[city] = false;
+        Some(city)
+    }
+
+    fn push_all(&mut self, cities: &[usize]) {
+        for &city in cities {
+            if !self.queued[city] {
+                self.queued[city] = true;
+                self.queue.push_back(city);
+            }
+        }
+    }
+}
+
+// Closed tour with the position of every city. On the ATSP it keeps the cost of the path
+// walked both ways up to every position, to cost reversals in constant time.
+struct Tour<'a> {
+    distance_matrix: &'a [f64],
+    n: usize,
+    symmetric: bool,
+    path: Vec<u16>,
+    position: Vec<usize>,
+    forward: Vec<f64>,
+    backward: Vec<f64>,
+}
+
+impl<'a> Tour<'a> {
+    fn new(distance_matrix: &'a [f64], n: usize, symmetric: bool, path: &[u16]) -> Self {
+        let mut tour = Tour {
+            distance_matrix,
+            n,
+            symmetric,
+            path: path.to_vec(),
+            position: vec![0; n],
+            forward: vec![0.0; n],
+            backward: vec![0.0; n],
+        };
+        tour.refresh();
+        tour
+    }
+
+    fn refresh(&mut self) {
+        for (index, &city) in self.path.iter().enumerate() {
+            self.position[city as usize] = index;
+        }
+        if !self.symmetric {
+            for index in 1..self.n {
+                let (u, v) = (self.path[index - 1] as usize, self.path[index] as usize);
+                self.forward[index] = self.forward[index - 1] + self.d(u, v);
+                self.backward[index] = self.backward[index - 1] + self.d(v, u);
+            }
+        }
+    }
+
+    fn d(&self, from: usize, to: usize) -> f64 {
+        self.distance_matrix[from * self.n + to]
+    }
+
+    // City at a position, past the end wraps around
+    fn at(&self, index: usize) -> usize {
+        self.path[index % self.n] as usize
+    }
+
+    fn next(&self, city: usize) -> usize {
+        self.at(self.position[city] + 1)
+    }
+
+    fn prev(&self, city: usize) -> usize {
+        self.at(self.position[city] + self.n - 1)
+    }
+
+    fn get_distance(&self) -> f64 {
+        (0..self.n)
+            .map(|index| self.d(self.at(index), self.at(index + 1)))
+            .sum()
+    }
+
+    // Gain of reversing the positions x..=y, which leave at least two cities out
+    fn get_reversal_gain(&self, x: usize, y: usize) -> f64 {
+        let n = self.n;
+        let (before, first, last, after) =
+            (self.at(x + n - 1), self.at(x), self.at(y), self.at(y + 1));
+        let inside = if self.symmetric {
+            0.0
+        } else {
+            (self.backward[y] - self.backward[x]) - (self.forward[y] - self.forward[x])
+        };
+        self.d(before, first) + self.d(last, after)
+            - self.d(before, last)
+            - self.d(first, after)
+            - inside
+    }
+
+    fn reverse(&mut self, x: usize, y: usize) {
+        self.path[x..=y].reverse();
+        for index in x..=y {
+            self.position[self.path[index] as usize] = index;
+        }
+        if !self.symmetric {
+            self.refresh();
+        }
+    }
+
+    // Reverses the positions from `from` forward to `to`, around the end if needed. The
+    // complement is reversed instead when shorter, the same tour on a symmetric matrix.
+    // Returns the positions reversed.
+    fn reverse_cyclic(&mut self, from: usize, to: usize) -> (usize, usize) {
+        let n = self.n;
+        let len = (to + n - from) % n + 1;
+        let (from, to, len) = if 2 * len > n && len < n {
+            ((to + 1) % n, (from + n - 1) % n, n - len)
+        } else {
+            (from, to, len)
+        };
+        let (mut i, mut j) = (from, to);
+        for _ in 0..len / 2 {
+            self.path.swap(i, j);
+            self.position[self.path[i] as usize] = i;
+            self.position[self.path[j] as usize] = j;
+            i = (i + 1) % n;
+            j = (j + n - 1) % n;
+        }
+        (from, to)
+    }
+
+    // Takes the `len` cities from position `start` out and puts them back after `city`
+    fn move_segment(&mut self, start: usize, len: usize, city: usize, reversed: bool) {
+        let n = self.n;
+        let mut segment: Vec<u16> = (0..len).map(|t| self.path[(start + t) % n]).collect();
+        if reversed {
+            segment.reverse();
+        }
+        let mut path = Vec::with_capacity(n);
+        for t in len..n {
+            let current = self.path[(start + t) % n];
+            path.push(current);
+            if current as usize == city {
+                path.extend_from_slice(&segment);
+            }
+        }
+        self.path = path;
+        self.refresh();
+    }
+
+    // From the city at position i, the segments at offsets 1..j and j..=k swap places
+    fn exchange_segments(&mut self, i: usize, j: usize, k: usize) {
+        let n = self.n;
+        let offsets = (0..1).chain(j..=k).chain(1..j).chain(k + 1..n);
+        self.path = offsets.map(|offset| self.path[(i + offset) % n]).collect();
+        self.refresh();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::stopping_criteria::StoppingCriteria;
+    use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+    const SIZES: [usize; 3] = [6, 9, 14];
+    const TOURS: u64 = 20;
+
+    // Rounded Euclidean distances of random points, a random detour added one way when
+    // asymmetric
+    fn create_matrix(n: usize, symmetric: bool, seed: u64) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let points: Vec<(f64, f64)> = (0..n)
+            .map(|_| (rng.random_range(0.0..100.0), rng.random_range(0.0..100.0)))
+            .collect();
+        let mut matrix = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..n {
+                if i != j {
+                    let (dx, dy) = (points[i].0 - points[j].0, points[i].1 - points[j].1);
+                    matrix[i * n + j] = (dx * dx + dy * dy).sqrt().round();
+                }
+            }
+        }
+        if !symmetric {
+            for value in matrix.iter_mut().filter(|value| **value > 0.0) {
+                *value += rng.random_range(0..30) as f64;
+            }
+        }
+        matrix
+    }
+
+    fn create_path(n: usize, seed: u64) -> Vec<u16> {
+        let mut path: Vec<u16> = (0..n as u16).collect();
+        path.shuffle(&mut StdRng::seed_from_u64(seed));
+        path
+    }
+
+    // Length of the closed tour, from the matrix alone
+    fn tour_length(matrix: &[f64], n: usize, path: &[u16]) -> f64 {
+        (0..n)
+            .map(|i| matrix[path[i] as usize * n + path[(i + 1) % n] as usize])
+            .sum()
+    }
+
+    fn assert_permutation(path: &[u16], n: usize) {
+        let mut sorted = path.to_vec();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..n as u16).collect::<Vec<_>>());
+    }
+
+    // The positions and prefix costs kept by the tour match the ones of its path
+    fn assert_consistent(tour: &Tour) {
+        let fresh = Tour::new(tour.distance_matrix, tour.n, tour.symmetric, &tour.path);
+        assert_eq!(tour.position, fresh.position);
+        assert_eq!(tour.forward, fresh.forward);
+        assert_eq!(tour.backward, fresh.backward);
+    }
+
+    // Runs a move on random tours of every size and matrix, and checks the result
+    fn check_move(
+        method: LocalSearchMethod,
+        apply: fn(&LocalSearch, &mut Tour, &mut StopCheck) -> bool,
+        symmetries: &[bool],
+    ) {
+        for n in SIZES {
+            for &symmetric in symmetries {
+                let matrix = create_matrix(n, symmetric, n as u64);
+                let search = LocalSearch::new(&matrix, n, method, DEFAULT_NEIGHBOURS);
+                assert_eq!(search.symmetric, symmetric);
+                for seed in 0..TOURS {
+                    let path = create_path(n, seed);
+                    let before = tour_length(&matrix, n, &path);
+                    let mut tour = Tour::new(&matrix, n, symmetric, &path);
+                    let mut termination = Termination::default();
+                    let mut stop = StopCheck::new(&mut termination);
+                    let improved = apply(&search, &mut tour, &mut stop);
+                    let after = tour_length(&matrix, n, &tour.path);
+                    assert_permutation(&tour.path, n);
+                    assert_consistent(&tour);
+                    assert!(after <= before + TOLERANCE, "{} > {}", after, before);
+                    if !improved {
+                        assert_eq!(tour.path, path);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reversal_gain_is_the_length_difference() {
+        for symmetric in [true, false] {
+            let n = 9;
+            let matrix = create_matrix(n, symmetric, 1);
+            let path = create_path(n, 1);
+            let before = tour_length(&matrix, n, &path);
+            let tour = Tour::new(&matrix, n, symmetric, &path);
+            for x in 0..n {
+                // at least two cities are left out
+                for y in x + 1..(x + n - 2).min(n) {
+                    let mut reversed = Tour::new(&matrix, n, symmetric, &path);
+                    reversed.reverse(x, y);
+                    let after = tour_length(&matrix, n, &reversed.path);
+                    let gain = tour.get_reversal_gain(x, y);
+                    assert!((gain - (before - after)).abs() < TOLERANCE, "{} {}", x, y);
+                    assert_consistent(&reversed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prefix_costs_walk_the_path_both_ways() {
+        let n = 6;
+        let matrix = create_matrix(n, false, 2);
+        let path = create_path(n, 2);
+        let tour = Tour::new(&matrix, n, false, &path);
+        for index in 0..n {
+            let forward: f64 = (1..=index)
+                .map(|t| matrix[path[t - 1] as usize * n + path[t] as usize])
+                .sum();
+            let backward: f64 = (1..=index)
+                .map(|t| matrix[path[t] as usize * n + path[t - 1] as usize])
+                .sum();
+            assert_eq!(tour.forward[index], forward);
+            assert_eq!(tour.backward[index], backward);
+        }
+        assert_eq!(tour.get_distance(), tour_length(&matrix, n, &path));
+    }
+
+    #[test]
+    fn two_opt_never_worsens() {
+        check_move(
+            LocalSearchMethod::TwoOpt,
+            LocalSearch::two_opt,
+            &[true, false],
+        );
+    }
+
+    #[test]
+    fn or_opt_never_worsens() {
+        check_move(
+            LocalSearchMethod::OrOpt,
+            LocalSearch::or_opt,
+            &[true, false],
+        );
+    }
+
+    #[test]
+    fn three_opt_never_worsens() {
+        check_move(
+            LocalSearchMethod::ThreeOpt,
+            LocalSearch::three_opt,
+            &[true, false],
+        );
+    }
+
+    #[test]
+    fn lin_kernighan_never_worsens() {
+        // the ATSP runs 2-opt instead
+        check_move(
+            LocalSearchMethod::LinKernighan,
+            LocalSearch::lin_kernighan,
+            &[true],
+        );
+    }
+
+    #[test]
+    fn lin_kernighan_step_keeps_its_best_prefix() {
+        let n = 14;
+        let matrix = create_matrix(n, true, 3);
+        let search = LocalSearch::new(&matrix, n, LocalSearchMethod::LinKernighan, 5);
+        for seed in 0..TOURS {
+            let path = create_path(n, seed);
+            let before = tour_length(&matrix, n, &path);
+            for t1 in 0..n {
+                let mut tour = Tour::new(&matrix, n, true, &path);
+                let first = tour.next(t1);
+                let touched = search.lin_kernighan_step(&mut tour, t1, first);
+                let after = tour_length(&matrix, n, &tour.path);
+                assert_permutation(&tour.path, n);
+                assert_consistent(&tour);
+                match touched {
+                    Some(_) => assert!(after < before),
+                    // the whole chain is undone
+                    None => assert_eq!(tour.path, path),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn improve_reports_the_tour_length() {
+        let methods = [
+            LocalSearchMethod::None,
+            LocalSearchMethod::TwoOpt,
+            LocalSearchMethod::OrOpt,
+            LocalSearchMethod::ThreeOpt,
+            LocalSearchMethod::LinKernighan,
+        ];
+        for n in SIZES {
+            for symmetric in [true, false] {
+                let matrix = create_matrix(n, symmetric, n as u64 + 100);
+                for method in methods {
+                    let search = LocalSearch::new(&matrix, n, method, DEFAULT_NEIGHBOURS);
+                    for seed in 0..TOURS {
+                        let mut path = create_path(n, seed);
+                        let before = tour_length(&matrix, n, &path);
+                        let distance =
+                            search.improve(&matrix, &mut path, &mut Termination::default());
+                        assert_permutation(&path, n);
+                        assert!((distance - tour_length(&matrix, n, &path)).abs() < TOLERANCE);
+                        assert!(distance <= before + TOLERANCE);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stopped_runs_keep_their_tour() {
+        let n = 14;
+        let matrix = create_matrix(n, true, 5);
+        let search = LocalSearch::new(&matrix, n, LocalSearchMethod::LinKernighan, 5);
+        let mut termination = Termination::new(&StoppingCriteria {
+            max_evaluations: Some(0),
+            ..StoppingCriteria::default()
+        });
+        assert!(termination.should_stop(f64::MAX));
+        let mut path = create_path(n, 5);
+        let before = path.clone();
+        let distance = search.improve(&matrix, &mut path, &mut termination);
+        assert_eq!(path, before);
+        assert_eq!(distance, tour_length(&matrix, n, &path));
+    }
+
+    #[test]
+    fn stop_is_checked_inside_the_moves() {
+        let mut termination = Termination::new(&StoppingCriteria {
+            max_evaluations: Some(0),
+            ..StoppingCriteria::default()
+        });
+        let n = 6;
+        let matrix = create_matrix(n, true, 6);
+        let path = create_path(n, 6);
+        let tour = Tour::new(&matrix, n, true, &path);
+        let mut stop = StopCheck::new(&mut termination);
+        let checks = (0..CHECK_INTERVAL)
+            .filter(|_| !stop.should_stop(&tour))
+            .count();
+        assert_eq!(checks, CHECK_INTERVAL - 1);
+    }
+}
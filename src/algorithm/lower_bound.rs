@@ -0,0 +1,513 @@
+use std::error::Error;
+
+// Lower bounds on the optimal tour, computed from the distance matrix only. An asymmetric
+// matrix is bounded through min(d(i, j), d(j, i)), every directed tour costs at least that.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum BoundKind {
+    // minimum spanning tree
+    SpanningTree,
+    // minimum spanning tree of the cities but 0, plus the two shortest edges of city 0
+    OneTree,
+    // 1-tree with the Held-Karp city penalties found by subgradient optimization
+    HeldKarp,
+    // subtour relaxation of the TSP linear program, only with the `lp` feature
+    LinearProgram,
+}
+
+impl BoundKind {
+    pub fn get_kind_from_str(kind: &str) -> Option<Self> {
+        match kind.to_lowercase().as_str() {
+            "mst" => Some(Self::SpanningTree),
+            "one_tree" => Some(Self::OneTree),
+            "held_karp" => Some(Self::HeldKarp),
+            "lp" => Some(Self::LinearProgram),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::SpanningTree => "mst",
+            Self::OneTree => "one_tree",
+            Self::HeldKarp => "held_karp",
+            Self::LinearProgram => "lp",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LowerBound {
+    kind: BoundKind,
+    value: f64,
+}
+
+impl LowerBound {
+    pub fn new(kind: BoundKind, value: f64) -> Self {
+        LowerBound { kind, value }
+    }
+
+    pub fn get_kind(&self) -> BoundKind {
+        self.kind
+    }
+
+    pub fn get_value(&self) -> f64 {
+        self.value
+    }
+
+    // Percentage of the bound the distance is above it
+    pub fn get_gap(&self, distance: f64) -> f64 {
+        if self.value > 0.0 {
+            ((distance - self.value) / self.value * 100.0).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+// Every bound up to the given kind, each at least as tight as the previous. upper_bound is
+// the length of a known tour, it sets the subgradient steps and lets it stop early.
+pub fn compute_lower_bounds(
+    distance_matrix: &[f64],
+    n: usize,
+    up_to: BoundKind,
+    upper_bound: f64,
+    iterations: usize,
+) -> Result<Vec<LowerBound>, Box<dyn Error>> {
+    let mut bounds = vec![LowerBound::new(
+        BoundKind::SpanningTree,
+        spanning_tree_bound(distance_matrix, n),
+    )];
+    if up_to >= BoundKind::OneTree {
+        let zeros = vec![0.0; n];
+        let (one_tree, _) = one_tree(distance_matrix, n, &zeros);
+        bounds.push(LowerBound::new(BoundKind::OneTree, one_tree));
+    }
+    if up_to >= BoundKind::HeldKarp {
+        bounds.push(LowerBound::new(
+            BoundKind::HeldKarp,
+            held_karp_bound(distance_matrix, n, upper_bound, iterations),
+        ));
+    }
+    if up_to >= BoundKind::LinearProgram {
+        bounds.push(LowerBound::new(
+            BoundKind::LinearProgram,
+            linear_program_bound(distance_matrix, n)?,
+        ));
+    }
+    Ok(bounds)
+}
+
+// The highest of the bounds
+pub fn get_best_bound(bounds: &[LowerBound]) -> Option<&LowerBound> {
+    bounds
+        .iter()
+        .max_by(|a, b| a.value.partial_cmp(&b.value).unwrap())
+}
+
+pub fn edge_cost(distance_matrix: &[f64], n: usize, i: usize, j: usize) -> f64 {
+    distance_matrix[i * n + j].min(distance_matrix[j * n + i])
+}
+
+// A tour without one of its edges is a spanning path, never shorter than the tree
+pub fn spanning_tree_bound(distance_matrix: &[f64], n: usize) -> f64 {
+    if n < 2 {
+        return 0.0;
+    }
+    let cities: Vec<usize> = (0..n).collect();
+    let (cost, _) = spanning_tree(distance_matrix, n, &cities, &vec![0.0; n]);
+    cost
+}
+
+// Cost of the minimum spanning tree of some of the cities, with the penalties added to the
+// edges (zeros for the plain tree)
+pub fn spanning_tree_cost(
+    distance_matrix: &[f64],
+    n: usize,
+    cities: &[usize],
+    penalties: &[f64],
+) -> f64 {
+    spanning_tree(distance_matrix, n, cities, penalties).0
+}
+
+// Prim on the complete graph of the cities, with the penalties added to the edges. Returns
+// the penalized cost and the degree of every city.
+fn spanning_tree(
+    distance_matrix: &[f64],
+    n: usize,
+    cities: &[usize],
+    penalties: &[f64],
+) -> (f64, Vec<usize>) {
+    let mut degrees = vec![0; n];
+    if cities.is_empty() {
+        return (0.0, degrees);
+    }
+    // positions in `cities` of the cities still out of the tree
+    let mut outside: Vec<usize> = (1..cities.len()).collect();
+    let mut closest = vec![f64::MAX; cities.len()];
+    let mut parent = vec![cities[0]; cities.len()];
+    let mut cost = 0.0;
+    let mut current = cities[0];
+    while !outside.is_empty() {
+        let mut next = 0;
+        for (index, &position) in outside.iter().enumerate() {
+            let city = cities[position];
+            let weight =
+                edge_cost(distance_matrix, n, current, city) + penalties[current] + penalties[city];
+            if weight < closest[position] {
+                closest[position] = weight;
+                parent[position] = current;
+            }
+            if closest[position] < closest[outside[next]] {
+                next = index;
+            }
+        }
+        let position = outside.swap_remove(next);
+        current = cities[position];
+        cost += closest[position];
+        degrees[current] += 1;
+        degrees[parent[position]] += 1;
+    }
+    (cost, degrees)
+}
+
+// Value of the penalized 1-tree, cost minus twice the penalties, and the degrees
+fn one_tree(distance_matrix: &[f64], n: usize, penalties: &[f64]) -> (f64, Vec<usize>) {
+    if n < 3 {
+        let cost = (0..n)
+            .map(|i| distance_matrix[i * n + (i + 1) % n])
+            .sum::<f64>();
+        return (cost, vec![2; n]);
+    }
+    let cities: Vec<usize> = (1..n).collect();
+    let (mut cost, mut degrees) = spanning_tree(distance_matrix, n, &cities, penalties);
+    let (mut first, mut second) = ((f64::MAX, 0), (f64::MAX, 0));
+    for city in 1..n {
+        let weight = edge_cost(distance_matrix, n, 0, city) + penalties[0] + penalties[city];
+        if weight < first.0 {
+            second = first;
+            first = (weight, city);
+        } else if weight < second.0 {
+            second = (weight, city);
+        }
+    }
+    cost += first.0 + second.0;
+    degrees[0] = 2;
+    degrees[first.1] += 1;
+    degrees[second.1] += 1;
+    (cost - 2.0 * penalties.iter().sum::<f64>(), degrees)
+}
+
+pub fn held_karp_bound(
+    distance_matrix: &[f64],
+    n: usize,
+    upper_bound: f64,
+    iterations: usize,
+) -> f64 {
+    held_karp_penalties(distance_matrix, n, upper_bound, iterations).0
+}
+
+// Held-Karp: raises the penalties of the cities with degree above 2 in the 1-tree and
+// lowers the leaves', every penalized 1-tree is a bound and the best one is kept. Returns
+// it with its penalties, which tighten any tree bound of a tour.
+pub fn held_karp_penalties(
+    distance_matrix: &[f64],
+    n: usize,
+    upper_bound: f64,
+    iterations: usize,
+) -> (f64, Vec<f64>) {
+    let mut penalties = vec![0.0; n];
+    let mut best = f64::MIN;
+    let mut best_penalties = penalties.clone();
+    // step scale, halved after a stretch without improvement
+    let mut lambda = 2.0;
+    let patience = (iterations / 10).max(5);
+    let mut not_improved = 0;
+    for _ in 0..iterations.max(1) {
+        let (bound, degrees) = one_tree(distance_matrix, n, &penalties);
+        if bound > best + 1e-9 {
+            best = bound;
+            best_penalties.clone_from(&penalties);
+            not_improved = 0;
+        } else {
+            not_improved += 1;
+            if not_improved >= patience {
+                lambda /= 2.0;
+                not_improved = 0;
+            }
+        }
+        let norm = degrees
+            .iter()
+            .map(|&degree| (degree as f64 - 2.0).powi(2))
+            .sum::<f64>();
+        // every city with degree 2: the 1-tree is a tour and the bound is optimal
+        if norm == 0.0 || upper_bound <= best {
+            break;
+        }
+        let step = lambda * (upper_bound - bound) / norm;
+        for (penalty, &degree) in penalties.iter_mut().zip(&degrees) {
+            *penalty += step * (degree as f64 - 2.0);
+        }
+    }
+    (best, best_penalties)
+}
+
+// The linear program has a variable per edge, larger instances are refused
+#[cfg(feature = "lp")]
+const MAX_LP_CITIES: usize = 150;
+
+// Degree constraints plus one subtour constraint per violated cut of the solution, solved
+// again until every cut is crossed at least twice
+#[cfg(feature = "lp")]
+pub fn linear_program_bound(distance_matrix: &[f64], n: usize) -> Result<f64, Box<dyn Error>> {
+    use microlp::{ComparisonOp, LinearExpr, OptimizationDirection, Problem};
+
+    if n > MAX_LP_CITIES {
+        return Err(format!(
+            "the LP bound is limited to {} cities, the instance has {}",
+            MAX_LP_CITIES, n
+        )
+        .into());
+    }
+    if n < 3 {
+        return Ok(one_tree(distance_matrix, n, &vec![0.0; n]).0);
+    }
+    let mut problem = Problem::new(OptimizationDirection::Minimize);
+    let mut edges = vec![];
+    let mut variables = vec![];
+    for i in 0..n {
+        for j in i + 1..n {
+            edges.push((i, j));
+            variables.push(problem.add_var(edge_cost(distance_matrix, n, i, j), (0.0, 1.0)));
+        }
+    }
+    for city in 0..n {
+        let mut expression = LinearExpr::empty();
+        for (&(i, j), &variable) in edges.iter().zip(&variables) {
+            if i == city || j == city {
+                expression.add(variable, 1.0);
+            }
+        }
+        problem.add_constraint(expression, ComparisonOp::Eq, 2.0);
+    }
+    let mut solution = problem.solve()?;
+    loop {
+        let mut weights = vec![0.0; n * n];
+        for (&(i, j), &variable) in edges.iter().zip(&variables) {
+            weights[i * n + j] = *solution.var_value(variable);
+            weights[j * n + i] = weights[i * n + j];
+        }
+        // a connected solution can still cross some cut less than twice
+        let (cut, side) = minimum_cut(&weights, n);
+        if cut >= 2.0 - 1e-6 {
+            return Ok(solution.objective());
+        }
+        let mut expression = LinearExpr::empty();
+        for (&(i, j), &variable) in edges.iter().zip(&variables) {
+            if side[i] != side[j] {
+                expression.add(variable, 1.0);
+            }
+        }
+        solution = solution.add_constraint(expression, ComparisonOp::Ge, 2.0)?;
+    }
+}
+
+// Stoer-Wagner on the symmetric weights: the lightest cut of the graph and the cities on
+// one of its sides
+#[cfg(feature = "lp")]
+fn minimum_cut(weights: &[f64], n: usize) -> (f64, Vec<bool>) {
+    let mut weights = weights.to_vec();
+    // cities merged into each vertex still in the graph
+    let mut merged: Vec<Vec<usize>> = (0..n).map(|city| vec![city]).collect();
+    let mut vertices: Vec<usize> = (0..n).collect();
+    let mut best = (f64::INFINITY, vec![]);
+    while vertices.len() > 1 {
+        // maximum adjacency order, the last two vertices are cut apart by their phase cut
+        let mut added = vec![false; n];
+        let mut connection = vec![0.0f64; n];
+        let (mut previous, mut last) = (vertices[0], vertices[0]);
+        for _ in 0..vertices.len() {
+            let next = *vertices
+                .iter()
+                .filter(|&&vertex| !added[vertex])
+                .max_by(|&&a, &&b| connection[a].total_cmp(&connection[b]))
+                .unwrap();
+            added[next] = true;
+            (previous, last) = (last, next);
+            for &vertex in &vertices {
+                connection[vertex] += weights[next * n + vertex];
+            }
+        }
+        if connection[last] < best.0 {
+            best = (connection[last], merged[last].clone());
+        }
+        let cities = std::mem::take(&mut merged[last]);
+        merged[previous].extend(cities);
+        for &vertex in vertices.iter().filter(|&&vertex| vertex != previous) {
+            weights[previous * n + vertex] += weights[last * n + vertex];
+            weights[vertex * n + previous] = weights[previous * n + vertex];
+        }
+        vertices.retain(|&vertex| vertex != last);
+    }
+    let mut side = vec![false; n];
+    for city in best.1 {
+        side[city] = true;
+    }
+    (best.0, side)
+}
+
+#[cfg(not(feature = "lp"))]
+pub fn linear_program_bound(_distance_matrix: &[f64], _n: usize) -> Result<f64, Box<dyn Error>> {
+    Err("the LP bound needs the `lp` feature (cargo build --features lp)".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    const TOLERANCE: f64 = 1e-6;
+    const INSTANCES: u64 = 5;
+    const ITERATIONS: usize = 200;
+
+    // Rounded Euclidean distances of random points, a random detour added one way when
+    // asymmetric
+    fn create_matrix(n: usize, symmetric: bool, seed: u64) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let points: Vec<(f64, f64)> = (0..n)
+            .map(|_| (rng.random_range(0.0..100.0), rng.random_range(0.0..100.0)))
+            .collect();
+        let mut matrix = vec![0.0; n * n];
+        for i in 0..n {
+            for j in 0..n {
+                if i != j {
+                    let (dx, dy) = (points[i].0 - points[j].0, points[i].1 - points[j].1);
+                    matrix[i * n + j] = (dx * dx + dy * dy).sqrt().round();
+                }
+            }
+        }
+        if !symmetric {
+            for value in matrix.iter_mut().filter(|value| **value > 0.0) {
+                *value += rng.random_range(0..30) as f64;
+            }
+        }
+        matrix
+    }
+
+    // Shortest tour by trying every order of the cities after the first
+    fn brute_force(matrix: &[f64], n: usize) -> f64 {
+        fn visit(matrix: &[f64], n: usize, path: &mut Vec<usize>, cost: f64, best: &mut f64) {
+            let last = *path.last().unwrap();
+            if path.len() == n {
+                *best = best.min(cost + matrix[last * n]);
+                return;
+            }
+            for city in 1..n {
+                if !path.contains(&city) {
+                    path.push(city);
+                    visit(matrix, n, path, cost + matrix[last * n + city], best);
+                    path.pop();
+                }
+            }
+        }
+        let mut best = f64::INFINITY;
+        visit(matrix, n, &mut vec![0], 0.0, &mut best);
+        best
+    }
+
+    // Every symmetric and asymmetric instance of 5 to 8 cities, with its optimum
+    fn for_each_instance(check: impl Fn(&[f64], usize, f64)) {
+        for n in 5..=8 {
+            for symmetric in [true, false] {
+                for seed in 0..INSTANCES {
+                    let matrix = create_matrix(n, symmetric, seed * 10 + n as u64);
+                    check(&matrix, n, brute_force(&matrix, n));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tree_bounds_never_exceed_the_optimum() {
+        for_each_instance(|matrix, n, optimum| {
+            let spanning_tree = spanning_tree_bound(matrix, n);
+            let (one_tree, _) = one_tree(matrix, n, &vec![0.0; n]);
+            let held_karp = held_karp_bound(matrix, n, optimum, ITERATIONS);
+            assert!(
+                spanning_tree <= optimum + TOLERANCE,
+                "{} > {}",
+                spanning_tree,
+                optimum
+            );
+            assert!(
+                one_tree <= optimum + TOLERANCE,
+                "{} > {}",
+                one_tree,
+                optimum
+            );
+            assert!(
+                held_karp <= optimum + TOLERANCE,
+                "{} > {}",
+                held_karp,
+                optimum
+            );
+        });
+    }
+
+    #[test]
+    fn held_karp_is_at_least_the_one_tree() {
+        for_each_instance(|matrix, n, optimum| {
+            let spanning_tree = spanning_tree_bound(matrix, n);
+            let (one_tree, _) = one_tree(matrix, n, &vec![0.0; n]);
+            // a loose upper bound, the subgradient must not need the optimum
+            let held_karp = held_karp_bound(matrix, n, optimum * 1.5, ITERATIONS);
+            assert!(one_tree >= spanning_tree - TOLERANCE);
+            assert!(
+                held_karp >= one_tree - TOLERANCE,
+                "{} < {}",
+                held_karp,
+                one_tree
+            );
+        });
+    }
+
+    #[test]
+    fn bounds_come_from_the_loosest_to_the_tightest() {
+        for_each_instance(|matrix, n, optimum| {
+            let bounds =
+                compute_lower_bounds(matrix, n, BoundKind::HeldKarp, optimum, ITERATIONS).unwrap();
+            let values: Vec<f64> = bounds.iter().map(|bound| bound.get_value()).collect();
+            assert_eq!(bounds.len(), 3);
+            assert!(values.windows(2).all(|pair| pair[0] <= pair[1] + TOLERANCE));
+            assert_eq!(get_best_bound(&bounds).unwrap().get_value(), values[2]);
+        });
+    }
+
+    #[cfg(feature = "lp")]
+    #[test]
+    fn linear_program_lies_between_held_karp_and_the_optimum() {
+        for_each_instance(|matrix, n, optimum| {
+            let linear_program = linear_program_bound(matrix, n).unwrap();
+            let held_karp = held_karp_bound(matrix, n, optimum, ITERATIONS);
+            assert!(
+                linear_program <= optimum + TOLERANCE,
+                "{} > {}",
+                linear_program,
+                optimum
+            );
+            // every penalized 1-tree bounds the subtour relaxation too
+            assert!(
+                linear_program >= held_karp - TOLERANCE,
+                "{} < {}",
+                linear_program,
+                held_karp
+            );
+        });
+    }
+
+    #[cfg(not(feature = "lp"))]
+    #[test]
+    fn linear_program_needs_the_feature() {
+        let matrix = create_matrix(5, true, 0);
+        assert!(linear_program_bound(&matrix, 5).is_err());
+    }
+}
@@ -1,6 +1,19 @@
+#[allow(clippy::module_inception)]
 pub mod algorithm;
 pub mod algorithm_strategy;
 pub mod ant_colony_optimization;
 pub mod branch_and_bound;
+pub mod checkpoint;
+pub mod crossover;
 pub mod genetic;
+pub mod held_karp;
+pub mod island_model;
+pub mod local_search;
+pub mod lower_bound;
+pub mod mutation;
+pub mod operator_selector;
+pub mod parameters;
+pub mod progress;
 pub mod simulated_annealing;
+pub mod stopping_criteria;
+pub mod tour_improvement;
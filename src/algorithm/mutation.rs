@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+
+use super::operator_selector::OperatorSelector;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mutation {
+    Swap,
+    Displacement,
+    Insertion,
+    SimpleInversion,
+    Inversion,
+    GreedySubTour,
+    GreedyInsertion,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MutationSelection {
+    // always the configured weights
+    #[default]
+    Fixed,
+    // starts from the weights and favours the mutations that recently improved a chromossome
+    Adaptive,
+}
+
+// Relative chance of each mutation, 0 disables it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MutationWeights {
+    pub swap: f64,
+    pub displacement: f64,
+    pub insertion: f64,
+    pub simple_inversion: f64,
+    pub inversion: f64,
+    pub greedy_sub_tour: f64,
+    pub greedy_insertion: f64,
+}
+
+impl Default for MutationWeights {
+    fn default() -> Self {
+        MutationWeights {
+            swap: 0.18,
+            displacement: 0.18,
+            insertion: 0.18,
+            simple_inversion: 0.18,
+            inversion: 0.24,
+            greedy_sub_tour: 0.02,
+            greedy_insertion: 0.02,
+        }
+    }
+}
+
+impl Mutation {
+    pub fn get_all() -> Vec<Self> {
+        vec![
+            Self::Swap,
+            Self::Displacement,
+            Self::Insertion,
+            Self::SimpleInversion,
+            Self::Inversion,
+            Self::GreedySubTour,
+            Self::GreedyInsertion,
+        ]
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Swap => "swap_mutation",
+            Self::Displacement => "displacement_mutation",
+            Self::Insertion => "insertion_mutation",
+            Self::SimpleInversion => "simple_inversion_mutation",
+            Self::Inversion => "inversion_mutation",
+            Self::GreedySubTour => "greedy_sub_tour_mutation",
+            Self::GreedyInsertion => "greedy_insertion_mutation",
+        }
+    }
+
+    // Smallest instance the operator can cut into segments
+    pub fn get_min_cities(&self) -> usize {
+        match self {
+            Self::GreedySubTour => 9,
+            Self::Displacement | Self::SimpleInversion | Self::Inversion => 4,
+            _ => 3,
+        }
+    }
+}
+
+impl MutationWeights {
+    pub fn get_weight(&self, mutation: Mutation) -> f64 {
+        match mutation {
+            Mutation::Swap => self.swap,
+            Mutation::Displacement => self.displacement,
+            Mutation::Insertion => self.insertion,
+            Mutation::SimpleInversion => self.simple_inversion,
+            Mutation::Inversion => self.inversion,
+            Mutation::GreedySubTour => self.greedy_sub_tour,
+            Mutation::GreedyInsertion => self.greedy_insertion,
+        }
+    }
+
+    // Enabled mutations that fit the instance
+    pub fn get_mutations(&self, cities_len: usize) -> Vec<Mutation> {
+        Mutation::get_all()
+            .into_iter()
+            .filter(|&m| self.get_weight(m) > 0.0 && m.get_min_cities() <= cities_len)
+            .collect()
+    }
+
+    pub fn validate(&self, cities_len: usize) -> Result<(), String> {
+        if let Some(mutation) = Mutation::get_all()
+            .into_iter()
+            .find(|&m| !(self.get_weight(m).is_finite() && self.get_weight(m) >= 0.0))
+        {
+            return Err(format!(
+                "G.mutation_weights of {} must be a non negative number",
+                mutation.get_name()
+            ));
+        }
+        if self.get_mutations(cities_len).is_empty() {
+            return Err(format!(
+                "G.mutation_weights enable no mutation for {} cities",
+                cities_len
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl MutationSelection {
+    pub fn create_selector(
+        &self,
+        mutations: &[Mutation],
+        weights: &MutationWeights,
+    ) -> OperatorSelector {
+        OperatorSelector::new(
+            "mutation",
+            mutations.iter().map(|m| m.get_name().to_string()).collect(),
+            &mutations
+                .iter()
+                .map(|&m| weights.get_weight(m))
+                .collect::<Vec<_>>(),
+            *self == Self::Adaptive,
+        )
+    }
+}
@@ -0,0 +1,231 @@
+use std::error::Error;
+
+use csv::Writer;
+use rand::Rng;
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+
+// Adaptive pursuit (Thierens, 2005): every operator keeps a quality estimate of its recent
+// success, updated with QUALITY_RATE, and the probability of the best one is pushed towards
+// the maximum with PURSUIT_RATE while the others decay towards the minimum.
+const QUALITY_RATE: f64 = 0.1;
+const PURSUIT_RATE: f64 = 0.1;
+// share of the probability split evenly, so no operator is ever abandoned
+const MIN_PROBABILITY_SHARE: f64 = 0.2;
+
+// Counters of one operator over a run. improved counts the applications rewarded by the
+// selector, accepted the children that entered the population, new_best and total_gain the
+// children that improved the best distance and by how much.
+#[derive(Debug, Clone, Serialize)]
+pub struct OperatorStats {
+    kind: String,
+    operator: String,
+    applied: usize,
+    improved: usize,
+    accepted: usize,
+    new_best: usize,
+    total_gain: f64,
+    probability: f64,
+}
+
+impl OperatorStats {
+    pub fn get_kind(&self) -> &String {
+        &self.kind
+    }
+
+    pub fn get_operator(&self) -> &String {
+        &self.operator
+    }
+
+    pub fn get_applied(&self) -> usize {
+        self.applied
+    }
+
+    pub fn get_improved(&self) -> usize {
+        self.improved
+    }
+
+    pub fn get_accepted(&self) -> usize {
+        self.accepted
+    }
+
+    pub fn get_new_best(&self) -> usize {
+        self.new_best
+    }
+
+    pub fn get_total_gain(&self) -> f64 {
+        self.total_gain
+    }
+
+    pub fn get_probability(&self) -> f64 {
+        self.probability
+    }
+
+    // "<kind> <operator>: applied <n>, improved <n>, accepted <n>, new best <n>, gain <d>, p = <p>"
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}: applied {}, improved {}, accepted {}, new best {}, gain {}, p = {:.3}",
+            self.kind,
+            self.operator,
+            self.applied,
+            self.improved,
+            self.accepted,
+            self.new_best,
+            self.total_gain,
+            self.probability
+        )
+    }
+}
+
+// Roulette selection among named operators, with fixed weights or adaptive pursuit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorSelector {
+    kind: String,
+    names: Vec<String>,
+    probabilities: Vec<f64>,
+    qualities: Vec<f64>,
+    adaptive: bool,
+    uses: Vec<usize>,
+    successes: Vec<usize>,
+    accepted: Vec<usize>,
+    new_bests: Vec<usize>,
+    gains: Vec<f64>,
+}
+
+impl OperatorSelector {
+    // Weights do not need to add up to 1, adaptive selection starts from them.
+    // kind names the operator family in the statistics ("crossover", "mutation").
+    pub fn new(kind: &str, names: Vec<String>, weights: &[f64], adaptive: bool) -> Self {
+        let total: f64 = weights.iter().sum();
+        let len = names.len();
+        OperatorSelector {
+            kind: kind.to_string(),
+            names,
+            probabilities: weights.iter().map(|weight| weight / total).collect(),
+            qualities: vec![1.0; len],
+            adaptive,
+            uses: vec![0; len],
+            successes: vec![0; len],
+            accepted: vec![0; len],
+            new_bests: vec![0; len],
+            gains: vec![0.0; len],
+        }
+    }
+
+    pub fn choose(&mut self, rng: &mut ChaCha12Rng) -> usize {
+        let len = self.probabilities.len();
+        let mut index = len - 1;
+        if len > 1 {
+            let mut r = rng.random_range(0.0..1.0);
+            for (i, probability) in self.probabilities.iter().enumerate() {
+                r -= probability;
+                if r <= 0.0 {
+                    index = i;
+                    break;
+                }
+            }
+        }
+        self.uses[index] += 1;
+        index
+    }
+
+    pub fn reward(&mut self, index: usize, success: bool) {
+        if success {
+            self.successes[index] += 1;
+        }
+        if !self.adaptive || self.probabilities.len() < 2 {
+            return;
+        }
+
+        let reward = if success { 1.0 } else { 0.0 };
+        self.qualities[index] += QUALITY_RATE * (reward - self.qualities[index]);
+
+        let len = self.probabilities.len() as f64;
+        let min_probability = MIN_PROBABILITY_SHARE / len;
+        let max_probability = 1.0 - (len - 1.0) * min_probability;
+        let best = self
+            .qualities
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+            .map(|(i, _)| i)
+            .unwrap();
+        for (i, probability) in self.probabilities.iter_mut().enumerate() {
+            let target = if i == best {
+                max_probability
+            } else {
+                min_probability
+            };
+            *probability += PURSUIT_RATE * (target - *probability);
+        }
+    }
+
+    pub fn record_accepted(&mut self, index: usize) {
+        self.accepted[index] += 1;
+    }
+
+    // gain: how much the best distance decreased
+    pub fn record_new_best(&mut self, index: usize, gain: f64) {
+        self.new_bests[index] += 1;
+        self.gains[index] += gain;
+    }
+
+    // Same family and operators in the same order, e.g. to restore a checkpoint
+    pub fn has_same_operators(&self, other: &OperatorSelector) -> bool {
+        self.kind == other.kind && self.names == other.names
+    }
+
+    pub fn get_name(&self, index: usize) -> &str {
+        &self.names[index]
+    }
+
+    pub fn get_stats(&self) -> Vec<OperatorStats> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| OperatorStats {
+                kind: self.kind.clone(),
+                operator: name.clone(),
+                applied: self.uses[i],
+                improved: self.successes[i],
+                accepted: self.accepted[i],
+                new_best: self.new_bests[i],
+                total_gain: self.gains[i],
+                probability: self.probabilities[i],
+            })
+            .collect()
+    }
+}
+
+// Sums the counters of the same operators over several selectors, e.g. the islands of the
+// genetic algorithm; the probability is the mean of theirs
+pub fn merge_operator_stats(selectors: &[Vec<OperatorStats>]) -> Vec<OperatorStats> {
+    let Some((first, others)) = selectors.split_first() else {
+        return vec![];
+    };
+    let mut merged = first.clone();
+    for stats in others {
+        for (total, operator) in merged.iter_mut().zip(stats) {
+            total.applied += operator.applied;
+            total.improved += operator.improved;
+            total.accepted += operator.accepted;
+            total.new_best += operator.new_best;
+            total.total_gain += operator.total_gain;
+            total.probability += operator.probability;
+        }
+    }
+    for total in merged.iter_mut() {
+        total.probability /= selectors.len() as f64;
+    }
+    merged
+}
+
+// One row per operator: kind,operator,applied,improved,accepted,new_best,total_gain,probability
+pub fn write_operator_stats_csv(path: &str, stats: &[OperatorStats]) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::from_path(path)?;
+    for operator in stats {
+        writer.serialize(operator)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,450 @@
+use std::{error::Error, fs::read_to_string, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use super::algorithm_strategy::ALGORITHMS;
+use super::crossover::{Crossover, CrossoverSelection};
+use super::held_karp::HeldKarp;
+use super::island_model::{MigrantSelection, MigrationTopology};
+use super::local_search::{DEFAULT_NEIGHBOURS, LocalSearchMethod};
+use super::mutation::{MutationSelection, MutationWeights};
+
+// Tuning knobs of every algorithm. The defaults are the values used in the experiments,
+// a file only needs the keys it changes. Sizes set to 0 are chosen from the number of cities.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlgorithmParameters {
+    #[serde(rename = "G")]
+    pub genetic: GeneticParameters,
+    #[serde(rename = "SA")]
+    pub simulated_annealing: SimulatedAnnealingParameters,
+    #[serde(rename = "ACO")]
+    pub ant_colony_optimization: AntColonyOptimizationParameters,
+    #[serde(rename = "BB")]
+    pub branch_and_bound: BranchAndBoundParameters,
+    #[serde(rename = "HK")]
+    pub held_karp: HeldKarpParameters,
+    #[serde(rename = "LS")]
+    pub local_search: LocalSearchParameters,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GeneticParameters {
+    // 0: 100, or a single chromossome above 1000 cities
+    pub population_size: usize,
+    // generations without a new best before stopping, 0: 200000 (500000 for a single chromossome)
+    pub gen_not_changed_best_breakpoint: usize,
+    pub crossover: Crossover,
+    pub crossover_selection: CrossoverSelection,
+    // crossovers used by the random and adaptive selections, empty: all that fit the instance
+    pub crossover_pool: Vec<Crossover>,
+    pub mutation_selection: MutationSelection,
+    pub mutation_weights: MutationWeights,
+    // memetic mode: local search on the children, none keeps the plain GA
+    pub local_search: LocalSearchMethod,
+    // fraction of the children improved by the local search
+    pub local_search_rate: f64,
+    // populations evolved on their own threads, 1: a single population
+    pub islands: usize,
+    pub migration_topology: MigrationTopology,
+    // generations of every island between two migrations
+    pub migration_interval: u32,
+    // chromossomes sent over every link, at most the population size, 0: isolated islands
+    pub migrants: usize,
+    pub migrant_selection: MigrantSelection,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimulatedAnnealingParameters {
+    // 0: 50, or 20 from 1000 cities on
+    pub population_size: usize,
+    pub generations: usize,
+    pub temp_list_len: usize,
+    // 0: number of cities
+    pub markov_chain_len: usize,
+    // fraction of the generations where the markov chain is the longest
+    pub pos: f64,
+    // 0: number of cities, at least 20
+    pub greedy_range: usize,
+    // improves the best solution of every generation, which replaces the worst one
+    pub local_search: LocalSearchMethod,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AntColonyOptimizationParameters {
+    pub num_ants: usize,
+    pub alpha: f64,
+    // initial value, beta follows the pheromone entropy after the first iteration
+    pub beta: f64,
+    pub rho0: f64,
+    pub gamma: f64,
+    pub omega: f64,
+    pub stall_limit: usize,
+    pub s_threshold: usize,
+    pub q: f64,
+    // applied to the ranked ants before they deposit pheromone
+    pub local_search: LocalSearchMethod,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BranchAndBoundParameters {
+    // search nodes expanded without a new best before giving up, the count starts over on
+    // every new best, --max-evaluations limits the total
+    pub max_call_count: usize,
+    // best-first queue size, beyond it the new nodes are searched depth-first
+    pub max_open_nodes: usize,
+    // subgradient iterations of the Held-Karp penalties used by the node bounds
+    pub held_karp_iterations: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LocalSearchParameters {
+    pub method: LocalSearchMethod,
+    // candidate cities of every move, the closest ones
+    pub neighbours: usize,
+}
+
+impl Default for GeneticParameters {
+    fn default() -> Self {
+        GeneticParameters {
+            population_size: 0,
+            gen_not_changed_best_breakpoint: 0,
+            crossover: Crossover::default(),
+            crossover_selection: CrossoverSelection::default(),
+            crossover_pool: vec![],
+            mutation_selection: MutationSelection::default(),
+            mutation_weights: MutationWeights::default(),
+            local_search: LocalSearchMethod::None,
+            local_search_rate: 1.0,
+            islands: 1,
+            migration_topology: MigrationTopology::default(),
+            migration_interval: 1000,
+            migrants: 2,
+            migrant_selection: MigrantSelection::default(),
+        }
+    }
+}
+
+impl Default for SimulatedAnnealingParameters {
+    fn default() -> Self {
+        SimulatedAnnealingParameters {
+            population_size: 0,
+            generations: 1000,
+            temp_list_len: 150,
+            markov_chain_len: 0,
+            pos: 0.375,
+            greedy_range: 0,
+            local_search: LocalSearchMethod::None,
+        }
+    }
+}
+
+impl Default for AntColonyOptimizationParameters {
+    fn default() -> Self {
+        AntColonyOptimizationParameters {
+            num_ants: 50,
+            alpha: 1.0,
+            beta: 1.0,
+            rho0: 0.3,
+            gamma: 0.8,
+            omega: 0.7,
+            stall_limit: 200,
+            s_threshold: 30,
+            q: 10.0,
+            local_search: LocalSearchMethod::TwoOpt,
+        }
+    }
+}
+
+impl Default for BranchAndBoundParameters {
+    fn default() -> Self {
+        BranchAndBoundParameters {
+            max_call_count: 500_000_000,
+            max_open_nodes: 1_000_000,
+            held_karp_iterations: 100,
+        }
+    }
+}
+
+impl Default for LocalSearchParameters {
+    fn default() -> Self {
+        LocalSearchParameters {
+            method: LocalSearchMethod::LinKernighan,
+            neighbours: DEFAULT_NEIGHBOURS,
+        }
+    }
+}
+
+impl Default for HeldKarpParameters {
+    fn default() -> Self {
+        HeldKarpParameters {
+            max_memory_mb: 2048,
+        }
+    }
+}
+
+impl AlgorithmParameters {
+    // TOML, or JSON when the file extension is .json
+    pub fn read_from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        let content = read_to_string(path)?;
+        let parameters = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => {
+                serde_json::from_str(&content).map_err(|err| format!("{}: {}", path, err))?
+            }
+            _ => toml::from_str(&content).map_err(|err| format!("{}: {}", path, err))?,
+        };
+        Ok(parameters)
+    }
+
+    // "<ALG>.<key>=<value>", e.g. "SA.generations=2000"
+    pub fn apply_override(&mut self, assignment: &str) -> Result<(), Box<dyn Error>> {
+        let (key, value) = assignment.split_once('=').ok_or_else(|| {
+            format!(
+                "invalid parameter '{}', expected <ALG>.<key>=<value>",
+                assignment
+            )
+        })?;
+        let (section, field) = key.trim().split_once('.').ok_or_else(|| {
+            format!(
+                "invalid parameter '{}', expected <ALG>.<key>=<value>",
+                assignment
+            )
+        })?;
+        let value = value
+            .trim()
+            .parse::<toml::Value>()
+            .unwrap_or_else(|_| toml::Value::String(value.trim().to_string()));
+
+        let mut table = toml::Table::try_from(&*self)?;
+        let mut current_table = table
+            .get_mut(&section.trim().to_uppercase())
+            .and_then(|section| section.as_table_mut())
+            .ok_or_else(|| format!("unknown algorithm '{}' in '{}'", section, assignment))?;
+        // nested keys, e.g. "G.mutation_weights.swap=0.3"
+        let mut keys: Vec<&str> = field.trim().split('.').collect();
+        let last_key = keys.pop().unwrap_or_default();
+        for key in keys {
+            current_table = current_table
+                .get_mut(key)
+                .and_then(|table| table.as_table_mut())
+                .ok_or_else(|| format!("unknown key '{}' in '{}'", key, assignment))?;
+        }
+        current_table.insert(last_key.to_string(), value);
+
+        *self = table
+            .try_into()
+            .map_err(|err| format!("invalid parameter '{}': {}", assignment, err))?;
+        Ok(())
+    }
+
+    // The parameters of one algorithm as JSON, null for an unknown algorithm
+    pub fn get_section(&self, algorithm: &str) -> serde_json::Value {
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|mut value| value.get_mut(algorithm.to_uppercase()).map(|v| v.take()))
+            .unwrap_or_default()
+    }
+
+    // Checks the parameters the algorithm will use for this number of cities
+    pub fn validate(&self, algorithm: &str, cities_len: usize) -> Result<(), String> {
+        match algorithm.to_uppercase().as_str() {
+            "G" => self.genetic.resolve(cities_len).validate(cities_len),
+            "SA" => self.simulated_annealing.resolve(cities_len).validate(),
+            "ACO" => self.ant_colony_optimization.validate(),
+            "BB" => self.branch_and_bound.validate(),
+            "HK" => self.held_karp.validate(cities_len),
+            "LS" => self.local_search.validate(),
+            _ => Err(format!(
+                "unknown algorithm '{}' ({})",
+                algorithm,
+                ALGORITHMS.join(", ")
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HeldKarpParameters {
+    // instances whose DP table needs more are refused
+    pub max_memory_mb: usize,
+}
+
+impl GeneticParameters {
+    pub fn resolve(&self, cities_len: usize) -> Self {
+        let population_size = match self.population_size {
+            0 if cities_len > 1000 => 1,
+            0 => 100,
+            size => size,
+        };
+        let gen_not_changed_best_breakpoint = match self.gen_not_changed_best_breakpoint {
+            0 if population_size == 1 => 500_000,
+            0 => 200_000,
+            breakpoint => breakpoint,
+        };
+        GeneticParameters {
+            population_size,
+            gen_not_changed_best_breakpoint,
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self, cities_len: usize) -> Result<(), String> {
+        if self.population_size == 0 {
+            return Err("G.population_size must be at least 1".to_string());
+        }
+        if self.gen_not_changed_best_breakpoint == 0 {
+            return Err("G.gen_not_changed_best_breakpoint must be at least 1".to_string());
+        }
+        self.mutation_weights.validate(cities_len)?;
+        if !(0.0..=1.0).contains(&self.local_search_rate) {
+            return Err("G.local_search_rate must be in [0, 1]".to_string());
+        }
+        if self.islands == 0 {
+            return Err("G.islands must be at least 1".to_string());
+        }
+        if self.migration_interval == 0 {
+            return Err("G.migration_interval must be at least 1".to_string());
+        }
+        if self.population_size == 1 {
+            return Ok(());
+        }
+        let crossovers = match self.crossover_selection {
+            CrossoverSelection::Fixed => vec![self.crossover],
+            _ => self.crossover_pool.clone(),
+        };
+        if let Some(crossover) = crossovers
+            .iter()
+            .find(|crossover| crossover.get_min_cities() > cities_len)
+        {
+            return Err(format!(
+                "G crossover {} needs at least {} cities",
+                crossover.get_name(),
+                crossover.get_min_cities()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl SimulatedAnnealingParameters {
+    pub fn resolve(&self, cities_len: usize) -> Self {
+        let or_default = |value: usize, default: usize| if value == 0 { default } else { value };
+        SimulatedAnnealingParameters {
+            population_size: or_default(
+                self.population_size,
+                if cities_len < 1000 { 50 } else { 20 },
+            ),
+            markov_chain_len: or_default(self.markov_chain_len, cities_len),
+            greedy_range: or_default(self.greedy_range, cities_len.max(20)),
+            ..self.clone()
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.population_size < 2 {
+            return Err("SA.population_size must be at least 2".to_string());
+        }
+        if self.generations < 2 {
+            return Err("SA.generations must be at least 2".to_string());
+        }
+        if self.temp_list_len == 0 {
+            return Err("SA.temp_list_len must be at least 1".to_string());
+        }
+        if !(self.pos > 0.0 && self.pos < 1.0) {
+            return Err("SA.pos must be between 0 and 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl AntColonyOptimizationParameters {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.num_ants == 0 {
+            return Err("ACO.num_ants must be at least 1".to_string());
+        }
+        if !(self.alpha >= 0.0 && self.beta >= 0.0) {
+            return Err("ACO.alpha and ACO.beta must not be negative".to_string());
+        }
+        if !(self.rho0 > 0.0 && self.rho0 < 1.0) {
+            return Err("ACO.rho0 must be between 0 and 1".to_string());
+        }
+        if !(self.gamma > 0.0 && self.gamma <= 1.0) {
+            return Err("ACO.gamma must be in (0, 1]".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.omega) {
+            return Err("ACO.omega must be in [0, 1]".to_string());
+        }
+        if self.stall_limit == 0 {
+            return Err("ACO.stall_limit must be at least 1".to_string());
+        }
+        if self.q.is_nan() || self.q <= 0.0 {
+            return Err("ACO.q must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl BranchAndBoundParameters {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_call_count == 0 {
+            return Err("BB.max_call_count must be at least 1".to_string());
+        }
+        if self.max_open_nodes == 0 {
+            return Err("BB.max_open_nodes must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl LocalSearchParameters {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.method == LocalSearchMethod::None {
+            return Err("LS.method must not be none".to_string());
+        }
+        if self.neighbours == 0 {
+            return Err("LS.neighbours must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl HeldKarpParameters {
+    pub fn validate(&self, cities_len: usize) -> Result<(), String> {
+        match HeldKarp::get_table_size(cities_len) {
+            Some(bytes) if bytes <= self.max_memory_mb.saturating_mul(1 << 20) => Ok(()),
+            Some(bytes) => Err(format!(
+                "HK needs {} MB for {} cities, above HK.max_memory_mb = {}",
+                bytes.div_ceil(1 << 20),
+                cities_len,
+                self.max_memory_mb
+            )),
+            None => Err(format!("HK cannot solve {} cities", cities_len)),
+        }
+    }
+}
+
+// One line "key = value, ..." for the metadata file, nested keys as "table.key"
+pub fn describe_parameters<T: Serialize>(parameters: &T) -> String {
+    let mut entries = vec![];
+    if let Ok(table) = toml::Table::try_from(parameters) {
+        flatten_table("", &table, &mut entries);
+    }
+    entries.join(", ")
+}
+
+fn flatten_table(prefix: &str, table: &toml::Table, entries: &mut Vec<String>) {
+    for (key, value) in table {
+        match value {
+            toml::Value::Table(nested) => {
+                flatten_table(&format!("{}{}.", prefix, key), nested, entries)
+            }
+            _ => entries.push(format!("{}{} = {}", prefix, key, value)),
+        }
+    }
+}
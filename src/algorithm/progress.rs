@@ -0,0 +1,119 @@
+use std::{error::Error, time::Duration};
+
+use csv::Writer;
+use serde::{Deserialize, Serialize};
+
+// State of a run when its best tour improves
+pub struct Progress<'a> {
+    elapsed: Duration,
+    iteration: u64,
+    evaluations: u64,
+    best_distance: f64,
+    best_path: &'a [u16],
+}
+
+impl<'a> Progress<'a> {
+    pub fn new(
+        elapsed: Duration,
+        iteration: u64,
+        evaluations: u64,
+        best_distance: f64,
+        best_path: &'a [u16],
+    ) -> Self {
+        Progress {
+            elapsed,
+            iteration,
+            evaluations,
+            best_distance,
+            best_path,
+        }
+    }
+
+    pub fn get_elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn get_iteration(&self) -> u64 {
+        self.iteration
+    }
+
+    pub fn get_evaluations(&self) -> u64 {
+        self.evaluations
+    }
+
+    pub fn get_best_distance(&self) -> f64 {
+        self.best_distance
+    }
+
+    // Borrowed from the algorithm, clone it to keep it
+    pub fn get_best_path(&self) -> &[u16] {
+        self.best_path
+    }
+}
+
+// Hook called by every algorithm each time its best tour improves
+// Send: the island model runs the genetic algorithm on several threads
+pub trait Observer: Send {
+    fn on_improvement(&mut self, progress: &Progress);
+}
+
+// Prints every improvement to stderr, stdout stays with the final distance and time
+pub struct ProgressPrinter;
+
+impl Observer for ProgressPrinter {
+    fn on_improvement(&mut self, progress: &Progress) {
+        eprintln!(
+            "{:.3}s iteration {} evaluations {}: {}",
+            progress.get_elapsed().as_secs_f64(),
+            progress.get_iteration(),
+            progress.get_evaluations(),
+            progress.get_best_distance()
+        );
+    }
+}
+
+// One improvement of the convergence trace
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TracePoint {
+    elapsed: f64,
+    iteration: u64,
+    evaluations: u64,
+    best_distance: f64,
+}
+
+impl TracePoint {
+    pub fn new(progress: &Progress) -> Self {
+        TracePoint {
+            elapsed: progress.get_elapsed().as_secs_f64(),
+            iteration: progress.get_iteration(),
+            evaluations: progress.get_evaluations(),
+            best_distance: progress.get_best_distance(),
+        }
+    }
+
+    pub fn get_elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    pub fn get_iteration(&self) -> u64 {
+        self.iteration
+    }
+
+    pub fn get_evaluations(&self) -> u64 {
+        self.evaluations
+    }
+
+    pub fn get_best_distance(&self) -> f64 {
+        self.best_distance
+    }
+}
+
+// elapsed,iteration,evaluations,best_distance, one row per improvement
+pub fn write_convergence_csv(path: &str, trace: &[TracePoint]) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::from_path(path)?;
+    for point in trace {
+        writer.serialize(point)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
@@ -1,30 +1,31 @@
-use std::{collections::BinaryHeap, time::Instant};
+use std::{collections::BinaryHeap, error::Error};
 
 use kiddo::KdTree;
 use ordered_float::OrderedFloat;
 use rand::{
-    Rng, rng,
-    rngs::ThreadRng,
+    Rng, SeedableRng,
     seq::{IndexedRandom, SliceRandom},
 };
+use rand_chacha::ChaCha12Rng;
+
+use serde::{Deserialize, Serialize};
 
 use super::algorithm::{Algorithm, ExecuteResponse};
-use crate::models::city::City;
+use super::checkpoint::Checkpoint;
+use super::local_search::{DEFAULT_NEIGHBOURS, LocalSearch, LocalSearchMethod};
+use super::parameters::{SimulatedAnnealingParameters, describe_parameters};
+use super::stopping_criteria::Termination;
+use crate::models::{city::City, distance_metric::DistanceMetric};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Solution {
     path: Vec<u16>,
     distance: f64,
-    rng: ThreadRng,
 }
 
 impl Solution {
     fn new(path: Vec<u16>, distance: f64) -> Self {
-        Solution {
-            path,
-            distance,
-            rng: rng(),
-        }
+        Solution { path, distance }
     }
 
     fn get_path(&self) -> &Vec<u16> {
@@ -42,10 +43,10 @@ impl Solution {
         self
     }
 
-    fn swap(mut self, distance_matrix: &[f64]) -> Self {
+    fn swap(mut self, distance_matrix: &[f64], rng: &mut ChaCha12Rng) -> Self {
         let n = &self.path.len();
-        let first = self.rng.random_range(0..n - 1);
-        let second = self.rng.random_range(0..n - 1);
+        let first = rng.random_range(0..n - 1);
+        let second = rng.random_range(0..n - 1);
 
         self.path.swap(first, second);
 
@@ -61,14 +62,20 @@ impl Solution {
         self.update_distance(distance_matrix)
     }
 
-    fn block_insert_sampling(mut self, distance_matrix: &[f64], city_i: u16, city_j: u16) -> Self {
+    fn block_insert_sampling(
+        mut self,
+        distance_matrix: &[f64],
+        city_i: u16,
+        city_j: u16,
+        rng: &mut ChaCha12Rng,
+    ) -> Self {
         let city_i_index = self.path.iter().position(|&x| x == city_i).unwrap();
         let city_j_index = self.path.iter().position(|&x| x == city_j).unwrap();
         let path_len = self.path.len();
 
-        let random = self.rng.random_range(1..10);
-        let block_size = random
-            .min(((city_i_index as isize - city_j_index as isize - 1) as isize).abs() as usize);
+        let random = rng.random_range(1..10);
+        let block_size =
+            random.min((city_i_index as isize - city_j_index as isize - 1).unsigned_abs());
 
         if block_size == 0 {
             return self;
@@ -118,20 +125,59 @@ impl Solution {
     }
 }
 
+// Everything a run needs to continue from a checkpoint
+#[derive(Serialize, Deserialize)]
+struct AnnealingState {
+    solutions: Vec<Solution>,
+    temperatures: Vec<Vec<f64>>,
+    // last city sampled by each solution
+    sampled_cities: Vec<u16>,
+    // next generation to run, a generation stopped halfway runs again
+    generation: usize,
+    best: Solution,
+    initial_best: Solution,
+    // the random sequence goes on where it stopped
+    rng: ChaCha12Rng,
+}
+
 pub struct SimulatedAnnealing {
     cities: Vec<City>,
     distance_matrix: Vec<f64>,
-    kd_tree: KdTree<f32, 2>,
-    rng: ThreadRng,
+    metric: DistanceMetric,
+    kd_tree: KdTree<f32, 3>,
+    parameters: SimulatedAnnealingParameters,
+    local_search: LocalSearch,
+    rng: ChaCha12Rng,
+    termination: Termination,
+    // takes the place of the first random solution
+    initial_tour: Option<Vec<u16>>,
+    resume_state: Option<AnnealingState>,
 }
 
 impl SimulatedAnnealing {
-    pub fn new(cities: &Vec<City>) -> Self {
+    pub fn new(
+        cities: &[City],
+        distance_matrix: &[f64],
+        metric: &DistanceMetric,
+        parameters: &SimulatedAnnealingParameters,
+        seed: u64,
+    ) -> Self {
         SimulatedAnnealing {
-            cities: cities.clone(),
-            distance_matrix: vec![],
-            kd_tree: SimulatedAnnealing::create_kd_tree(cities.clone()),
-            rng: rng(),
+            cities: cities.to_vec(),
+            distance_matrix: distance_matrix.to_vec(),
+            metric: metric.clone(),
+            kd_tree: SimulatedAnnealing::create_kd_tree(cities, metric),
+            parameters: parameters.resolve(cities.len()),
+            local_search: LocalSearch::new(
+                distance_matrix,
+                cities.len(),
+                parameters.local_search,
+                DEFAULT_NEIGHBOURS,
+            ),
+            rng: ChaCha12Rng::seed_from_u64(seed),
+            termination: Termination::default(),
+            initial_tour: None,
+            resume_state: None,
         }
     }
 
@@ -140,7 +186,7 @@ impl SimulatedAnnealing {
         &mut self,
         city: u16,
         solution: &Solution,
-        a_sol: &Vec<Solution>,
+        a_sol: &[Solution],
     ) -> Solution {
         let random = self.rng.random_range(0..a_sol.len() - 1);
         let solution_y = &a_sol[random];
@@ -150,12 +196,11 @@ impl SimulatedAnnealing {
         let pos_city_in_y = y_path.iter().position(|&x| x == city).unwrap();
         let mut city_j = y_path[(pos_city_in_y + 1) % y_path.len()];
         if city_j == x_path[(pos_city_in_x + 1) % x_path.len()] {
-            let leading_index;
-            if pos_city_in_y == 0 {
-                leading_index = y_path.len() - 1;
+            let leading_index = if pos_city_in_y == 0 {
+                y_path.len() - 1
             } else {
-                leading_index = pos_city_in_y - 1
-            }
+                pos_city_in_y - 1
+            };
             city_j = y_path[(leading_index) % y_path.len()];
         }
         let x_1 = solution
@@ -164,9 +209,12 @@ impl SimulatedAnnealing {
         let x_2 = solution
             .clone()
             .swap_sampling(&self.distance_matrix, city, city_j);
-        let x_3 = solution
-            .clone()
-            .block_insert_sampling(&self.distance_matrix, city, city_j);
+        let x_3 = solution.clone().block_insert_sampling(
+            &self.distance_matrix,
+            city,
+            city_j,
+            &mut self.rng,
+        );
 
         self.find_best_solution(&[x_1, x_2, x_3])
     }
@@ -194,11 +242,12 @@ impl SimulatedAnnealing {
                 &self.cities[current_city],
                 greedy_n,
                 &visited_cities,
+                &self.metric,
             );
 
             let chosen = near_neighbours.choose(&mut self.rng).unwrap();
 
-            current_city = chosen.clone();
+            current_city = *chosen;
             path.push(current_city);
             visited_cities.push(&self.cities[current_city]);
         }
@@ -236,7 +285,9 @@ impl SimulatedAnnealing {
         let mut priority_list = vec![];
 
         while priority_list.len() < 2 * len {
-            let new_solution = current_solution.clone().swap(&self.distance_matrix);
+            let new_solution = current_solution
+                .clone()
+                .swap(&self.distance_matrix, &mut self.rng);
             let distance_new = new_solution.get_distance();
             let distance_curr = current_solution.get_distance();
             priority_list.push(OrderedFloat((distance_new - distance_curr).abs()));
@@ -244,6 +295,7 @@ impl SimulatedAnnealing {
                 current_solution = new_solution.clone()
             }
         }
+        self.termination.add_evaluations(2 * len as u64);
 
         priority_list.sort_unstable();
         priority_list.drain(0..len / 2);
@@ -272,15 +324,13 @@ impl SimulatedAnnealing {
         let mut a_mcl = Vec::with_capacity(generations);
 
         for i in 0..generations {
-            let ratio;
-            if i as f64 <= best_gen {
-                ratio = (((i as f64) / best_gen) * mcl as f64).round();
+            let ratio = if i as f64 <= best_gen {
+                (((i as f64) / best_gen) * mcl as f64).round()
             } else {
-                ratio = ((generations as f64 - 1.0 - i as f64)
-                    / (generations as f64 - 1.0 - best_gen)
+                ((generations as f64 - 1.0 - i as f64) / (generations as f64 - 1.0 - best_gen)
                     * mcl as f64)
-                    .round();
-            }
+                    .round()
+            };
             let i_mcl = mcl / 2 + ratio as usize;
             a_mcl.push(i_mcl);
         }
@@ -288,6 +338,62 @@ impl SimulatedAnnealing {
         a_mcl
     }
 
+    fn write_checkpoint(
+        &mut self,
+        solutions: &[Solution],
+        temperatures: &[BinaryHeap<OrderedFloat<f64>>],
+        sampled_cities: &[u16],
+        generation: usize,
+        best: &Solution,
+        initial_best: &Solution,
+    ) {
+        let state = AnnealingState {
+            solutions: solutions.to_vec(),
+            temperatures: temperatures
+                .iter()
+                .map(|list| list.iter().map(|temperature| temperature.0).collect())
+                .collect(),
+            sampled_cities: sampled_cities.to_vec(),
+            generation,
+            best: best.clone(),
+            initial_best: initial_best.clone(),
+            rng: self.rng.clone(),
+        };
+        self.termination
+            .write_checkpoint("SA", self.cities.len(), state);
+    }
+
+    // Improves the best solution of the population, the improved copy takes the place of
+    // the worst one so the annealing keeps the original
+    fn apply_local_search(&mut self, population: &mut [Solution], best: &mut Solution) {
+        if self.local_search.get_method() == LocalSearchMethod::None {
+            return;
+        }
+        let mut improved = self.find_best_solution(population);
+        let distance = self.local_search.improve(
+            &self.distance_matrix,
+            &mut improved.path,
+            &mut self.termination,
+        );
+        self.termination.add_evaluations(1);
+        if distance >= improved.distance {
+            return;
+        }
+        improved.distance = distance;
+        if distance < *best.get_distance() {
+            *best = improved.clone();
+            self.termination
+                .report_best(*best.get_distance(), best.get_path());
+        }
+        let worst = population
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.distance.partial_cmp(&b.1.distance).unwrap())
+            .map(|(index, _)| index)
+            .unwrap();
+        population[worst] = improved;
+    }
+
     fn find_best_solution(&self, population: &[Solution]) -> Solution {
         population
             .iter()
@@ -300,36 +406,76 @@ impl SimulatedAnnealing {
 impl Algorithm for SimulatedAnnealing {
     fn execute(&mut self) -> ExecuteResponse {
         println!("Execute SimulatedAnnealing");
-        let start_time = Instant::now();
+        self.termination.start();
         let cities_len = self.cities.len();
-        let greedy_range = cities_len.max(20);
-        let temp_list_len = 150;
-        //p
-        let population_size;
-        if cities_len < 1000 {
-            population_size = 50;
-        } else {
-            population_size = 20;
-        }
+        let greedy_range = self.parameters.greedy_range;
+        let temp_list_len = self.parameters.temp_list_len;
         //g
-        let generations = 1000;
+        let generations = self.parameters.generations;
         //m
-        let markov_chain_len = cities_len;
-        let pos = 0.375;
-        self.distance_matrix = Self::create_distance_matrix(&self.cities);
-
-        let mut a_sol = self.create_random_solutions(population_size);
-        let mut tempreture_matrix =
-            self.create_temperature_lists_matrix(population_size, temp_list_len, greedy_range);
-        let mut a_city: Vec<u16> = vec![0; population_size];
+        let markov_chain_len = self.parameters.markov_chain_len;
+        let pos = self.parameters.pos;
+
+        let (
+            mut a_sol,
+            mut tempreture_matrix,
+            mut a_city,
+            first_generation,
+            mut best,
+            initial_best,
+        ) = match self.resume_state.take() {
+            Some(state) => {
+                self.rng = state.rng;
+                (
+                    state.solutions,
+                    state
+                        .temperatures
+                        .into_iter()
+                        .map(|list| list.into_iter().map(OrderedFloat).collect())
+                        .collect(),
+                    state.sampled_cities,
+                    state.generation,
+                    state.best,
+                    state.initial_best,
+                )
+            }
+            None => {
+                //p
+                let population_size = self.parameters.population_size;
+                let mut a_sol = self.create_random_solutions(population_size);
+                self.termination.add_evaluations(population_size as u64);
+                if let Some(tour) = self.initial_tour.take() {
+                    let distance = Self::calculate_path_distance(&tour, &self.distance_matrix);
+                    a_sol[0] = Solution::new(tour, distance);
+                }
+                let tempreture_matrix = self.create_temperature_lists_matrix(
+                    population_size,
+                    temp_list_len,
+                    greedy_range,
+                );
+                let best = self.find_best_solution(&a_sol);
+                let initial_best = best.clone();
+                (
+                    a_sol,
+                    tempreture_matrix,
+                    vec![0; population_size],
+                    0,
+                    best,
+                    initial_best,
+                )
+            }
+        };
+        // a resumed run keeps the population size of its checkpoint
+        let population_size = a_sol.len();
         let a_mcl = self.create_mcl_list(pos, markov_chain_len, generations);
-        let mut best = self.find_best_solution(&a_sol);
-        let initial_best = best.clone();
-        for g in 0..generations {
+        self.termination
+            .report_best(*best.get_distance(), best.get_path());
+        let mut next_generation = first_generation;
+        for (g, &mcl_len) in a_mcl.iter().enumerate().skip(first_generation) {
             for i in 0..population_size {
                 let temperature = tempreture_matrix[i].peek().unwrap();
                 let (mut k, mut c, mut s) = (0, 0, 0.0);
-                while k < a_mcl[g] {
+                while k < mcl_len {
                     a_city[i] = (a_city[i] + 1) % (cities_len as u16);
                     let solution_y = self
                         .create_new_solution_by_heuristic_strategy(a_city[i], &a_sol[i], &a_sol);
@@ -337,12 +483,11 @@ impl Algorithm for SimulatedAnnealing {
                     let current_distance = a_sol[i].get_distance();
                     let y_distance = solution_y.get_distance();
                     let distance_diff = y_distance - current_distance;
-                    let p;
-                    if y_distance < current_distance {
-                        p = 1.0;
+                    let p = if y_distance < current_distance {
+                        1.0
                     } else {
-                        p = (-distance_diff / temperature.0).exp()
-                    }
+                        (-distance_diff / temperature.0).exp()
+                    };
                     let random = self.rng.random_range(0.0..1.0);
                     if random < p {
                         if distance_diff > 0.0 {
@@ -350,27 +495,76 @@ impl Algorithm for SimulatedAnnealing {
                             c += 1;
                         } else if current_distance < best.get_distance() {
                             best = a_sol[i].clone();
+                            self.termination
+                                .report_best(*best.get_distance(), best.get_path());
                             // println!("{} {}", &best.get_distance(), g);
                         }
                         a_sol[i] = solution_y.clone();
                     }
                     k += 1;
                 }
+                self.termination.add_evaluations(mcl_len as u64);
                 if c > 0 {
                     tempreture_matrix[i].pop();
                     tempreture_matrix[i].push(OrderedFloat(s / c as f64));
                 }
+                if self.termination.should_stop(*best.get_distance()) {
+                    break;
+                }
+            }
+            if !self.termination.is_stopped() {
+                self.apply_local_search(&mut a_sol, &mut best);
+                next_generation = g + 1;
+            }
+            if self.termination.next_iteration(*best.get_distance()) {
+                break;
+            }
+            if self.termination.checkpoint_due() {
+                self.write_checkpoint(
+                    &a_sol,
+                    &tempreture_matrix,
+                    &a_city,
+                    next_generation,
+                    &best,
+                    &initial_best,
+                );
             }
             // if g % 50 == 0 {
             //     println!("gen {}", g);
             // }
         }
-        ExecuteResponse::new(
+        self.write_checkpoint(
+            &a_sol,
+            &tempreture_matrix,
+            &a_city,
+            next_generation,
+            &best,
+            &initial_best,
+        );
+        let mut response = ExecuteResponse::new(
             initial_best.get_path().clone(),
             best.get_path().clone(),
-            best.get_distance().clone(),
-            start_time.elapsed(),
-            String::new(),
-        )
+            *best.get_distance(),
+            self.termination.get_elapsed(),
+            format!("Parameters: {}", describe_parameters(&self.parameters)),
+        );
+        response.set_termination(&self.termination);
+        response
+    }
+
+    fn get_termination(&mut self) -> &mut Termination {
+        &mut self.termination
+    }
+
+    fn set_initial_tour(&mut self, tour: &[u16]) {
+        self.initial_tour = Some(tour.to_vec());
+    }
+
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let checkpoint: Checkpoint<AnnealingState> =
+            Checkpoint::read_from_file(path, "SA", self.cities.len())?;
+        self.termination.restore(&checkpoint);
+        self.resume_state = Some(checkpoint.into_state());
+        Ok(())
     }
 }
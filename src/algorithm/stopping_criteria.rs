@@ -0,0 +1,287 @@
+use std::{
+    process::exit,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use super::checkpoint::{Checkpoint, CheckpointSettings};
+use super::progress::{Observer, Progress, TracePoint};
+
+// Set from the signal handler, every running algorithm stops at its next check
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+// Asks the running algorithms to stop and return their best tour so far
+pub fn request_interruption() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+// The first SIGINT/SIGTERM requests the interruption, telling what happens next, a second
+// one quits
+pub fn install_interrupt_handler(message: &'static str) {
+    ctrlc::set_handler(move || {
+        if is_interrupted() {
+            exit(130);
+        }
+        eprintln!("{}", message);
+        request_interruption();
+    })
+    .unwrap();
+}
+
+// Limits shared by every algorithm, checked on top of its own stopping rule. None: no limit.
+#[derive(Debug, Clone, Default)]
+pub struct StoppingCriteria {
+    pub max_time: Option<Duration>,
+    // candidate tours evaluated
+    pub max_evaluations: Option<u64>,
+    // stops as soon as the best distance is at most this value
+    pub target_distance: Option<f64>,
+    // iterations without a new best
+    pub max_stagnation: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StopReason {
+    // the algorithm's own rule (GA breakpoint, SA generations, ACO stall limit, BB search)
+    #[default]
+    Completed,
+    TimeLimit,
+    EvaluationLimit,
+    TargetReached,
+    Stagnation,
+    // SIGINT or SIGTERM
+    Interrupted,
+    // BB gave up after max_call_count nodes without a new best, the tour is not proved optimal
+    CallLimit,
+}
+
+impl StopReason {
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Completed => "completed",
+            Self::TimeLimit => "time_limit",
+            Self::EvaluationLimit => "evaluation_limit",
+            Self::TargetReached => "target_reached",
+            Self::Stagnation => "stagnation",
+            Self::Interrupted => "interrupted",
+            Self::CallLimit => "call_limit",
+        }
+    }
+}
+
+// Tracks a run against the stopping criteria, records the improvements of the best tour,
+// passing them to the observer, and times the checkpoints. What an iteration and an evaluation are depend on the algorithm:
+// G: a generation, every child and every mutation (islands: a migration interval)
+// SA: a generation, every neighbour solution
+// ACO: an iteration, every ant tour
+// BB: a search node, every node
+pub struct Termination {
+    criteria: StoppingCriteria,
+    start_time: Instant,
+    // run time of the checkpoint this run resumed from
+    previous_elapsed: Duration,
+    iterations: u64,
+    evaluations: u64,
+    best_distance: f64,
+    stagnation: usize,
+    reason: Option<StopReason>,
+    trace: Vec<TracePoint>,
+    observer: Option<Box<dyn Observer>>,
+    checkpoint: Option<CheckpointSettings>,
+    last_checkpoint: Instant,
+}
+
+impl Default for Termination {
+    fn default() -> Self {
+        Termination::new(&StoppingCriteria::default())
+    }
+}
+
+impl Termination {
+    pub fn new(criteria: &StoppingCriteria) -> Self {
+        Termination {
+            criteria: criteria.clone(),
+            start_time: Instant::now(),
+            previous_elapsed: Duration::ZERO,
+            iterations: 0,
+            evaluations: 0,
+            best_distance: f64::MAX,
+            stagnation: 0,
+            reason: None,
+            trace: vec![],
+            observer: None,
+            checkpoint: None,
+            last_checkpoint: Instant::now(),
+        }
+    }
+
+    pub fn set_criteria(&mut self, criteria: &StoppingCriteria) {
+        self.criteria = criteria.clone();
+    }
+
+    pub fn get_criteria(&self) -> &StoppingCriteria {
+        &self.criteria
+    }
+
+    pub fn set_observer(&mut self, observer: Box<dyn Observer>) {
+        self.observer = Some(observer);
+    }
+
+    pub fn set_checkpoint(&mut self, checkpoint: &CheckpointSettings) {
+        self.checkpoint = Some(checkpoint.clone());
+    }
+
+    // Continues the counters and the trace of a checkpoint, the stagnation starts over
+    pub fn restore<T>(&mut self, checkpoint: &Checkpoint<T>) {
+        self.previous_elapsed = checkpoint.get_elapsed();
+        self.iterations = checkpoint.get_iterations();
+        self.evaluations = checkpoint.get_evaluations();
+        self.trace = checkpoint.get_trace().clone();
+    }
+
+    // The clock starts with the execution, not with the construction
+    pub fn start(&mut self) {
+        self.start_time = Instant::now();
+        self.last_checkpoint = Instant::now();
+    }
+
+    // Run time, including the runs resumed from
+    pub fn get_elapsed(&self) -> Duration {
+        self.previous_elapsed + self.start_time.elapsed()
+    }
+
+    // True when checkpoints are enabled and the interval has passed since the last one
+    pub fn checkpoint_due(&self) -> bool {
+        self.checkpoint
+            .as_ref()
+            .is_some_and(|checkpoint| self.last_checkpoint.elapsed() >= checkpoint.interval)
+    }
+
+    // A failed write is reported and the run goes on
+    pub fn write_checkpoint<T: Serialize>(&mut self, algorithm: &str, cities_len: usize, state: T) {
+        let Some(settings) = &self.checkpoint else {
+            return;
+        };
+        let checkpoint = Checkpoint::new(
+            algorithm,
+            cities_len,
+            settings.instance.clone(),
+            self.get_elapsed(),
+            self.iterations,
+            self.evaluations,
+            self.trace.clone(),
+            state,
+        );
+        if let Err(err) = checkpoint.write_to_file(&settings.path) {
+            eprintln!("Error writing checkpoint {}: {}", settings.path, err);
+        }
+        self.last_checkpoint = Instant::now();
+    }
+
+    pub fn add_evaluations(&mut self, evaluations: u64) {
+        self.evaluations += evaluations;
+    }
+
+    // Called whenever the best tour improves, including the first one
+    pub fn report_best(&mut self, best_distance: f64, best_path: &[u16]) {
+        // e.g. the best of a resumed run, already in the trace
+        if self
+            .trace
+            .last()
+            .is_some_and(|point| point.get_best_distance() <= best_distance)
+        {
+            return;
+        }
+        let progress = Progress::new(
+            self.get_elapsed(),
+            self.iterations,
+            self.evaluations,
+            best_distance,
+            best_path,
+        );
+        self.trace.push(TracePoint::new(&progress));
+        if let Some(observer) = &mut self.observer {
+            observer.on_improvement(&progress);
+        }
+    }
+
+    // Ends an iteration: counts the stagnation and checks every criterion
+    pub fn next_iteration(&mut self, best_distance: f64) -> bool {
+        self.iterations += 1;
+        if best_distance < self.best_distance {
+            self.best_distance = best_distance;
+            self.stagnation = 0;
+        } else {
+            self.stagnation += 1;
+        }
+        if self.reason.is_none()
+            && self
+                .criteria
+                .max_stagnation
+                .is_some_and(|max| self.stagnation >= max)
+        {
+            self.reason = Some(StopReason::Stagnation);
+        }
+        self.should_stop(best_distance)
+    }
+
+    // Checks time, evaluations and target without ending an iteration, for long inner loops
+    pub fn should_stop(&mut self, best_distance: f64) -> bool {
+        if self.reason.is_some() {
+            return true;
+        }
+        if is_interrupted() {
+            self.reason = Some(StopReason::Interrupted);
+        } else if self
+            .criteria
+            .target_distance
+            .is_some_and(|target| best_distance <= target)
+        {
+            self.reason = Some(StopReason::TargetReached);
+        } else if self
+            .criteria
+            .max_evaluations
+            .is_some_and(|max| self.evaluations >= max)
+        {
+            self.reason = Some(StopReason::EvaluationLimit);
+        } else if self
+            .criteria
+            .max_time
+            .is_some_and(|max| self.get_elapsed() >= max)
+        {
+            self.reason = Some(StopReason::TimeLimit);
+        }
+        self.reason.is_some()
+    }
+
+    // An algorithm's own limit, other than completing its search
+    pub fn stop(&mut self, reason: StopReason) {
+        self.reason.get_or_insert(reason);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.reason.is_some()
+    }
+
+    pub fn get_reason(&self) -> StopReason {
+        self.reason.unwrap_or_default()
+    }
+
+    pub fn get_iterations(&self) -> u64 {
+        self.iterations
+    }
+
+    pub fn get_evaluations(&self) -> u64 {
+        self.evaluations
+    }
+
+    pub fn get_trace(&self) -> &Vec<TracePoint> {
+        &self.trace
+    }
+}
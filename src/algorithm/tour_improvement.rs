@@ -0,0 +1,91 @@
+use std::error::Error;
+
+use super::algorithm::{Algorithm, ExecuteResponse};
+use super::local_search::LocalSearch;
+use super::parameters::{LocalSearchParameters, describe_parameters};
+use super::stopping_criteria::Termination;
+use crate::models::city::City;
+
+// Local search alone: improves the initial tour (the nearest neighbour tour when none is
+// given) until no move of the method shortens it
+pub struct TourImprovement {
+    cities: Vec<City>,
+    size: usize,
+    distance_matrix: Vec<f64>,
+    initial_path: Option<Vec<u16>>,
+    local_search: LocalSearch,
+    parameters: LocalSearchParameters,
+    termination: Termination,
+}
+
+impl TourImprovement {
+    pub fn new(
+        cities: &[City],
+        distance_matrix: &[f64],
+        parameters: &LocalSearchParameters,
+    ) -> Self {
+        TourImprovement {
+            cities: cities.to_vec(),
+            size: cities.len(),
+            distance_matrix: distance_matrix.to_vec(),
+            initial_path: None,
+            local_search: LocalSearch::new(
+                distance_matrix,
+                cities.len(),
+                parameters.method,
+                parameters.neighbours,
+            ),
+            parameters: parameters.clone(),
+            termination: Termination::default(),
+        }
+    }
+}
+
+impl Algorithm for TourImprovement {
+    fn execute(&mut self) -> ExecuteResponse {
+        println!("Execute TourImprovement");
+        self.termination.start();
+
+        let initial_path = match self.initial_path.take() {
+            Some(path) => path,
+            None => Self::create_nearest_neighbour_path(&self.distance_matrix, self.size),
+        };
+        let initial_cost = Self::calculate_path_distance(&initial_path, &self.distance_matrix);
+        self.termination.add_evaluations(1);
+        self.termination.report_best(initial_cost, &initial_path);
+
+        let mut path = initial_path.clone();
+        let cost =
+            self.local_search
+                .improve(&self.distance_matrix, &mut path, &mut self.termination);
+        self.termination.add_evaluations(1);
+        self.termination.report_best(cost, &path);
+
+        let mut response = ExecuteResponse::new(
+            initial_path,
+            path,
+            cost,
+            self.termination.get_elapsed(),
+            format!(
+                "Initial Distance: {}\nParameters: {}",
+                initial_cost,
+                describe_parameters(&self.parameters)
+            ),
+        );
+        response.set_termination(&self.termination);
+        response
+    }
+
+    fn get_termination(&mut self) -> &mut Termination {
+        &mut self.termination
+    }
+
+    fn set_initial_tour(&mut self, tour: &[u16]) {
+        self.initial_path = Some(tour.to_vec());
+    }
+
+    // A run takes seconds at most and writes no checkpoints
+    fn resume(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        Err(format!("{}: LS runs cannot be resumed, run them again", path).into())
+    }
+}
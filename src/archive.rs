@@ -0,0 +1,305 @@
+use std::{
+    error::Error,
+    fs::{create_dir, create_dir_all, read_dir, write},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use csv::Writer;
+use serde::Serialize;
+
+use crate::models::run_record::RunRecord;
+
+pub const DEFAULT_ARCHIVE_PATH: &str = "src/assets/archive";
+
+// Every run kept in its own folder, <root>/<instance>/<ALG>/<timestamp>_<seed>, with its
+// result.json and whatever else the caller writes next to it
+pub struct ResultArchive {
+    root: PathBuf,
+}
+
+pub struct ArchivedRun {
+    folder: PathBuf,
+    record: RunRecord,
+}
+
+impl ArchivedRun {
+    pub fn new(folder: PathBuf, record: RunRecord) -> Self {
+        ArchivedRun { folder, record }
+    }
+
+    pub fn get_folder(&self) -> &PathBuf {
+        &self.folder
+    }
+
+    pub fn get_record(&self) -> &RunRecord {
+        &self.record
+    }
+}
+
+impl ResultArchive {
+    pub fn new(root: &str) -> Self {
+        ResultArchive {
+            root: PathBuf::from(root),
+        }
+    }
+
+    // Creates the folder of the run and writes its result.json. Runs of the same second and
+    // seed, e.g. parallel experiments, get a numbered suffix.
+    pub fn store(&self, record: &RunRecord) -> Result<PathBuf, Box<dyn Error>> {
+        let parent = self
+            .root
+            .join(record.get_instance())
+            .join(record.get_algorithm());
+        create_dir_all(&parent)?;
+        let mut name = record.get_timestamp().unwrap_or_default().to_string();
+        if let Some(seed) = record.get_seed() {
+            name = format!("{}_{}", name, seed);
+        }
+
+        let mut folder = parent.join(&name);
+        let mut suffix = 1;
+        loop {
+            match create_dir(&folder) {
+                Ok(()) => break,
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    suffix += 1;
+                    folder = parent.join(format!("{}_{}", name, suffix));
+                }
+                Err(err) => return Err(format!("{}: {}", folder.display(), err).into()),
+            }
+        }
+        record.write_json(&folder.join("result.json").to_string_lossy())?;
+        Ok(folder)
+    }
+
+    // Every result.json under the root, oldest first. The root may also be a results folder
+    // whose metadata.txt files were migrated.
+    pub fn load(&self) -> Result<Vec<ArchivedRun>, Box<dyn Error>> {
+        let mut files = vec![];
+        if self.root.exists() {
+            find_results(&self.root, &mut files)?;
+        }
+        let mut runs = vec![];
+        for file in files {
+            match RunRecord::read_json(&file.to_string_lossy()) {
+                Ok(record) => runs.push(ArchivedRun::new(
+                    file.parent().map(Path::to_path_buf).unwrap_or_default(),
+                    record,
+                )),
+                Err(err) => eprintln!("Skipping unreadable run: {}", err),
+            }
+        }
+        runs.sort_by_key(|run| run.record.get_timestamp());
+        Ok(runs)
+    }
+}
+
+fn find_results(folder: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let mut entries = read_dir(folder)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            find_results(&entry, files)?;
+        } else if entry.file_name().is_some_and(|name| name == "result.json") {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+// Runs of some algorithms, instances and/or parameter sets, an empty list keeps them all
+#[derive(Debug, Clone, Default)]
+pub struct RunFilter {
+    pub algorithms: Vec<String>,
+    pub instances: Vec<String>,
+    pub parameter_sets: Vec<String>,
+}
+
+impl RunFilter {
+    pub fn matches(&self, record: &RunRecord) -> bool {
+        let matches = |wanted: &Vec<String>, value: &String| {
+            wanted.is_empty() || wanted.iter().any(|w| w.eq_ignore_ascii_case(value))
+        };
+        matches(&self.algorithms, record.get_algorithm())
+            && matches(&self.instances, record.get_instance())
+            && matches(&self.parameter_sets, record.get_parameter_set())
+    }
+}
+
+// Shortest, then fastest
+fn is_better(run: &RunRecord, other: &RunRecord) -> bool {
+    run.get_distance() < other.get_distance()
+        || run.get_distance() == other.get_distance()
+            && run.get_timings().algorithm < other.get_timings().algorithm
+}
+
+// The best run of every instance and algorithm, by instance then algorithm
+pub fn best_runs<'a>(runs: &[&'a ArchivedRun]) -> Vec<&'a ArchivedRun> {
+    let mut best: Vec<&ArchivedRun> = vec![];
+    for &run in runs {
+        let position = best.iter().position(|other| {
+            other.record.get_instance() == run.record.get_instance()
+                && other.record.get_algorithm() == run.record.get_algorithm()
+        });
+        match position {
+            Some(position) if is_better(&run.record, &best[position].record) => {
+                best[position] = run
+            }
+            Some(_) => {}
+            None => best.push(run),
+        }
+    }
+    best.sort_by(|a, b| {
+        (a.record.get_instance(), a.record.get_algorithm())
+            .cmp(&(b.record.get_instance(), b.record.get_algorithm()))
+    });
+    best
+}
+
+// One entry per algorithm and parameter set of an instance, ranked by their best distance
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderboardEntry {
+    instance: String,
+    rank: usize,
+    algorithm: String,
+    parameter_set: String,
+    runs: usize,
+    best: f64,
+    mean: f64,
+    // % above the best distance of the instance
+    gap: f64,
+    time_of_best: f64,
+    seed_of_best: Option<u64>,
+}
+
+impl LeaderboardEntry {
+    pub fn describe(&self) -> String {
+        format!(
+            "{:<10} {:>4} {:<6} {:<12} {:>5} {:>14.4} {:>14.4} {:>9.4} {:>10.3}",
+            self.instance,
+            self.rank,
+            self.algorithm,
+            self.parameter_set,
+            self.runs,
+            self.best,
+            self.mean,
+            self.gap,
+            self.time_of_best
+        )
+    }
+
+    pub fn describe_header() -> String {
+        format!(
+            "{:<10} {:>4} {:<6} {:<12} {:>5} {:>14} {:>14} {:>9} {:>10}",
+            "instance", "rank", "alg", "parameters", "runs", "best", "mean", "gap %", "best_time"
+        )
+    }
+}
+
+pub fn leaderboard(runs: &[&ArchivedRun]) -> Vec<LeaderboardEntry> {
+    let mut cells: Vec<(&String, &String, &String)> = vec![];
+    for run in runs {
+        let cell = (
+            run.record.get_instance(),
+            run.record.get_algorithm(),
+            run.record.get_parameter_set(),
+        );
+        if !cells.contains(&cell) {
+            cells.push(cell);
+        }
+    }
+
+    let mut entries: Vec<LeaderboardEntry> = cells
+        .into_iter()
+        .map(|(instance, algorithm, parameter_set)| {
+            let cell_runs: Vec<&RunRecord> = runs
+                .iter()
+                .map(|run| &run.record)
+                .filter(|record| {
+                    record.get_instance() == instance
+                        && record.get_algorithm() == algorithm
+                        && record.get_parameter_set() == parameter_set
+                })
+                .collect();
+            let best = cell_runs
+                .iter()
+                .copied()
+                .reduce(|best, run| if is_better(run, best) { run } else { best })
+                .unwrap();
+            LeaderboardEntry {
+                instance: instance.clone(),
+                rank: 0,
+                algorithm: algorithm.clone(),
+                parameter_set: parameter_set.clone(),
+                runs: cell_runs.len(),
+                best: best.get_distance(),
+                mean: cell_runs.iter().map(|run| run.get_distance()).sum::<f64>()
+                    / cell_runs.len() as f64,
+                gap: 0.0,
+                time_of_best: best.get_timings().algorithm,
+                seed_of_best: best.get_seed(),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| {
+        a.instance
+            .cmp(&b.instance)
+            .then(a.best.total_cmp(&b.best))
+            .then(a.mean.total_cmp(&b.mean))
+    });
+
+    // ties share the rank, up to the rounding of the sums of the tours
+    let same_distance = |a: f64, b: f64| (a - b).abs() <= 1e-9 * a.abs().max(1.0);
+    let mut instance_best = 0.0;
+    for i in 0..entries.len() {
+        if i == 0 || entries[i].instance != entries[i - 1].instance {
+            instance_best = entries[i].best;
+            entries[i].rank = 1;
+        } else if same_distance(entries[i].best, entries[i - 1].best) {
+            entries[i].rank = entries[i - 1].rank;
+        } else {
+            entries[i].rank = entries[i - 1].rank + 1;
+        }
+        entries[i].gap = if instance_best > 0.0 {
+            (entries[i].best - instance_best) / instance_best * 100.0
+        } else {
+            0.0
+        };
+    }
+    entries
+}
+
+// Markdown table when the file ends in .md, CSV otherwise
+pub fn write_leaderboard(path: &str, entries: &[LeaderboardEntry]) -> Result<(), Box<dyn Error>> {
+    if path.ends_with(".md") {
+        let mut text = String::from(
+            "| Instance | Rank | Algorithm | Parameters | Runs | Best | Mean | Gap (%) | Time of best (s) |\n\
+             |---|---:|---|---|---:|---:|---:|---:|---:|\n",
+        );
+        for entry in entries {
+            text.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {:.4} | {:.4} | {:.4} | {:.3} |\n",
+                entry.instance,
+                entry.rank,
+                entry.algorithm,
+                entry.parameter_set,
+                entry.runs,
+                entry.best,
+                entry.mean,
+                entry.gap,
+                entry.time_of_best
+            ));
+        }
+        write(path, text)?;
+        return Ok(());
+    }
+    let mut writer = Writer::from_path(path)?;
+    for entry in entries {
+        writer.serialize(entry)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
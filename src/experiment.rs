@@ -0,0 +1,631 @@
+use std::{
+    error::Error,
+    fs::create_dir_all,
+    path::Path,
+    process::exit,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+    time::Duration,
+};
+
+use csv::Writer;
+use serde::Serialize;
+
+use crate::algorithm::{
+    algorithm::ExecuteResponse,
+    algorithm_strategy::{ALGORITHMS, AlgorithmStrategy, RunOptions},
+    parameters::AlgorithmParameters,
+    progress::write_convergence_csv,
+    stopping_criteria::{StoppingCriteria, install_interrupt_handler, is_interrupted},
+};
+use crate::archive::{DEFAULT_ARCHIVE_PATH, ResultArchive};
+use crate::models::{
+    city::City,
+    city_importer::{CityColumns, CityImporter, DEFAULT_CITIES_PATH},
+    distance_metric::DistanceMetric,
+    run_record::{PhaseTimings, RunRecord},
+    tsplib::TsplibInstance,
+    uf::UF,
+};
+
+#[derive(clap::Args)]
+pub struct ExperimentArgs {
+    #[arg(short = 'a', long, value_delimiter = ',', required = true)]
+    algorithms: Vec<String>,
+
+    #[arg(short = 'u', long, value_delimiter = ',')]
+    ufs: Vec<String>,
+
+    #[arg(long, value_delimiter = ',')]
+    tsplib: Vec<String>,
+
+    // one parameter set per file, named by the file stem; none: the defaults
+    #[arg(long, value_delimiter = ',')]
+    configs: Vec<String>,
+
+    // applied to every parameter set
+    #[arg(long = "param", value_name = "ALG.KEY=VALUE")]
+    params: Vec<String>,
+
+    #[arg(short = 'r', long, default_value_t = 10)]
+    repetitions: usize,
+
+    // runs at the same time, 0: one per core
+    #[arg(short = 'j', long, default_value_t = 1)]
+    jobs: usize,
+
+    // every run gets its own seed derived from this one, random when not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(short = 'm', long, default_value = "EUCLIDEAN")]
+    metric: String,
+
+    #[arg(long, default_value = DEFAULT_CITIES_PATH)]
+    cities: String,
+
+    // seconds
+    #[arg(long)]
+    max_time: Option<f64>,
+
+    #[arg(long)]
+    max_evaluations: Option<u64>,
+
+    #[arg(long)]
+    target_distance: Option<f64>,
+
+    #[arg(long)]
+    max_stagnation: Option<usize>,
+
+    // runs.csv and summary.csv go here
+    #[arg(short = 'o', long, default_value = "src/assets/outputs/experiment")]
+    output: String,
+
+    // every run is kept in its own folder here
+    #[arg(long, default_value = DEFAULT_ARCHIVE_PATH)]
+    archive: String,
+
+    #[arg(long)]
+    no_archive: bool,
+}
+
+// Cities and distances of a UF or a TSPLIB instance, shared by every run on it
+pub struct Instance {
+    name: String,
+    cities: Vec<City>,
+    distance_matrix: Vec<f64>,
+    symmetric: bool,
+    // description of the TSPLIB distances, none for the coordinates of a uf
+    matrix: Option<String>,
+}
+
+impl Instance {
+    pub fn from_uf(
+        cities_path: &str,
+        uf: &str,
+        metric: &DistanceMetric,
+    ) -> Result<Self, Box<dyn Error>> {
+        let uf = UF::get_uf_from_str(uf).ok_or_else(|| format!("unknown uf '{}'", uf))?;
+        let cities = CityImporter::new(b',', CityColumns::default()).read_from_file(
+            cities_path,
+            &uf,
+            None,
+        )?;
+        Ok(Instance {
+            name: uf.get_name().to_uppercase(),
+            distance_matrix: metric.create_distance_matrix(&cities),
+            cities,
+            symmetric: true,
+            matrix: None,
+        })
+    }
+
+    pub fn from_tsplib(path: &str) -> Result<Self, Box<dyn Error>> {
+        let instance =
+            TsplibInstance::read_from_file(path).map_err(|err| format!("{}: {}", path, err))?;
+        Ok(Instance {
+            name: instance.get_name().clone(),
+            cities: instance.get_cities().clone(),
+            distance_matrix: instance.get_distance_matrix().clone(),
+            symmetric: instance.is_symmetric(),
+            matrix: Some(instance.get_description()),
+        })
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+}
+
+// Named parameters of every algorithm, the file stem of its config or "default"
+pub struct ParameterSet {
+    name: String,
+    parameters: AlgorithmParameters,
+}
+
+impl ParameterSet {
+    pub fn new(name: String, parameters: AlgorithmParameters) -> Self {
+        ParameterSet { name, parameters }
+    }
+}
+
+// One row per run
+#[derive(Debug, Clone, Serialize)]
+pub struct RunResult {
+    algorithm: String,
+    instance: String,
+    parameter_set: String,
+    repetition: usize,
+    seed: u64,
+    distance: f64,
+    // seconds
+    time: f64,
+    evaluations: u64,
+    stop_reason: String,
+}
+
+// Statistics of the runs of one algorithm, instance and parameter set
+#[derive(Debug, Clone, Serialize)]
+pub struct CellSummary {
+    algorithm: String,
+    instance: String,
+    parameter_set: String,
+    runs: usize,
+    mean: f64,
+    best: f64,
+    worst: f64,
+    // sample standard deviation, 0 for a single run
+    std_dev: f64,
+    mean_time: f64,
+    time_of_best: f64,
+}
+
+impl CellSummary {
+    pub fn describe(&self) -> String {
+        format!(
+            "{:<6} {:<10} {:<12} {:>4} {:>14.4} {:>14.4} {:>14.4} {:>12.4} {:>10.3} {:>10.3}",
+            self.algorithm,
+            self.instance,
+            self.parameter_set,
+            self.runs,
+            self.mean,
+            self.best,
+            self.worst,
+            self.std_dev,
+            self.mean_time,
+            self.time_of_best
+        )
+    }
+
+    pub fn describe_header() -> String {
+        format!(
+            "{:<6} {:<10} {:<12} {:>4} {:>14} {:>14} {:>14} {:>12} {:>10} {:>10}",
+            "alg",
+            "instance",
+            "parameters",
+            "runs",
+            "mean",
+            "best",
+            "worst",
+            "std_dev",
+            "mean_time",
+            "best_time"
+        )
+    }
+}
+
+// Every algorithm × instance × parameter set, repeated. Run i gets the seed derived from
+// the experiment seed and i, so the results do not depend on the number of jobs.
+pub struct Experiment {
+    algorithms: Vec<String>,
+    instances: Vec<Instance>,
+    parameter_sets: Vec<ParameterSet>,
+    repetitions: usize,
+    jobs: usize,
+    seed: u64,
+    metric: DistanceMetric,
+    stopping_criteria: StoppingCriteria,
+    archive: Option<ResultArchive>,
+}
+
+struct Job<'a> {
+    algorithm: &'a str,
+    instance: &'a Instance,
+    parameter_set: &'a ParameterSet,
+    repetition: usize,
+    seed: u64,
+}
+
+impl Experiment {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        algorithms: Vec<String>,
+        instances: Vec<Instance>,
+        parameter_sets: Vec<ParameterSet>,
+        repetitions: usize,
+        jobs: usize,
+        seed: u64,
+        metric: DistanceMetric,
+        stopping_criteria: StoppingCriteria,
+    ) -> Self {
+        Experiment {
+            algorithms: algorithms.iter().map(|a| a.to_uppercase()).collect(),
+            instances,
+            parameter_sets,
+            repetitions,
+            jobs: jobs.max(1),
+            seed,
+            metric,
+            stopping_criteria,
+            archive: None,
+        }
+    }
+
+    // Keeps every run in the archive as well
+    pub fn set_archive(&mut self, archive: ResultArchive) {
+        self.archive = Some(archive);
+    }
+
+    // Checks every parameter set for every algorithm and instance before anything runs
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(algorithm) = self
+            .algorithms
+            .iter()
+            .find(|algorithm| !ALGORITHMS.contains(&algorithm.as_str()))
+        {
+            return Err(format!(
+                "unknown algorithm '{}' ({})",
+                algorithm,
+                ALGORITHMS.join(", ")
+            ));
+        }
+        for set in &self.parameter_sets {
+            for algorithm in &self.algorithms {
+                for instance in &self.instances {
+                    set.parameters
+                        .validate(algorithm, instance.cities.len())
+                        .map_err(|err| format!("{} on {}: {}", set.name, instance.name, err))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn create_jobs(&self) -> Vec<Job<'_>> {
+        let mut jobs = vec![];
+        for instance in &self.instances {
+            for algorithm in &self.algorithms {
+                for parameter_set in &self.parameter_sets {
+                    for repetition in 1..=self.repetitions {
+                        jobs.push(Job {
+                            algorithm,
+                            instance,
+                            parameter_set,
+                            repetition,
+                            seed: derive_seed(self.seed, jobs.len() as u64),
+                        });
+                    }
+                }
+            }
+        }
+        jobs
+    }
+
+    // Runs the jobs on `jobs` threads, in the order of the matrix. After an interruption the
+    // runs in progress return their best tour and the others are skipped.
+    pub fn run(&self) -> Vec<RunResult> {
+        let jobs = self.create_jobs();
+        let next_job = AtomicUsize::new(0);
+        let finished = AtomicUsize::new(0);
+        let results = Mutex::new(vec![]);
+        thread::scope(|scope| {
+            for _ in 0..self.jobs.min(jobs.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let index = next_job.fetch_add(1, Ordering::Relaxed);
+                        if index >= jobs.len() || is_interrupted() {
+                            break;
+                        }
+                        let result = self.run_job(&jobs[index]);
+                        eprintln!(
+                            "[{}/{}] {} {} {} #{}: {} ({:.3}s)",
+                            finished.fetch_add(1, Ordering::Relaxed) + 1,
+                            jobs.len(),
+                            result.algorithm,
+                            result.instance,
+                            result.parameter_set,
+                            result.repetition,
+                            result.distance,
+                            result.time
+                        );
+                        results.lock().unwrap().push((index, result));
+                    }
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|(index, _)| *index);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    fn run_job(&self, job: &Job) -> RunResult {
+        let instance = job.instance;
+        let response = AlgorithmStrategy::execute_algorithm(
+            job.algorithm,
+            &instance.cities,
+            &instance.distance_matrix,
+            instance.symmetric,
+            &self.metric,
+            &job.parameter_set.parameters,
+            RunOptions {
+                seed: job.seed,
+                stopping_criteria: self.stopping_criteria.clone(),
+                ..RunOptions::default()
+            },
+        )
+        // validate refuses unknown algorithms and experiments never resume
+        .unwrap();
+        if let Some(archive) = &self.archive
+            && let Err(err) = self.archive_run(archive, job, &response)
+        {
+            eprintln!("Error archiving the run: {}", err);
+        }
+        RunResult {
+            algorithm: job.algorithm.to_string(),
+            instance: instance.name.clone(),
+            parameter_set: job.parameter_set.name.clone(),
+            repetition: job.repetition,
+            seed: job.seed,
+            distance: *response.get_distance(),
+            time: response.get_total_time().as_secs_f64(),
+            evaluations: response.get_evaluations(),
+            stop_reason: response.get_stop_reason().get_name().to_string(),
+        }
+    }
+
+    fn archive_run(
+        &self,
+        archive: &ResultArchive,
+        job: &Job,
+        response: &ExecuteResponse,
+    ) -> Result<(), Box<dyn Error>> {
+        let instance = job.instance;
+        // Branch and Bound returns the tour closed on the first city
+        let mut path = response.get_final_path().clone();
+        if path.len() == instance.cities.len() + 1 && path.first() == path.last() {
+            path.pop();
+        }
+        let mut record = RunRecord::new(
+            job.algorithm,
+            &instance.name,
+            *response.get_distance(),
+            path.iter()
+                .map(|&id| instance.cities[id as usize].get_code())
+                .collect(),
+        );
+        record.set_parameters(
+            &job.parameter_set.name,
+            job.parameter_set.parameters.get_section(job.algorithm),
+        );
+        record.set_seed(job.seed);
+        record.set_distance_source(&self.metric, instance.matrix.clone());
+        if instance.matrix.is_none() {
+            record.measure_distances(&instance.cities, &path);
+        }
+        record.set_termination(
+            response.get_iterations(),
+            response.get_evaluations(),
+            response.get_stop_reason().get_name(),
+            response.is_optimal(),
+        );
+        record.set_info(response.get_metadata_info());
+        // the instance is loaded once for every run
+        let time = response.get_total_time().as_secs_f64();
+        record.set_timings(PhaseTimings {
+            algorithm: time,
+            total: time,
+            ..PhaseTimings::default()
+        });
+
+        let folder = archive.store(&record)?;
+        write_convergence_csv(
+            &folder.join("convergence.csv").to_string_lossy(),
+            response.get_trace(),
+        )?;
+        Ok(())
+    }
+}
+
+// splitmix64 of the experiment seed and the run index
+fn derive_seed(seed: u64, index: u64) -> u64 {
+    let mut z = seed.wrapping_add(index.wrapping_add(1).wrapping_mul(0x9E3779B97F4A7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+// One summary per algorithm, instance and parameter set, in the order of the runs
+pub fn summarize(results: &[RunResult]) -> Vec<CellSummary> {
+    let mut cells: Vec<(&str, &str, &str)> = vec![];
+    for result in results {
+        let cell = (
+            result.algorithm.as_str(),
+            result.instance.as_str(),
+            result.parameter_set.as_str(),
+        );
+        if !cells.contains(&cell) {
+            cells.push(cell);
+        }
+    }
+    cells
+        .into_iter()
+        .map(|(algorithm, instance, parameter_set)| {
+            let runs: Vec<&RunResult> = results
+                .iter()
+                .filter(|r| {
+                    r.algorithm == algorithm
+                        && r.instance == instance
+                        && r.parameter_set == parameter_set
+                })
+                .collect();
+            let count = runs.len() as f64;
+            let mean = runs.iter().map(|r| r.distance).sum::<f64>() / count;
+            let variance = if runs.len() > 1 {
+                runs.iter()
+                    .map(|r| (r.distance - mean).powi(2))
+                    .sum::<f64>()
+                    / (count - 1.0)
+            } else {
+                0.0
+            };
+            let best = runs
+                .iter()
+                .min_by(|a, b| a.distance.total_cmp(&b.distance))
+                .unwrap();
+            CellSummary {
+                algorithm: algorithm.to_string(),
+                instance: instance.to_string(),
+                parameter_set: parameter_set.to_string(),
+                runs: runs.len(),
+                mean,
+                best: best.distance,
+                worst: runs.iter().map(|r| r.distance).fold(f64::MIN, f64::max),
+                std_dev: variance.sqrt(),
+                mean_time: runs.iter().map(|r| r.time).sum::<f64>() / count,
+                time_of_best: best.time,
+            }
+        })
+        .collect()
+}
+
+// runs.csv and summary.csv in the folder
+pub fn write_results(
+    folder: &str,
+    results: &[RunResult],
+    summaries: &[CellSummary],
+) -> Result<(), Box<dyn Error>> {
+    create_dir_all(folder)?;
+    write_csv(&Path::new(folder).join("runs.csv"), results)?;
+    write_csv(&Path::new(folder).join("summary.csv"), summaries)?;
+    Ok(())
+}
+
+fn write_csv<T: Serialize>(path: &Path, rows: &[T]) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::from_path(path)?;
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn run_experiment(args: ExperimentArgs) {
+    let metric = DistanceMetric::get_metric_from_str(&args.metric).unwrap_or_else(|| {
+        eprintln!("Unknown metric '{}'", args.metric);
+        exit(1);
+    });
+    if args.ufs.is_empty() && args.tsplib.is_empty() {
+        eprintln!("Give the instances with --ufs and/or --tsplib");
+        exit(1);
+    }
+    if args.repetitions == 0 {
+        eprintln!("--repetitions must be at least 1");
+        exit(1);
+    }
+    let max_time = match args.max_time {
+        Some(seconds) if !(seconds.is_finite() && seconds > 0.0) => {
+            eprintln!("--max-time must be a positive number of seconds");
+            exit(1);
+        }
+        max_time => max_time.map(Duration::from_secs_f64),
+    };
+    let stopping_criteria = StoppingCriteria {
+        max_time,
+        max_evaluations: args.max_evaluations,
+        target_distance: args.target_distance,
+        max_stagnation: args.max_stagnation,
+    };
+
+    let instances: Vec<Instance> = args
+        .ufs
+        .iter()
+        .map(|uf| Instance::from_uf(&args.cities, uf, &metric))
+        .chain(args.tsplib.iter().map(|path| Instance::from_tsplib(path)))
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|err| {
+            eprintln!("Error reading instance: {}", err);
+            exit(1);
+        });
+
+    let configs = if args.configs.is_empty() {
+        vec![None]
+    } else {
+        args.configs.iter().map(Some).collect()
+    };
+    let parameter_sets = configs
+        .into_iter()
+        .map(|config| {
+            let (name, mut parameters) = match config {
+                Some(path) => (
+                    Path::new(path)
+                        .file_stem()
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string(),
+                    AlgorithmParameters::read_from_file(path)?,
+                ),
+                None => ("default".to_string(), AlgorithmParameters::default()),
+            };
+            for assignment in &args.params {
+                parameters.apply_override(assignment)?;
+            }
+            Ok(ParameterSet::new(name, parameters))
+        })
+        .collect::<Result<Vec<_>, Box<dyn Error>>>()
+        .unwrap_or_else(|err| {
+            eprintln!("Error reading parameters: {}", err);
+            exit(1);
+        });
+
+    let jobs = match args.jobs {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        jobs => jobs,
+    };
+    let seed = args.seed.unwrap_or_else(rand::random);
+    let mut experiment = Experiment::new(
+        args.algorithms.clone(),
+        instances,
+        parameter_sets,
+        args.repetitions,
+        jobs,
+        seed,
+        metric,
+        stopping_criteria,
+    );
+    if let Err(err) = experiment.validate() {
+        eprintln!("Invalid experiment: {}", err);
+        exit(1);
+    }
+    if !args.no_archive {
+        experiment.set_archive(ResultArchive::new(&args.archive));
+    }
+
+    // The first SIGINT/SIGTERM stops the runs in progress and skips the others
+    install_interrupt_handler("Interrupted, finishing the runs in progress (again to quit)");
+
+    eprintln!("Experiment seed: {}", seed);
+    let results = experiment.run();
+    let summaries = summarize(&results);
+    write_results(&args.output, &results, &summaries).unwrap_or_else(|err| {
+        eprintln!("Error writing the results to {}: {}", args.output, err);
+        exit(1);
+    });
+
+    println!("{}", CellSummary::describe_header());
+    for summary in &summaries {
+        println!("{}", summary.describe());
+    }
+}
@@ -3,33 +3,64 @@
 #![allow(dead_code)]
 
 mod algorithm;
+mod archive;
+mod experiment;
 mod models;
+mod report;
+mod statistics;
 
-use algorithm::algorithm_strategy::AlgorithmStrategy;
-use clap::Parser;
+use algorithm::{
+    algorithm_strategy::{AlgorithmStrategy, RunOptions},
+    checkpoint::{CheckpointInstance, CheckpointSettings},
+    island_model::write_island_stats_csv,
+    lower_bound::{BoundKind, compute_lower_bounds, get_best_bound},
+    operator_selector::write_operator_stats_csv,
+    parameters::AlgorithmParameters,
+    progress::{Observer, ProgressPrinter, write_convergence_csv},
+    stopping_criteria::{StoppingCriteria, install_interrupt_handler},
+};
+use archive::{
+    ArchivedRun, DEFAULT_ARCHIVE_PATH, LeaderboardEntry, ResultArchive, RunFilter, best_runs,
+    leaderboard, write_leaderboard,
+};
+use clap::{Parser, Subcommand};
 use core::f32;
-use csv::Reader;
+use experiment::{ExperimentArgs, run_experiment};
 use models::{
     city::City,
+    city_importer::{CityColumns, CityImporter, DEFAULT_CITIES_PATH},
     coordinates::Coordinates,
+    distance_metric::DistanceMetric,
     graph_metadata::GraphMetadata,
+    initial_tour::InitialTour,
+    road_matrix::RoadMatrix,
+    run_record::{PhaseTimings, RunRecord},
+    tsplib::TsplibInstance,
     uf::{UF, UFEnum},
 };
 use plotters::{
     chart::ChartBuilder,
-    prelude::{BitMapBackend, Circle, IntoDrawingArea, PathElement},
+    prelude::{BitMapBackend, Circle, IntoDrawingArea, PathElement, Text},
     style::{BLUE, Color, RED, WHITE},
 };
+use report::{Report, TableFormat, read_best_known};
 use std::{
+    collections::HashMap,
     error::Error,
-    fs::{File, create_dir_all},
+    fs::{File, create_dir_all, read_dir, write},
     iter::once,
     path::Path,
+    process::exit,
+    thread,
+    time::{Duration, Instant},
 };
 
 #[derive(Parser)]
-#[command(name = "Optimizer")]
+#[command(name = "Optimizer", args_conflicts_with_subcommands = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(short = 'a', long, default_value = "G")]
     algorithm: String,
 
@@ -38,20 +69,272 @@ struct Args {
 
     #[arg(short = 'p', long)]
     plot: bool,
+
+    #[arg(short = 'm', long, default_value = "EUCLIDEAN")]
+    metric: String,
+
+    #[arg(long)]
+    matrix: Option<String>,
+
+    #[arg(long, default_value = "km")]
+    matrix_unit: String,
+
+    #[arg(long, conflicts_with = "matrix")]
+    tsplib: Option<String>,
+
+    #[arg(long)]
+    tour: Option<String>,
+
+    #[arg(long)]
+    tour_csv: Option<String>,
+
+    #[arg(long)]
+    operator_stats: Option<String>,
+
+    // result.json of the run
+    #[arg(long)]
+    result: Option<String>,
+
+    // appends the run as a row, creating the file with a header
+    #[arg(long)]
+    result_csv: Option<String>,
+
+    // every run is kept in its own folder here
+    #[arg(long, default_value = DEFAULT_ARCHIVE_PATH)]
+    archive: String,
+
+    #[arg(long)]
+    no_archive: bool,
+
+    #[arg(long, default_value = DEFAULT_CITIES_PATH)]
+    cities: String,
+
+    #[arg(long, default_value = ",")]
+    delimiter: String,
+
+    #[arg(long, default_value = "codigo_ibge")]
+    id_column: String,
+
+    #[arg(long, default_value = "nome")]
+    name_column: String,
+
+    #[arg(long, default_value = "latitude")]
+    latitude_column: String,
+
+    #[arg(long, default_value = "longitude")]
+    longitude_column: String,
+
+    #[arg(long, default_value = "codigo_uf")]
+    region_column: String,
+
+    #[arg(long, default_value = "capital")]
+    capital_column: String,
+
+    #[arg(long, default_value = "fuso_horario")]
+    timezone_column: String,
+
+    #[arg(long)]
+    region: Option<String>,
+
+    #[arg(long)]
+    config: Option<String>,
+
+    #[arg(long = "param", value_name = "ALG.KEY=VALUE")]
+    params: Vec<String>,
+
+    // random when not given, the seed used is always written to the metadata
+    #[arg(long)]
+    seed: Option<u64>,
+
+    // seconds
+    #[arg(long)]
+    max_time: Option<f64>,
+
+    #[arg(long)]
+    max_evaluations: Option<u64>,
+
+    #[arg(long)]
+    target_distance: Option<f64>,
+
+    #[arg(long)]
+    max_stagnation: Option<usize>,
+
+    // prints every improvement of the best distance to stderr
+    #[arg(long)]
+    progress: bool,
+
+    #[arg(long)]
+    convergence: Option<String>,
+
+    // saves the state of the run to this file, periodically and at the end
+    #[arg(long)]
+    checkpoint: Option<String>,
+
+    // seconds between checkpoints
+    #[arg(long, default_value_t = 60.0)]
+    checkpoint_interval: f64,
+
+    // continues the run saved in this checkpoint
+    #[arg(long)]
+    resume: Option<String>,
+
+    // starts from this tour: metadata.txt of a previous run, TSPLIB .tour or CSV of codes
+    #[arg(long, conflicts_with = "resume")]
+    initial_tour: Option<String>,
+
+    // tightest bound to compute for the gap: mst, one_tree, held_karp, lp or none. The
+    // default is cheap next to the run, held_karp and lp are asked for.
+    #[arg(long, default_value = "one_tree")]
+    lower_bound: String,
+
+    // subgradient iterations of the Held-Karp bound
+    #[arg(long, default_value_t = 100)]
+    bound_iterations: usize,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    // Runs every algorithm on every instance with every parameter set, repeated, and
+    // summarizes the distances and times
+    Experiment(Box<ExperimentArgs>),
+    // Writes a result.json next to every metadata.txt written before it existed
+    Migrate(MigrateArgs),
+    // Queries the runs kept in the archive
+    Results(ResultsArgs),
+    // Compares the algorithms of the archived runs with statistics and significance tests
+    Report(ReportArgs),
+}
+
+#[derive(clap::Args)]
+struct MigrateArgs {
+    // metadata.txt files, or folders searched for them
+    #[arg(default_value = "src/assets/results")]
+    paths: Vec<String>,
+
+    // maps the Path of the older files to the cities of their UF
+    #[arg(long, default_value = DEFAULT_CITIES_PATH)]
+    cities: String,
+
+    // replaces the result.json files that already exist
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(clap::Args)]
+struct FilterArgs {
+    // also a results folder with result.json files, see migrate
+    #[arg(long, default_value = DEFAULT_ARCHIVE_PATH)]
+    archive: String,
+
+    #[arg(short = 'a', long, value_delimiter = ',')]
+    algorithms: Vec<String>,
+
+    // ufs, TSPLIB names or "<cities file stem>/<region>"
+    #[arg(short = 'u', long, value_delimiter = ',')]
+    instances: Vec<String>,
+
+    // file stems of the configs, or "default"
+    #[arg(short = 'c', long, value_delimiter = ',')]
+    configs: Vec<String>,
+}
+
+impl FilterArgs {
+    fn load_runs(&self) -> Vec<ArchivedRun> {
+        let runs = ResultArchive::new(&self.archive)
+            .load()
+            .unwrap_or_else(|err| {
+                eprintln!("Error reading the archive {}: {}", self.archive, err);
+                exit(1);
+            });
+        let filter = RunFilter {
+            algorithms: self.algorithms.clone(),
+            instances: self.instances.clone(),
+            parameter_sets: self.configs.clone(),
+        };
+        runs.into_iter()
+            .filter(|run| filter.matches(run.get_record()))
+            .collect()
+    }
+}
+
+#[derive(clap::Args)]
+struct ReportArgs {
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    // "instance,distance" CSV, e.g. TSPLIB optima; otherwise the best run of each instance
+    #[arg(long)]
+    best_known: Option<String>,
+
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f64,
+
+    // markdown or latex, by default latex when the output ends in .tex
+    #[arg(short = 'f', long)]
+    format: Option<String>,
+
+    // printed when not given
+    #[arg(short = 'o', long)]
+    output: Option<String>,
+}
+
+#[derive(clap::Args)]
+struct ResultsArgs {
+    #[arg(value_enum)]
+    query: ResultsQuery,
+
+    #[command(flatten)]
+    filter: FilterArgs,
+
+    // CSV of the runs; for the leaderboard CSV, or markdown when it ends in .md
+    #[arg(short = 'o', long)]
+    output: Option<String>,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ResultsQuery {
+    // Best run of every instance and algorithm
+    Best,
+    // Every run, oldest first
+    Runs,
+    // Algorithms and parameter sets of every instance ranked by their best distance
+    Leaderboard,
+}
+
+const MAX_LABELLED_CITIES: usize = 30;
+
+type PlotBounds = ((f32, f32), (f32, f32));
+
+fn get_uf_plot_bounds(uf: &UF) -> PlotBounds {
+    let (min_x, max_x): (f32, f32) = *uf.get_min_max_longitude();
+    let (min_y, max_y): (f32, f32) = *uf.get_min_max_latitude();
+    ((min_x - 1.0, max_x + 1.0), (min_y - 1.0, max_y + 1.0))
+}
+
+fn get_cities_plot_bounds(cities: &[City]) -> PlotBounds {
+    let xs = cities.iter().map(|c| c.get_coordinates().get_longitude());
+    let ys = cities.iter().map(|c| c.get_coordinates().get_latitude());
+    let (min_x, max_x) = xs.fold((f32::MAX, f32::MIN), |(a, b), x| (a.min(x), b.max(x)));
+    let (min_y, max_y) = ys.fold((f32::MAX, f32::MIN), |(a, b), y| (a.min(y), b.max(y)));
+    let margin = ((max_x - min_x).max(max_y - min_y) * 0.05).max(f32::EPSILON);
+    (
+        (min_x - margin, max_x + margin),
+        (min_y - margin, max_y + margin),
+    )
 }
 
 fn plot_state(
-    cities: &Vec<City>,
+    cities: &[City],
     cities_path: &[u16],
     file_path: &str,
-    uf: &UF,
+    bounds: &PlotBounds,
 ) -> Result<(), Box<dyn Error>> {
-    let (min_x, max_x): (f32, f32) = uf.get_min_max_longitude().clone();
-    let (min_y, max_y): (f32, f32) = uf.get_min_max_latitude().clone();
+    let ((min_x, max_x), (min_y, max_y)) = *bounds;
     let image_size = (1024, 768);
     let font_style = "sans-serif";
     let caption_font_size = 30;
     let y_x_font_size = 20;
+    let label_font_size = 14;
 
     create_dir_all(Path::new(file_path).parent().unwrap())?;
 
@@ -81,7 +364,7 @@ fn plot_state(
         .margin(10)
         .x_label_area_size(70)
         .y_label_area_size(70)
-        .build_cartesian_2d(min_x - 1.0..max_x + 1.0, min_y - 1.0..max_y + 1.0)?;
+        .build_cartesian_2d(min_x..max_x, min_y..max_y)?;
 
     chart
         .configure_mesh()
@@ -98,113 +381,724 @@ fn plot_state(
             .map(|(&x, &y)| Circle::new((x, y), 2, RED.filled())),
     )?;
 
-    chart.draw_series(once(PathElement::new(path_lines, &BLUE)))?;
+    chart.draw_series(once(PathElement::new(path_lines, BLUE)))?;
+
+    // Small instances get every name, larger ones only the capitals and the starting city
+    let start_id = cities_path.first().copied();
+    chart.draw_series(
+        cities
+            .iter()
+            .filter(|city| {
+                cities.len() <= MAX_LABELLED_CITIES
+                    || city.is_capital()
+                    || Some(city.get_id()) == start_id
+            })
+            .map(|city| {
+                let coordinates = city.get_coordinates();
+                Text::new(
+                    city.get_name().clone(),
+                    (coordinates.get_longitude(), coordinates.get_latitude()),
+                    (font_style, label_font_size),
+                )
+            }),
+    )?;
 
     image.present()?;
     Ok(())
 }
 
-fn read_csv_cities(path: &str, uf: &UF) -> Vec<City> {
-    let file = File::open(path.to_string()).expect("Failed to open file");
-    let mut reader = Reader::from_reader(file);
-
-    let records: Vec<_> = reader.records().collect();
-
-    let mut cities: Vec<City> = Vec::with_capacity(records.len());
-
-    let mut id: u16 = 0;
-    for record in records.iter() {
-        match record {
-            Ok(city) => {
-                let city_uf = UF::get_uf_from_code(city[5].parse::<u8>().unwrap()).unwrap();
-                if uf.get_uf_enum() == city_uf.get_uf_enum() || *uf.get_uf_enum() == UFEnum::BRAZIL
-                {
-                    cities.push(City::new(
-                        id,
-                        UF::get_uf_from_code(city[5].parse::<u8>().unwrap()).unwrap(),
-                        Coordinates::new(
-                            city[2].parse::<f32>().unwrap(),
-                            city[3].parse::<f32>().unwrap(),
-                        ),
-                    ));
-                    id += 1;
-                }
-            }
+fn get_delimiter_from_str(str: &str) -> Option<u8> {
+    match str {
+        "\\t" | "tab" => Some(b'\t'),
+        _ if str.len() == 1 && str.is_ascii() => Some(str.as_bytes()[0]),
+        _ => None,
+    }
+}
+
+fn find_metadata_files(path: &Path, files: &mut Vec<String>) -> Result<(), Box<dyn Error>> {
+    if path.is_file() {
+        files.push(path.to_string_lossy().to_string());
+        return Ok(());
+    }
+    let mut entries = read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            find_metadata_files(&entry, files)?;
+        } else if entry.file_name().is_some_and(|name| name == "metadata.txt") {
+            files.push(entry.to_string_lossy().to_string());
+        }
+    }
+    Ok(())
+}
+
+fn run_migrate(args: MigrateArgs) {
+    let mut files = vec![];
+    for path in &args.paths {
+        find_metadata_files(Path::new(path), &mut files).unwrap_or_else(|err| {
+            eprintln!("Error reading {}: {}", path, err);
+            exit(1);
+        });
+    }
+
+    let mut failed = 0;
+    for file in &files {
+        let result_path = Path::new(file).with_file_name("result.json");
+        if result_path.exists() && !args.force {
+            println!("{}: skipped, result.json exists", file);
+            continue;
+        }
+        // the older files only have the positions of the cities of their UF
+        let cities = Path::new(file)
+            .parent()
+            .and_then(|folder| folder.parent())
+            .and_then(|folder| folder.file_name())
+            .and_then(|name| UF::get_uf_from_str(&name.to_string_lossy()))
+            .and_then(|uf| {
+                CityImporter::new(b',', CityColumns::default())
+                    .read_from_file(&args.cities, &uf, None)
+                    .ok()
+            });
+        let migrated = RunRecord::read_metadata_file(file, cities.as_deref()).and_then(|record| {
+            record.write_json(&result_path.to_string_lossy())?;
+            Ok(record)
+        });
+        match migrated {
+            Ok(record) => println!(
+                "{}: {} {} {}",
+                result_path.display(),
+                record.get_algorithm(),
+                record.get_instance(),
+                record.get_distance()
+            ),
             Err(err) => {
-                eprintln!("Error reading city in {} file: {}", path, err);
+                eprintln!("Error migrating {}", err);
+                failed += 1;
             }
         }
     }
+    if failed > 0 {
+        exit(1);
+    }
+}
+
+fn describe_run(run: &ArchivedRun) -> String {
+    let record = run.get_record();
+    format!(
+        "{:<10} {:<6} {:<12} {:>20} {:>14.4} {:>10.3} {:<16} {}",
+        record.get_instance(),
+        record.get_algorithm(),
+        record.get_parameter_set(),
+        record
+            .get_seed()
+            .map_or("-".to_string(), |seed| seed.to_string()),
+        record.get_distance(),
+        record.get_timings().algorithm,
+        record
+            .get_stop_reason()
+            .map_or("-", |reason| reason.as_str()),
+        run.get_folder().display()
+    )
+}
+
+fn run_results_query(args: ResultsArgs) {
+    let runs = args.filter.load_runs();
+    let runs: Vec<&ArchivedRun> = runs.iter().collect();
+
+    if let ResultsQuery::Leaderboard = args.query {
+        let entries = leaderboard(&runs);
+        println!("{}", LeaderboardEntry::describe_header());
+        for entry in &entries {
+            println!("{}", entry.describe());
+        }
+        if let Some(output) = &args.output {
+            write_leaderboard(output, &entries).unwrap_or_else(|err| {
+                eprintln!("Error writing {}: {}", output, err);
+                exit(1);
+            });
+        }
+        return;
+    }
 
-    cities
+    let runs = match args.query {
+        ResultsQuery::Best => best_runs(&runs),
+        _ => runs,
+    };
+    println!(
+        "{:<10} {:<6} {:<12} {:>20} {:>14} {:>10} {:<16} folder",
+        "instance", "alg", "parameters", "seed", "distance", "time", "stop_reason"
+    );
+    for run in &runs {
+        println!("{}", describe_run(run));
+    }
+    if let Some(output) = &args.output {
+        let records: Vec<&RunRecord> = runs.iter().map(|run| run.get_record()).collect();
+        RunRecord::write_csv(output, &records).unwrap_or_else(|err| {
+            eprintln!("Error writing {}: {}", output, err);
+            exit(1);
+        });
+    }
+}
+
+fn run_report(args: ReportArgs) {
+    let format_name = args.format.clone().unwrap_or_else(|| {
+        if args
+            .output
+            .as_ref()
+            .is_some_and(|output| output.ends_with(".tex"))
+        {
+            "latex".to_string()
+        } else {
+            "markdown".to_string()
+        }
+    });
+    let format = TableFormat::get_format_from_str(&format_name).unwrap_or_else(|| {
+        eprintln!("Unknown format '{}' (markdown or latex)", format_name);
+        exit(1);
+    });
+    let best_known = match &args.best_known {
+        Some(path) => read_best_known(path).unwrap_or_else(|err| {
+            eprintln!("Error reading the best-known distances: {}", err);
+            exit(1);
+        }),
+        None => HashMap::new(),
+    };
+
+    let runs = args.filter.load_runs();
+    let runs: Vec<&ArchivedRun> = runs.iter().collect();
+    let report = Report::new(&runs, &best_known, args.alpha).unwrap_or_else(|err| {
+        eprintln!("Cannot compare the runs: {}", err);
+        exit(1);
+    });
+    let text = report.render(format);
+    match &args.output {
+        Some(output) => write(output, text).unwrap_or_else(|err| {
+            eprintln!("Error writing {}: {}", output, err);
+            exit(1);
+        }),
+        None => print!("{}", text),
+    }
 }
 
 fn main() {
     let args = Args::parse();
+    match args.command {
+        Some(Command::Experiment(experiment_args)) => {
+            run_experiment(*experiment_args);
+            return;
+        }
+        Some(Command::Migrate(migrate_args)) => {
+            run_migrate(migrate_args);
+            return;
+        }
+        Some(Command::Results(results_args)) => {
+            run_results_query(results_args);
+            return;
+        }
+        Some(Command::Report(report_args)) => {
+            run_report(report_args);
+            return;
+        }
+        None => {}
+    }
+    let start_time = Instant::now();
     let algorithm = args.algorithm.as_str();
-    let uf = UF::get_uf_from_str(args.uf.as_str()).unwrap();
+    let uf = UF::get_uf_from_str(args.uf.as_str()).unwrap_or_else(|| {
+        eprintln!("Unknown uf '{}'", args.uf);
+        exit(1);
+    });
     let plot = args.plot;
+    let metric = DistanceMetric::get_metric_from_str(args.metric.as_str()).unwrap_or_else(|| {
+        eprintln!("Unknown metric '{}'", args.metric);
+        exit(1);
+    });
 
-    let cities = read_csv_cities("src/assets/cities.csv", &uf);
+    let tsplib = args.tsplib.as_ref().map(|path| {
+        TsplibInstance::read_from_file(path).unwrap_or_else(|err| {
+            eprintln!("Error reading TSPLIB instance {}: {}", path, err);
+            exit(1);
+        })
+    });
 
-    let cities_result = AlgorithmStrategy::execute_algorithm(algorithm, &cities);
+    let cities = match &tsplib {
+        Some(instance) => instance.get_cities().clone(),
+        None => {
+            let delimiter = get_delimiter_from_str(&args.delimiter).unwrap_or_else(|| {
+                eprintln!("Invalid delimiter '{}'", args.delimiter);
+                exit(1);
+            });
+            let optional_column = |column: &String| Some(column.clone()).filter(|c| !c.is_empty());
+            let importer = CityImporter::new(
+                delimiter,
+                CityColumns {
+                    id: args.id_column.clone(),
+                    name: optional_column(&args.name_column),
+                    latitude: args.latitude_column.clone(),
+                    longitude: args.longitude_column.clone(),
+                    region: optional_column(&args.region_column),
+                    capital: optional_column(&args.capital_column),
+                    timezone: optional_column(&args.timezone_column),
+                },
+            );
+            importer
+                .read_from_file(&args.cities, &uf, args.region.as_deref())
+                .unwrap_or_else(|err| {
+                    eprintln!("Error reading cities: {}", err);
+                    exit(1);
+                })
+        }
+    };
 
-    if plot {
-        let folder = format!(
-            "src/assets/results/{}/{}",
-            args.uf.as_str().to_uppercase(),
-            algorithm
+    let road_matrix = args.matrix.as_ref().map(|path| {
+        RoadMatrix::read_from_file(path, &cities, &args.matrix_unit).unwrap_or_else(|err| {
+            eprintln!("Error reading distance matrix: {}", err);
+            exit(1);
+        })
+    });
+    let loading_time = start_time.elapsed();
+    let (distance_matrix, symmetric, matrix_description) = match (&tsplib, &road_matrix) {
+        (Some(instance), _) => (
+            instance.get_distance_matrix().clone(),
+            instance.is_symmetric(),
+            Some(instance.get_description()),
+        ),
+        (None, Some(road_matrix)) => (
+            road_matrix.get_values().clone(),
+            road_matrix.is_symmetric(),
+            Some(road_matrix.get_description()),
+        ),
+        (None, None) => (metric.create_distance_matrix(&cities), true, None),
+    };
+    let distance_matrix_time = start_time.elapsed() - loading_time;
+
+    let initial_tour = args.initial_tour.as_ref().map(|path| {
+        InitialTour::read_from_file(path, &cities, tsplib.is_some()).unwrap_or_else(|err| {
+            eprintln!("Error reading initial tour: {}", err);
+            exit(1);
+        })
+    });
+    if let Some(tour) = &initial_tour {
+        println!(
+            "Initial tour: {} ({})",
+            tour.get_distance(&distance_matrix),
+            tour.get_source()
         );
+    }
 
-        let mut best_distance_in_file = 0.0;
-        let mut best_time_in_file = 0.0;
-        let metadata_path = format!("{}/metadata.txt", folder);
-        let folder_exists = Path::new(&metadata_path).exists();
-        if folder_exists {
-            best_distance_in_file = GraphMetadata::get_distance_from_file(&metadata_path);
-            best_time_in_file = GraphMetadata::get_time_from_file(&metadata_path);
+    let mut parameters = match &args.config {
+        Some(path) => AlgorithmParameters::read_from_file(path).unwrap_or_else(|err| {
+            eprintln!("Error reading config: {}", err);
+            exit(1);
+        }),
+        None => AlgorithmParameters::default(),
+    };
+    for assignment in &args.params {
+        parameters.apply_override(assignment).unwrap_or_else(|err| {
+            eprintln!("Error in --param: {}", err);
+            exit(1);
+        });
+    }
+    if let Err(err) = parameters.validate(algorithm, cities.len()) {
+        eprintln!("Invalid parameters: {}", err);
+        exit(1);
+    }
+
+    let seed = args.seed.unwrap_or_else(rand::random);
+
+    let max_time = match args.max_time {
+        Some(seconds) if !(seconds.is_finite() && seconds > 0.0) => {
+            eprintln!("--max-time must be a positive number of seconds");
+            exit(1);
         }
+        max_time => max_time.map(Duration::from_secs_f64),
+    };
+    let stopping_criteria = StoppingCriteria {
+        max_time,
+        max_evaluations: args.max_evaluations,
+        target_distance: args.target_distance,
+        max_stagnation: args.max_stagnation,
+    };
 
-        if !folder_exists
-            || (best_distance_in_file > *cities_result.get_distance())
-            || (best_distance_in_file == *cities_result.get_distance())
-                && best_time_in_file > cities_result.get_total_time().as_secs_f64()
-        {
-            let mut final_path = cities_result.get_final_path().clone();
-            final_path.push(final_path[0]);
-
-            plot_state(
-                &cities,
-                &final_path,
-                format!("{}/final.png", folder).as_str(),
-                &uf,
+    if !(args.checkpoint_interval.is_finite() && args.checkpoint_interval >= 0.0) {
+        eprintln!("--checkpoint-interval must be a non-negative number of seconds");
+        exit(1);
+    }
+    // the islands evolve on their own threads, their state is never saved
+    if args.checkpoint.is_some()
+        && algorithm.eq_ignore_ascii_case("G")
+        && parameters.genetic.islands > 1
+    {
+        eprintln!("--checkpoint is not supported with G.islands above 1");
+        exit(1);
+    }
+    let bound_kind = match args.lower_bound.as_str() {
+        "none" => None,
+        kind => match BoundKind::get_kind_from_str(kind) {
+            Some(kind) => Some(kind),
+            None => {
+                eprintln!(
+                    "Unknown lower bound '{}' (mst, one_tree, held_karp, lp or none)",
+                    kind
+                );
+                exit(1);
+            }
+        },
+    };
+
+    // Cities from another file or region are kept apart from the kelvins results
+    let custom_cities = args.cities != DEFAULT_CITIES_PATH || args.region.is_some();
+    let instance_name = match &tsplib {
+        Some(instance) => instance.get_name().clone(),
+        None if custom_cities => format!(
+            "{}/{}",
+            Path::new(&args.cities)
+                .file_stem()
+                .unwrap()
+                .to_string_lossy(),
+            args.region.as_ref().unwrap_or(&args.uf).to_uppercase()
+        ),
+        None => args.uf.to_uppercase(),
+    };
+    let checkpoint_instance = CheckpointInstance::new(
+        &instance_name,
+        matrix_description
+            .as_deref()
+            .unwrap_or_else(|| metric.get_name()),
+        &distance_matrix,
+    );
+    if let Some(path) = &args.resume
+        && let Err(err) = checkpoint_instance.check(path)
+    {
+        eprintln!("Error resuming: {}", err);
+        exit(1);
+    }
+    let checkpoint = args.checkpoint.clone().map(|path| CheckpointSettings {
+        path,
+        interval: Duration::from_secs_f64(args.checkpoint_interval),
+        instance: checkpoint_instance,
+    });
+
+    // The first SIGINT/SIGTERM lets the algorithm return its best tour, a second one quits
+    install_interrupt_handler("Interrupted, finishing with the best tour so far (again to quit)");
+
+    let cities_result = AlgorithmStrategy::execute_algorithm(
+        algorithm,
+        &cities,
+        &distance_matrix,
+        symmetric,
+        &metric,
+        &parameters,
+        RunOptions {
+            seed,
+            stopping_criteria,
+            observer: args
+                .progress
+                .then(|| Box::new(ProgressPrinter) as Box<dyn Observer>),
+            checkpoint,
+            resume: args.resume.clone(),
+            initial_tour: initial_tour.as_ref().map(|tour| tour.get_path().clone()),
+        },
+    )
+    .unwrap_or_else(|err| {
+        eprintln!("Error running {}: {}", algorithm, err);
+        exit(1);
+    });
+
+    // Branch and Bound returns the tour closed on the first city, the others do not
+    let mut tour = cities_result.get_final_path().clone();
+    if tour.len() == cities.len() + 1 && tour.first() == tour.last() {
+        tour.pop();
+    }
+    let mut final_path = tour.clone();
+    final_path.push(final_path[0]);
+
+    let lower_bound_start = Instant::now();
+    let lower_bound = bound_kind.and_then(|kind| {
+        let compute = |kind| {
+            compute_lower_bounds(
+                &distance_matrix,
+                cities.len(),
+                kind,
+                *cities_result.get_distance(),
+                args.bound_iterations,
             )
-            .unwrap();
+        };
+        // only the LP bound fails, the others are still reported
+        let bounds = compute(kind).unwrap_or_else(|err| {
+            eprintln!("Error computing the lower bound: {}", err);
+            compute(BoundKind::HeldKarp).unwrap_or_default()
+        });
+        get_best_bound(&bounds).cloned()
+    });
+    let lower_bound_time = lower_bound_start.elapsed();
+    let bound_info = match &lower_bound {
+        Some(bound) => format!(
+            "Lower Bound: {} ({})\nGap: {:.4}%\n",
+            bound.get_value(),
+            bound.get_kind().get_name(),
+            bound.get_gap(*cities_result.get_distance())
+        ),
+        None => String::new(),
+    };
+    let initial_tour_info = match &initial_tour {
+        Some(tour) => format!(
+            "Initial Tour: {} ({})\n",
+            tour.get_source(),
+            tour.get_distance(&distance_matrix)
+        ),
+        None => String::new(),
+    };
+
+    let metadata = GraphMetadata::new(
+        final_path.clone(),
+        &cities,
+        *cities_result.get_distance(),
+        *cities_result.get_total_time(),
+        metric.clone(),
+        matrix_description.clone(),
+        seed,
+        format!(
+            "Stop Reason: {}\nEvaluations: {}\n{}{}{}",
+            cities_result.get_stop_reason().get_name(),
+            cities_result.get_evaluations(),
+            bound_info,
+            initial_tour_info,
+            cities_result.get_metadata_info()
+        ),
+    );
+
+    let mut record = RunRecord::new(
+        algorithm,
+        &instance_name,
+        *cities_result.get_distance(),
+        tour.iter()
+            .map(|&id| cities[id as usize].get_code())
+            .collect(),
+    );
+    let parameter_set = match &args.config {
+        Some(path) => Path::new(path)
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string(),
+        None => "default".to_string(),
+    };
+    record.set_parameters(&parameter_set, parameters.get_section(algorithm));
+    record.set_seed(seed);
+    record.set_distance_source(&metric, matrix_description.clone());
+    // TSPLIB coordinates are not latitudes and longitudes
+    if tsplib.is_none() {
+        record.measure_distances(&cities, &tour);
+    }
+    record.set_termination(
+        cities_result.get_iterations(),
+        cities_result.get_evaluations(),
+        cities_result.get_stop_reason().get_name(),
+        cities_result.is_optimal(),
+    );
+    if let Some(bound) = &lower_bound {
+        record.set_lower_bound(bound.get_kind().get_name(), bound.get_value());
+    }
+    record.set_info(cities_result.get_metadata_info());
+    record.set_timings(PhaseTimings {
+        loading: loading_time.as_secs_f64(),
+        distance_matrix: distance_matrix_time.as_secs_f64(),
+        algorithm: cities_result.get_total_time().as_secs_f64(),
+        lower_bound: lower_bound_time.as_secs_f64(),
+        total: start_time.elapsed().as_secs_f64(),
+    });
+
+    // A file that cannot be written is reported, the run and the other outputs are kept
+    if let Some(result_path) = &args.result
+        && let Err(err) = record.write_json(result_path)
+    {
+        eprintln!("Error writing {}: {}", result_path, err);
+    }
+
+    if let Some(result_csv_path) = &args.result_csv
+        && let Err(err) = record.append_csv(result_csv_path)
+    {
+        eprintln!("Error writing {}: {}", result_csv_path, err);
+    }
+
+    let operator_stats = cities_result.get_operator_stats();
+    let island_stats = cities_result.get_island_stats();
+    if !args.no_archive {
+        let archived = ResultArchive::new(&args.archive)
+            .store(&record)
+            .and_then(|folder| {
+                write_convergence_csv(
+                    &folder.join("convergence.csv").to_string_lossy(),
+                    cities_result.get_trace(),
+                )?;
+                if !operator_stats.is_empty() {
+                    write_operator_stats_csv(
+                        &folder.join("operators.csv").to_string_lossy(),
+                        operator_stats,
+                    )?;
+                }
+                if !island_stats.is_empty() {
+                    write_island_stats_csv(
+                        &folder.join("islands.csv").to_string_lossy(),
+                        island_stats,
+                    )?;
+                }
+                Ok(folder)
+            });
+        match archived {
+            Ok(folder) => eprintln!("Archived in {}", folder.display()),
+            Err(err) => eprintln!("Error archiving the run: {}", err),
+        }
+    }
+
+    if let Some(tour_path) = &args.tour {
+        let nodes: Vec<u32> = tour
+            .iter()
+            .map(|&id| match &tsplib {
+                Some(_) => cities[id as usize].get_code(),
+                None => id as u32 + 1,
+            })
+            .collect();
+        let name = match &tsplib {
+            Some(instance) => format!("{}.{}.tour", instance.get_name(), algorithm),
+            None => format!("{}.{}.tour", args.uf.to_uppercase(), algorithm),
+        };
+        let comment = format!("Length {} ({})", cities_result.get_distance(), algorithm);
+        if let Err(err) = TsplibInstance::write_tour(tour_path, &name, &comment, &nodes) {
+            eprintln!("Error writing {}: {}", tour_path, err);
+        }
+    }
+
+    if let Some(tour_csv_path) = &args.tour_csv
+        && let Err(err) = metadata.generate_tour_csv(tour_csv_path)
+    {
+        eprintln!("Error writing {}: {}", tour_csv_path, err);
+    }
+
+    if let Some(operator_stats_path) = &args.operator_stats
+        && let Err(err) = write_operator_stats_csv(operator_stats_path, operator_stats)
+    {
+        eprintln!("Error writing {}: {}", operator_stats_path, err);
+    }
 
-            let mut initial_path = cities_result.get_initial_path().clone();
-            if initial_path.len() != 0 {
-                initial_path.push(initial_path[0]);
+    if let Some(convergence_path) = &args.convergence
+        && let Err(err) = write_convergence_csv(convergence_path, cities_result.get_trace())
+    {
+        eprintln!("Error writing {}: {}", convergence_path, err);
+    }
+
+    if plot {
+        let mut folder = match &tsplib {
+            Some(instance) => format!(
+                "src/assets/results/TSPLIB/{}/{}",
+                instance.get_name(),
+                algorithm
+            ),
+            None if custom_cities => format!(
+                "src/assets/results/{}/{}/{}",
+                Path::new(&args.cities)
+                    .file_stem()
+                    .unwrap()
+                    .to_string_lossy(),
+                args.region.as_ref().unwrap_or(&args.uf).to_uppercase(),
+                algorithm
+            ),
+            None => format!(
+                "src/assets/results/{}/{}",
+                args.uf.as_str().to_uppercase(),
+                algorithm
+            ),
+        };
+        let bounds = if tsplib.is_some() || custom_cities {
+            get_cities_plot_bounds(&cities)
+        } else {
+            get_uf_plot_bounds(&uf)
+        };
+        if let Some(path) = &args.matrix {
+            let matrix_name = Path::new(path).file_stem().unwrap().to_string_lossy();
+            folder = format!("{}_{}", folder, matrix_name);
+        } else if tsplib.is_none() && metric != DistanceMetric::Euclidean {
+            folder = format!("{}_{}", folder, metric.get_name());
+        }
+
+        // The folder keeps the best run: shortest, then fastest
+        let result_path = format!("{}/result.json", folder);
+        let metadata_path = format!("{}/metadata.txt", folder);
+        let best_in_folder = if Path::new(&result_path).exists() {
+            Some(RunRecord::read_json(&result_path))
+        } else if Path::new(&metadata_path).exists() {
+            Some(RunRecord::read_metadata_file(&metadata_path, Some(&cities)))
+        } else {
+            None
+        }
+        .and_then(|best| {
+            best.map_err(|err| eprintln!("Replacing unreadable result: {}", err))
+                .ok()
+        });
+        let is_better = match &best_in_folder {
+            Some(best) => {
+                best.get_distance() > record.get_distance()
+                    || best.get_distance() == record.get_distance()
+                        && best.get_timings().algorithm > record.get_timings().algorithm
+            }
+            None => true,
+        };
 
+        if is_better {
+            let saved = (|| -> Result<(), Box<dyn Error>> {
                 plot_state(
                     &cities,
-                    &initial_path,
-                    format!("{}/inicial.png", folder).as_str(),
-                    &uf,
-                )
-                .unwrap();
-            }
+                    &final_path,
+                    format!("{}/final.png", folder).as_str(),
+                    &bounds,
+                )?;
 
-            let metadata = GraphMetadata::new(
-                final_path,
-                cities_result.get_distance().clone(),
-                cities_result.get_total_time().clone(),
-                cities_result.get_metadata_info().clone(),
-            );
+                let mut initial_path = cities_result.get_initial_path().clone();
+                if !initial_path.is_empty() {
+                    initial_path.push(initial_path[0]);
 
-            metadata.generate_file(format!("{}/metadata.txt", folder));
+                    plot_state(
+                        &cities,
+                        &initial_path,
+                        format!("{}/inicial.png", folder).as_str(),
+                        &bounds,
+                    )?;
+                }
+
+                metadata.generate_file(&metadata_path)?;
+                record.write_json(&result_path)?;
+                metadata.generate_tour_csv(&format!("{}/tour.csv", folder))?;
+                write_convergence_csv(
+                    &format!("{}/convergence.csv", folder),
+                    cities_result.get_trace(),
+                )?;
+                if !operator_stats.is_empty() {
+                    write_operator_stats_csv(&format!("{}/operators.csv", folder), operator_stats)?;
+                }
+                if !island_stats.is_empty() {
+                    write_island_stats_csv(&format!("{}/islands.csv", folder), island_stats)?;
+                }
+                Ok(())
+            })();
+            if let Err(err) = saved {
+                eprintln!("Error saving the run in {}: {}", folder, err);
+            }
         }
     }
 
     println!("{}", cities_result.get_distance());
     println!("{:#?}", cities_result.get_total_time().as_secs_f64());
+    if cities_result.is_optimal() {
+        println!("Optimal: proved");
+    }
+    if let Some(bound) = &lower_bound {
+        println!(
+            "Lower bound: {} ({}), gap: {:.4}%",
+            bound.get_value(),
+            bound.get_kind().get_name(),
+            bound.get_gap(*cities_result.get_distance())
+        );
+    }
 }
@@ -4,15 +4,37 @@ use super::uf::UF;
 #[derive(Debug, Clone)]
 pub struct City {
     id: u16,
-    uf: UF,
+    // codigo_ibge for the municipalities, node number for TSPLIB instances
+    code: u32,
+    name: String,
+    // raw value of the region column, uf is filled when it is a known UF code or abbreviation
+    region: Option<String>,
+    uf: Option<UF>,
+    capital: bool,
+    timezone: Option<String>,
     coordinates: Coordinates,
 }
 
 impl City {
-    pub fn new(id: u16, uf: UF, coordinates: Coordinates) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        id: u16,
+        code: u32,
+        name: String,
+        region: Option<String>,
+        uf: Option<UF>,
+        capital: bool,
+        timezone: Option<String>,
+        coordinates: Coordinates,
+    ) -> Self {
         City {
             id,
+            code,
+            name,
+            region,
             uf,
+            capital,
+            timezone,
             coordinates,
         }
     }
@@ -21,11 +43,39 @@ impl City {
         self.id
     }
 
+    pub fn get_code(&self) -> u32 {
+        self.code
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_region(&self) -> Option<&String> {
+        self.region.as_ref()
+    }
+
+    // UF abbreviation when known, raw region value otherwise
+    pub fn get_region_name(&self) -> Option<String> {
+        match &self.uf {
+            Some(uf) => Some(uf.get_name()),
+            None => self.region.clone(),
+        }
+    }
+
+    pub fn is_capital(&self) -> bool {
+        self.capital
+    }
+
+    pub fn get_timezone(&self) -> Option<&String> {
+        self.timezone.as_ref()
+    }
+
     pub fn get_coordinates(&self) -> &Coordinates {
         &self.coordinates
     }
 
-    pub fn get_uf(&self) -> &UF {
-        &self.uf
+    pub fn get_uf(&self) -> Option<&UF> {
+        self.uf.as_ref()
     }
 }
@@ -0,0 +1,292 @@
+use std::{collections::HashSet, error::Error, fs::File};
+
+use csv::{ReaderBuilder, StringRecord};
+
+use super::{
+    city::City,
+    coordinates::Coordinates,
+    uf::{UF, UFEnum},
+};
+
+pub const DEFAULT_CITIES_PATH: &str = "src/assets/cities.csv";
+const MAX_REPORTED_ERRORS: usize = 20;
+
+// Column of each field, by header name (case insensitive) or 0-based index.
+// Optional fields are left out of the cities when their column is None.
+#[derive(Debug, Clone)]
+pub struct CityColumns {
+    pub id: String,
+    pub name: Option<String>,
+    pub latitude: String,
+    pub longitude: String,
+    pub region: Option<String>,
+    pub capital: Option<String>,
+    pub timezone: Option<String>,
+}
+
+// Columns of the kelvins export (src/assets/cities.csv)
+impl Default for CityColumns {
+    fn default() -> Self {
+        CityColumns {
+            id: "codigo_ibge".to_string(),
+            name: Some("nome".to_string()),
+            latitude: "latitude".to_string(),
+            longitude: "longitude".to_string(),
+            region: Some("codigo_uf".to_string()),
+            capital: Some("capital".to_string()),
+            timezone: Some("fuso_horario".to_string()),
+        }
+    }
+}
+
+// Reads cities from any delimited file, so datasets other than the kelvins export can be used
+// without changes.
+#[derive(Debug, Clone)]
+pub struct CityImporter {
+    delimiter: u8,
+    columns: CityColumns,
+}
+
+struct CityRow {
+    code: u32,
+    name: String,
+    coordinates: Coordinates,
+    region: Option<String>,
+    uf: Option<UF>,
+    capital: bool,
+    timezone: Option<String>,
+}
+
+struct ColumnIndexes {
+    id: usize,
+    name: Option<usize>,
+    latitude: usize,
+    longitude: usize,
+    region: Option<usize>,
+    capital: Option<usize>,
+    timezone: Option<usize>,
+}
+
+impl CityImporter {
+    pub fn new(delimiter: u8, columns: CityColumns) -> Self {
+        CityImporter { delimiter, columns }
+    }
+
+    // Keeps the cities of the given uf (every city for BRAZIL) and, when set, of the given
+    // region value. Every invalid row is reported with its line and field instead of stopping
+    // at the first one.
+    pub fn read_from_file(
+        &self,
+        path: &str,
+        uf: &UF,
+        region: Option<&str>,
+    ) -> Result<Vec<City>, Box<dyn Error>> {
+        let file = File::open(path).map_err(|err| format!("failed to open {}: {}", path, err))?;
+        let mut reader = ReaderBuilder::new()
+            .delimiter(self.delimiter)
+            .flexible(true)
+            .from_reader(file);
+
+        let headers = reader.headers()?.clone();
+        let find_optional = |column: &Option<String>| {
+            column
+                .as_ref()
+                .map(|column| Self::find_column(&headers, column, path))
+                .transpose()
+        };
+        let columns = ColumnIndexes {
+            id: Self::find_column(&headers, &self.columns.id, path)?,
+            name: find_optional(&self.columns.name)?,
+            latitude: Self::find_column(&headers, &self.columns.latitude, path)?,
+            longitude: Self::find_column(&headers, &self.columns.longitude, path)?,
+            region: find_optional(&self.columns.region)?,
+            capital: find_optional(&self.columns.capital)?,
+            timezone: find_optional(&self.columns.timezone)?,
+        };
+
+        let mut cities: Vec<City> = vec![];
+        let mut errors: Vec<String> = vec![];
+        let mut seen_codes: HashSet<u32> = HashSet::new();
+
+        for record in reader.records() {
+            let record = match record {
+                Ok(record) => record,
+                Err(err) => {
+                    errors.push(err.to_string());
+                    continue;
+                }
+            };
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+
+            let row = match self.read_record(&record, &columns) {
+                Ok(row) => row,
+                Err(err) => {
+                    errors.push(format!("line {}: {}", line, err));
+                    continue;
+                }
+            };
+
+            if !seen_codes.insert(row.code) {
+                errors.push(format!(
+                    "line {}: field '{}': id {} appears more than once",
+                    line, self.columns.id, row.code
+                ));
+                continue;
+            }
+
+            let in_uf = *uf.get_uf_enum() == UFEnum::BRAZIL
+                || row
+                    .uf
+                    .as_ref()
+                    .is_some_and(|city_uf| city_uf.get_uf_enum() == uf.get_uf_enum());
+            let in_region = region.is_none_or(|region| {
+                row.region
+                    .as_ref()
+                    .is_some_and(|city_region| city_region.eq_ignore_ascii_case(region))
+            });
+            if !in_uf || !in_region {
+                continue;
+            }
+
+            if cities.len() > u16::MAX as usize {
+                return Err(format!(
+                    "{} selects more than {} cities",
+                    path,
+                    u16::MAX as usize + 1
+                )
+                .into());
+            }
+            cities.push(City::new(
+                cities.len() as u16,
+                row.code,
+                row.name,
+                row.region,
+                row.uf,
+                row.capital,
+                row.timezone,
+                row.coordinates,
+            ));
+        }
+
+        if !errors.is_empty() {
+            let mut message = format!("{} invalid rows in {}:", errors.len(), path);
+            for error in errors.iter().take(MAX_REPORTED_ERRORS) {
+                message.push_str(&format!("\n  {}", error));
+            }
+            if errors.len() > MAX_REPORTED_ERRORS {
+                message.push_str(&format!(
+                    "\n  ... and {} more",
+                    errors.len() - MAX_REPORTED_ERRORS
+                ));
+            }
+            return Err(message.into());
+        }
+
+        if cities.is_empty() {
+            return Err(format!("no cities selected from {}", path).into());
+        }
+
+        Ok(cities)
+    }
+
+    fn read_record(
+        &self,
+        record: &StringRecord,
+        columns: &ColumnIndexes,
+    ) -> Result<CityRow, String> {
+        let field = |index: usize, column: &str| -> Result<&str, String> {
+            record
+                .get(index)
+                .map(str::trim)
+                .ok_or_else(|| format!("field '{}': missing", column))
+        };
+        let optional_field = |index: Option<usize>, column: &Option<String>| match (index, column) {
+            (Some(index), Some(column)) => {
+                field(index, column).map(|value| Some(value).filter(|value| !value.is_empty()))
+            }
+            _ => Ok(None),
+        };
+
+        let id = field(columns.id, &self.columns.id)?;
+        let code = id
+            .parse::<u32>()
+            .map_err(|_| format!("field '{}': invalid id '{}'", self.columns.id, id))?;
+
+        let name = optional_field(columns.name, &self.columns.name)?
+            .map(str::to_string)
+            .unwrap_or_else(|| code.to_string());
+
+        let latitude = Self::parse_coordinate(
+            field(columns.latitude, &self.columns.latitude)?,
+            &self.columns.latitude,
+            90.0,
+        )?;
+        let longitude = Self::parse_coordinate(
+            field(columns.longitude, &self.columns.longitude)?,
+            &self.columns.longitude,
+            180.0,
+        )?;
+
+        let region = optional_field(columns.region, &self.columns.region)?;
+        let uf = region.and_then(|value| match value.parse::<u8>() {
+            Ok(code) => UF::get_uf_from_code(code),
+            Err(_) => UF::get_uf_from_str(value),
+        });
+
+        let capital = match optional_field(columns.capital, &self.columns.capital)? {
+            None => false,
+            Some(value) => match value.to_lowercase().as_str() {
+                "1" | "true" | "yes" | "sim" => true,
+                "0" | "false" | "no" | "nao" | "não" => false,
+                _ => {
+                    return Err(format!(
+                        "field '{}': invalid capital flag '{}'",
+                        self.columns.capital.as_deref().unwrap_or_default(),
+                        value
+                    ));
+                }
+            },
+        };
+
+        let timezone = optional_field(columns.timezone, &self.columns.timezone)?;
+
+        Ok(CityRow {
+            code,
+            name,
+            coordinates: Coordinates::new(latitude, longitude),
+            region: region.map(str::to_string),
+            uf,
+            capital,
+            timezone: timezone.map(str::to_string),
+        })
+    }
+
+    fn parse_coordinate(value: &str, column: &str, limit: f32) -> Result<f32, String> {
+        match value.parse::<f32>() {
+            Ok(coordinate) if coordinate.is_finite() && coordinate.abs() <= limit => Ok(coordinate),
+            Ok(_) => Err(format!(
+                "field '{}': {} is outside [-{}, {}]",
+                column, value, limit, limit
+            )),
+            Err(_) => Err(format!("field '{}': invalid number '{}'", column, value)),
+        }
+    }
+
+    fn find_column(headers: &StringRecord, column: &str, path: &str) -> Result<usize, String> {
+        if let Some(index) = headers
+            .iter()
+            .position(|header| header.trim().eq_ignore_ascii_case(column.trim()))
+        {
+            return Ok(index);
+        }
+        match column.trim().parse::<usize>() {
+            Ok(index) if index < headers.len() => Ok(index),
+            _ => Err(format!(
+                "column '{}' not found in {} (columns: {})",
+                column,
+                path,
+                headers.iter().collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+}
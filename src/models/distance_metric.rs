@@ -0,0 +1,218 @@
+use super::{city::City, coordinates::Coordinates};
+
+// Mean Earth radius (IUGG), used by the spherical formulas
+const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+// WGS-84 ellipsoid, used by Vincenty
+const WGS84_A_KM: f64 = 6378.137;
+const WGS84_F: f64 = 1.0 / 298.257223563;
+const WGS84_B_KM: f64 = WGS84_A_KM * (1.0 - WGS84_F);
+
+const VINCENTY_MAX_ITERATIONS: usize = 200;
+const VINCENTY_TOLERANCE: f64 = 1e-12;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DistanceMetric {
+    Euclidean,
+    Haversine,
+    Vincenty,
+}
+
+impl DistanceMetric {
+    pub fn get_metric_from_str(str: &str) -> Option<Self> {
+        match str.to_uppercase().as_str() {
+            "EUCLIDEAN" => Some(Self::Euclidean),
+            "HAVERSINE" => Some(Self::Haversine),
+            "VINCENTY" => Some(Self::Vincenty),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Euclidean => "EUCLIDEAN",
+            Self::Haversine => "HAVERSINE",
+            Self::Vincenty => "VINCENTY",
+        }
+    }
+
+    pub fn get_unit(&self) -> &str {
+        match self {
+            Self::Euclidean => "degrees",
+            Self::Haversine | Self::Vincenty => "km",
+        }
+    }
+
+    pub fn calculate_distance(&self, c1: &Coordinates, c2: &Coordinates) -> f64 {
+        match self {
+            Self::Euclidean => Self::euclidean(c1, c2),
+            Self::Haversine => Self::haversine(c1, c2),
+            Self::Vincenty => Self::vincenty(c1, c2).unwrap_or_else(|| Self::haversine(c1, c2)),
+        }
+    }
+
+    pub fn create_distance_matrix(&self, cities: &[City]) -> Vec<f64> {
+        let n = cities.len();
+
+        let mut distance_matrix: Vec<f64> = vec![0.0; n * n];
+
+        for i in 0..n {
+            for j in 0..n {
+                if i < j {
+                    let distance = self.calculate_distance(
+                        cities[i].get_coordinates(),
+                        cities[j].get_coordinates(),
+                    );
+                    distance_matrix[i * n + j] = distance;
+                    distance_matrix[j * n + i] = distance;
+                }
+            }
+        }
+
+        distance_matrix
+    }
+
+    // Point stored in the KD-tree. For the geodesic metrics the cities are placed on the unit
+    // sphere, where the straight-line (chord) distance grows monotonically with the great-circle
+    // distance, so the nearest neighbours returned by the tree follow the metric.
+    // The Euclidean cities are points of a plane in the same 3D tree. Padding them with a
+    // constant third coordinate gives an axis where every city has the same value, and kiddo
+    // fails to split a bucket once more cities than it holds share the value of its split axis.
+    // The plane is mapped onto two orthonormal vectors instead, so no axis is constant and the
+    // distances between cities, and so their nearest neighbours, are unchanged.
+    pub fn get_kd_tree_point(&self, coordinates: &Coordinates) -> [f32; 3] {
+        match self {
+            Self::Euclidean => {
+                // latitude * (2, 2, 1) / 3 + longitude * (2, -1, -2) / 3, both unit and
+                // perpendicular
+                let latitude = coordinates.get_latitude() as f64;
+                let longitude = coordinates.get_longitude() as f64;
+                [
+                    ((2.0 * latitude + 2.0 * longitude) / 3.0) as f32,
+                    ((2.0 * latitude - longitude) / 3.0) as f32,
+                    ((latitude - 2.0 * longitude) / 3.0) as f32,
+                ]
+            }
+            Self::Haversine | Self::Vincenty => {
+                let latitude = (coordinates.get_latitude() as f64).to_radians();
+                let longitude = (coordinates.get_longitude() as f64).to_radians();
+                [
+                    (latitude.cos() * longitude.cos()) as f32,
+                    (latitude.cos() * longitude.sin()) as f32,
+                    latitude.sin() as f32,
+                ]
+            }
+        }
+    }
+
+    fn euclidean(c1: &Coordinates, c2: &Coordinates) -> f64 {
+        let x_part: f64 = (c2.get_longitude() - c1.get_longitude()).powi(2).into();
+        let y_part: f64 = (c2.get_latitude() - c1.get_latitude()).powi(2).into();
+
+        (x_part + y_part).sqrt()
+    }
+
+    fn haversine(c1: &Coordinates, c2: &Coordinates) -> f64 {
+        let latitude_1 = (c1.get_latitude() as f64).to_radians();
+        let latitude_2 = (c2.get_latitude() as f64).to_radians();
+        let delta_latitude = latitude_2 - latitude_1;
+        let delta_longitude = (c2.get_longitude() as f64 - c1.get_longitude() as f64).to_radians();
+
+        let a = (delta_latitude / 2.0).sin().powi(2)
+            + latitude_1.cos() * latitude_2.cos() * (delta_longitude / 2.0).sin().powi(2);
+
+        2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
+
+    // Vincenty inverse formula on the WGS-84 ellipsoid.
+    // Returns None when the iteration does not converge (nearly antipodal points).
+    fn vincenty(c1: &Coordinates, c2: &Coordinates) -> Option<f64> {
+        let l = (c2.get_longitude() as f64 - c1.get_longitude() as f64).to_radians();
+        let u1 = ((1.0 - WGS84_F) * (c1.get_latitude() as f64).to_radians().tan()).atan();
+        let u2 = ((1.0 - WGS84_F) * (c2.get_latitude() as f64).to_radians().tan()).atan();
+        let (sin_u1, cos_u1) = u1.sin_cos();
+        let (sin_u2, cos_u2) = u2.sin_cos();
+
+        let mut lambda = l;
+        for _ in 0..VINCENTY_MAX_ITERATIONS {
+            let (sin_lambda, cos_lambda) = lambda.sin_cos();
+            let sin_sigma = ((cos_u2 * sin_lambda).powi(2)
+                + (cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda).powi(2))
+            .sqrt();
+            if sin_sigma == 0.0 {
+                return Some(0.0);
+            }
+            let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+            let sigma = sin_sigma.atan2(cos_sigma);
+            let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+            let cos_sq_alpha = 1.0 - sin_alpha.powi(2);
+            let cos_2_sigma_m = if cos_sq_alpha == 0.0 {
+                0.0
+            } else {
+                cos_sigma - 2.0 * sin_u1 * sin_u2 / cos_sq_alpha
+            };
+            let c = WGS84_F / 16.0 * cos_sq_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos_sq_alpha));
+
+            let previous_lambda = lambda;
+            lambda = l
+                + (1.0 - c)
+                    * WGS84_F
+                    * sin_alpha
+                    * (sigma
+                        + c * sin_sigma
+                            * (cos_2_sigma_m
+                                + c * cos_sigma * (-1.0 + 2.0 * cos_2_sigma_m.powi(2))));
+
+            if (lambda - previous_lambda).abs() < VINCENTY_TOLERANCE {
+                let u_sq =
+                    cos_sq_alpha * (WGS84_A_KM.powi(2) - WGS84_B_KM.powi(2)) / WGS84_B_KM.powi(2);
+                let a = 1.0
+                    + u_sq / 16384.0 * (4096.0 + u_sq * (-768.0 + u_sq * (320.0 - 175.0 * u_sq)));
+                let b = u_sq / 1024.0 * (256.0 + u_sq * (-128.0 + u_sq * (74.0 - 47.0 * u_sq)));
+                let delta_sigma = b
+                    * sin_sigma
+                    * (cos_2_sigma_m
+                        + b / 4.0
+                            * (cos_sigma * (-1.0 + 2.0 * cos_2_sigma_m.powi(2))
+                                - b / 6.0
+                                    * cos_2_sigma_m
+                                    * (-3.0 + 4.0 * sin_sigma.powi(2))
+                                    * (-3.0 + 4.0 * cos_2_sigma_m.powi(2))));
+
+                return Some(WGS84_B_KM * a * (sigma - delta_sigma));
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn euclidean_tree_points_keep_the_distances() {
+        let metric = DistanceMetric::Euclidean;
+        // cities on one latitude, the case a constant axis breaks
+        let cities: Vec<Coordinates> = (0..50)
+            .map(|i| Coordinates::new(-10.0, -50.0 + i as f32 * 0.37))
+            .chain([Coordinates::new(3.5, -61.25)])
+            .collect();
+        for a in &cities {
+            for b in &cities {
+                let (p, q) = (metric.get_kd_tree_point(a), metric.get_kd_tree_point(b));
+                let squared: f64 = (0..3).map(|k| ((p[k] - q[k]) as f64).powi(2)).sum();
+                assert!((squared.sqrt() - DistanceMetric::euclidean(a, b)).abs() < 1e-3);
+            }
+        }
+        let points: Vec<[f32; 3]> = cities.iter().map(|c| metric.get_kd_tree_point(c)).collect();
+        for k in 0..3 {
+            assert!(
+                points.iter().any(|p| p[k] != points[0][k]),
+                "axis {} constant",
+                k
+            );
+        }
+    }
+}
@@ -1,63 +1,167 @@
 use std::{
+    error::Error,
     fs::{read_to_string, write},
-    time::{Duration, Instant},
+    time::Duration,
 };
 
+use csv::Writer;
+
+use super::{city::City, distance_metric::DistanceMetric, initial_tour::TourNodes};
+
 pub struct GraphMetadata {
     path: Vec<u16>,
+    // cities in tour order, without repeating the first one at the end
+    tour: Vec<City>,
     distance: f64,
     total_time: Duration,
+    metric: DistanceMetric,
+    matrix: Option<String>,
+    seed: u64,
     custom_info: String,
 }
 
 impl GraphMetadata {
-    pub fn new(path: Vec<u16>, distance: f64, total_time: Duration, custom_info: String) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        path: Vec<u16>,
+        cities: &[City],
+        distance: f64,
+        total_time: Duration,
+        metric: DistanceMetric,
+        matrix: Option<String>,
+        seed: u64,
+        custom_info: String,
+    ) -> Self {
+        let tour_len = if path.len() > 1 && path.first() == path.last() {
+            path.len() - 1
+        } else {
+            path.len()
+        };
+        let tour = path[..tour_len]
+            .iter()
+            .map(|&id| cities[id as usize].clone())
+            .collect();
+
         GraphMetadata {
             path,
+            tour,
             distance,
             total_time,
+            metric,
+            matrix,
+            seed,
             custom_info,
         }
     }
 
-    pub fn get_distance_from_file(path: &String) -> f64 {
-        let metadata_string = read_to_string(path).unwrap();
-        let split = metadata_string.split("\n");
-
-        let mut distance = 0.0;
-
-        for line in split {
-            if line.starts_with("Distance: ") {
-                distance = line["Distance: ".len()..].trim().parse::<f64>().unwrap();
+    // The codes of the Tour section, or the Path positions for files written before it
+    pub fn read_tour_from_file(path: &str) -> Result<TourNodes, Box<dyn Error>> {
+        let metadata_string = read_to_string(path)?;
+        let mut lines = metadata_string.lines();
+        let mut positions = None;
+        while let Some(line) = lines.next() {
+            if let Some(value) = line.strip_prefix("Path: ") {
+                let value = value.trim().trim_start_matches('[').trim_end_matches(']');
+                let path_positions = value
+                    .split(',')
+                    .filter(|position| !position.trim().is_empty())
+                    .map(|position| position.trim().parse::<usize>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| format!("{}: invalid Path line", path))?;
+                positions = Some(path_positions);
+            } else if line == "Tour:" {
+                // "<position>: <code> <name> ..."
+                let codes = lines
+                    .filter_map(|line| line.split_once(": "))
+                    .map(|(_, city)| {
+                        let code = city.split_whitespace().next().unwrap_or_default();
+                        code.parse::<u32>()
+                            .map_err(|_| format!("{}: invalid city '{}' in Tour", path, city))
+                    })
+                    .collect::<Result<_, _>>()?;
+                return Ok(TourNodes::Codes(codes));
             }
         }
-
-        distance
-    }
-
-    pub fn get_time_from_file(path: &String) -> f64 {
-        let metadata_string = read_to_string(path).unwrap();
-        let split = metadata_string.split("\n");
-
-        let mut time = 0.0;
-
-        for line in split {
-            if line.starts_with("Total Time: ") {
-                time = line["Total Time: ".len()..].trim().parse::<f64>().unwrap();
+        match positions {
+            // the path is closed on its first city
+            Some(mut positions) => {
+                if positions.len() > 1 && positions.first() == positions.last() {
+                    positions.pop();
+                }
+                Ok(TourNodes::Positions(positions))
             }
+            None => Err(format!("{}: no Path or Tour in the metadata", path).into()),
         }
-
-        time
     }
 
-    pub fn generate_file(&self, path: String) {
-        let file_text = format!(
-            "Path: {:?}\nDistance: {}\nTotal Time: {}\n{}",
+    pub fn generate_file(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let distance_info = match &self.matrix {
+            Some(matrix) => format!("Matrix: {}", matrix),
+            None => format!(
+                "Metric: {} ({})",
+                self.metric.get_name(),
+                self.metric.get_unit()
+            ),
+        };
+        let mut file_text = format!(
+            "Path: {:?}\nDistance: {}\nTotal Time: {}\n{}\nSeed: {}\n{}",
             &self.path,
             &self.distance,
             &self.total_time.as_secs_f64(),
+            distance_info,
+            &self.seed,
             &self.custom_info
         );
-        write(path, file_text).unwrap();
+        if !file_text.ends_with('\n') {
+            file_text.push('\n');
+        }
+        file_text.push_str("Tour:\n");
+        for (position, city) in self.tour.iter().enumerate() {
+            file_text.push_str(&format!("{}\n", Self::describe_city(position + 1, city)));
+        }
+        write(path, file_text)?;
+        Ok(())
+    }
+
+    // "<position>: <code> <name> - <region> (capital)"
+    fn describe_city(position: usize, city: &City) -> String {
+        let mut description = format!("{}: {} {}", position, city.get_code(), city.get_name());
+        if let Some(region) = city.get_region_name() {
+            description.push_str(&format!(" - {}", region));
+        }
+        if city.is_capital() {
+            description.push_str(" (capital)");
+        }
+        description
+    }
+
+    pub fn generate_tour_csv(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = Writer::from_path(path)?;
+        writer.write_record([
+            "position",
+            "id",
+            "code",
+            "name",
+            "region",
+            "capital",
+            "timezone",
+            "latitude",
+            "longitude",
+        ])?;
+        for (position, city) in self.tour.iter().enumerate() {
+            writer.write_record([
+                (position + 1).to_string(),
+                city.get_id().to_string(),
+                city.get_code().to_string(),
+                city.get_name().clone(),
+                city.get_region_name().unwrap_or_default(),
+                (city.is_capital() as u8).to_string(),
+                city.get_timezone().cloned().unwrap_or_default(),
+                city.get_coordinates().get_latitude().to_string(),
+                city.get_coordinates().get_longitude().to_string(),
+            ])?;
+        }
+        writer.flush()?;
+        Ok(())
     }
 }
@@ -0,0 +1,175 @@
+use std::{collections::HashMap, error::Error, fs::File, path::Path};
+
+use csv::ReaderBuilder;
+
+use super::{
+    city::City, graph_metadata::GraphMetadata, run_record::RunRecord, tsplib::TsplibInstance,
+};
+
+// How a tour file names its cities
+pub enum TourNodes {
+    // codigo_ibge, or the node number for TSPLIB instances
+    Codes(Vec<u32>),
+    // positions in the list of cities of the run
+    Positions(Vec<usize>),
+}
+
+// Tour given to start a run from, checked to visit every city of the instance once
+#[derive(Debug, Clone)]
+pub struct InitialTour {
+    path: Vec<u16>,
+    source: String,
+}
+
+impl InitialTour {
+    // `.tour`: TSPLIB tour; `.csv`: codes in the `code` or `codigo_ibge` column (or the only
+    // column); `.json`: result.json of a previous run; anything else: its metadata.txt
+    pub fn read_from_file(
+        path: &str,
+        cities: &[City],
+        tsplib: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        let nodes = match extension.as_deref() {
+            Some("tour") => {
+                let nodes = TsplibInstance::read_tour(path)?;
+                // tours of the ufs are written with the position + 1, codigo_ibge never fits
+                if !tsplib && nodes.iter().all(|&node| node as usize <= cities.len()) {
+                    TourNodes::Positions(nodes.iter().map(|&node| node as usize - 1).collect())
+                } else {
+                    TourNodes::Codes(nodes)
+                }
+            }
+            Some("csv") => TourNodes::Codes(Self::read_csv(path)?),
+            Some("json") => TourNodes::Codes(RunRecord::read_json(path)?.get_tour().clone()),
+            _ => GraphMetadata::read_tour_from_file(path)?,
+        };
+        let path_in_cities =
+            Self::validate(nodes, cities).map_err(|err| format!("{}: {}", path, err))?;
+        Ok(InitialTour {
+            path: path_in_cities,
+            source: path.to_string(),
+        })
+    }
+
+    fn read_csv(path: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+        let file = File::open(path)?;
+        let mut reader = ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(file);
+        let mut records = reader.records().peekable();
+        // a first row that is not a code is the header
+        let mut column = 0;
+        if let Some(Ok(first)) = records.peek()
+            && first
+                .iter()
+                .any(|field| field.trim().parse::<u32>().is_err())
+        {
+            let headers: Vec<String> = first.iter().map(|h| h.trim().to_lowercase()).collect();
+            column = match headers
+                .iter()
+                .position(|h| h == "code" || h == "codigo_ibge")
+            {
+                Some(column) => column,
+                None if headers.len() == 1 => 0,
+                None => {
+                    return Err(
+                        format!("{}: no code or codigo_ibge column in the header", path).into(),
+                    );
+                }
+            };
+            records.next();
+        }
+
+        let mut codes = vec![];
+        for record in records {
+            let record = record?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let value = record.get(column).unwrap_or_default().trim();
+            let code = value
+                .parse::<u32>()
+                .map_err(|_| format!("{}: invalid code '{}' at line {}", path, value, line))?;
+            codes.push(code);
+        }
+        Ok(codes)
+    }
+
+    // The tour in positions of the cities, when it visits each of them exactly once
+    fn validate(nodes: TourNodes, cities: &[City]) -> Result<Vec<u16>, String> {
+        let positions = match nodes {
+            TourNodes::Positions(positions) => {
+                if let Some(position) = positions.iter().find(|&&p| p >= cities.len()) {
+                    return Err(format!(
+                        "position {} is out of the {} cities",
+                        position,
+                        cities.len()
+                    ));
+                }
+                positions
+            }
+            TourNodes::Codes(codes) => {
+                let index: HashMap<u32, usize> = cities
+                    .iter()
+                    .enumerate()
+                    .map(|(position, city)| (city.get_code(), position))
+                    .collect();
+                codes
+                    .iter()
+                    .map(|code| {
+                        index
+                            .get(code)
+                            .copied()
+                            .ok_or_else(|| format!("city {} is not in the instance", code))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+        };
+
+        let mut visited = vec![false; cities.len()];
+        for &position in &positions {
+            if visited[position] {
+                return Err(format!(
+                    "city {} appears more than once",
+                    cities[position].get_code()
+                ));
+            }
+            visited[position] = true;
+        }
+        let missing: Vec<String> = visited
+            .iter()
+            .enumerate()
+            .filter(|&(_, &visited)| !visited)
+            .map(|(position, _)| cities[position].get_code().to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "the tour misses {} of the {} cities: {}{}",
+                missing.len(),
+                cities.len(),
+                missing[..missing.len().min(10)].join(", "),
+                if missing.len() > 10 { ", ..." } else { "" }
+            ));
+        }
+        Ok(positions.iter().map(|&position| position as u16).collect())
+    }
+
+    pub fn get_path(&self) -> &Vec<u16> {
+        &self.path
+    }
+
+    pub fn get_source(&self) -> &String {
+        &self.source
+    }
+
+    // Length of the closed tour
+    pub fn get_distance(&self, distance_matrix: &[f64]) -> f64 {
+        let n = self.path.len();
+        (0..n)
+            .map(|i| distance_matrix[self.path[i] as usize * n + self.path[(i + 1) % n] as usize])
+            .sum()
+    }
+}
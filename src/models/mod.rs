@@ -1,4 +1,10 @@
 pub mod city;
+pub mod city_importer;
 pub mod coordinates;
+pub mod distance_metric;
 pub mod graph_metadata;
+pub mod initial_tour;
+pub mod road_matrix;
+pub mod run_record;
+pub mod tsplib;
 pub mod uf;
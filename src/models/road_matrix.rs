@@ -0,0 +1,237 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{File, read},
+    path::Path,
+};
+
+use csv::ReaderBuilder;
+
+use super::city::City;
+
+// Binary layout (little endian):
+// "TSPM" | n: u32 | n x codigo_ibge: u32 | n x n x value: f64 (row = origin, column = destination)
+const BINARY_MAGIC: &[u8; 4] = b"TSPM";
+const SYMMETRY_TOLERANCE: f64 = 1e-9;
+
+#[derive(Debug, Clone)]
+pub struct RoadMatrix {
+    values: Vec<f64>,
+    size: usize,
+    symmetric: bool,
+    unit: String,
+    source: String,
+}
+
+impl RoadMatrix {
+    // Reads a distance/duration matrix keyed by codigo_ibge and keeps only the loaded cities,
+    // in the same order as their ids. A missing entry is taken from the opposite direction,
+    // so files storing a single triangle of a symmetric matrix are accepted.
+    pub fn read_from_file(path: &str, cities: &[City], unit: &str) -> Result<Self, Box<dyn Error>> {
+        let (codes, file_values) = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => Self::read_csv(path)?,
+            _ => Self::read_binary(path)?,
+        };
+
+        let file_size = codes.len();
+        let mut file_index: HashMap<u32, usize> = HashMap::with_capacity(file_size);
+        for (i, &code) in codes.iter().enumerate() {
+            if file_index.insert(code, i).is_some() {
+                return Err(format!("codigo_ibge {} appears twice in {}", code, path).into());
+            }
+        }
+
+        let indexes: Vec<usize> = cities
+            .iter()
+            .map(|city| {
+                file_index.get(&city.get_code()).copied().ok_or_else(|| {
+                    format!("codigo_ibge {} is not present in {}", city.get_code(), path)
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let n = cities.len();
+        let mut values = vec![0.0; n * n];
+        for (i, &fi) in indexes.iter().enumerate() {
+            for (j, &fj) in indexes.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                let mut value = file_values[fi * file_size + fj];
+                if value.is_nan() {
+                    value = file_values[fj * file_size + fi];
+                }
+                if value.is_nan() {
+                    return Err(format!(
+                        "no value between codigo_ibge {} and {} in {}",
+                        codes[fi], codes[fj], path
+                    )
+                    .into());
+                }
+                if value < 0.0 || value.is_infinite() {
+                    return Err(format!(
+                        "invalid value {} from codigo_ibge {} to {} in {}",
+                        value, codes[fi], codes[fj], path
+                    )
+                    .into());
+                }
+                values[i * n + j] = value;
+            }
+        }
+
+        let symmetric = (0..n).all(|i| {
+            (i + 1..n).all(|j| {
+                let (a, b) = (values[i * n + j], values[j * n + i]);
+                (a - b).abs() <= SYMMETRY_TOLERANCE * a.abs().max(b.abs()).max(1.0)
+            })
+        });
+
+        Ok(RoadMatrix {
+            values,
+            size: n,
+            symmetric,
+            unit: unit.to_string(),
+            source: path.to_string(),
+        })
+    }
+
+    // Square matrix: header "codigo_ibge,<code>,<code>,..." and one row per origin city
+    // starting with its code. Empty cells are treated as missing.
+    fn read_csv(path: &str) -> Result<(Vec<u32>, Vec<f64>), Box<dyn Error>> {
+        let file = File::open(path)?;
+        let mut reader = ReaderBuilder::new().has_headers(true).from_reader(file);
+
+        let codes: Vec<u32> = reader
+            .headers()?
+            .iter()
+            .skip(1)
+            .enumerate()
+            .map(|(column, code)| {
+                code.trim().parse::<u32>().map_err(|_| {
+                    format!(
+                        "invalid codigo_ibge '{}' in header column {} of {}",
+                        code,
+                        column + 2,
+                        path
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let n = codes.len();
+        let mut values = vec![f64::NAN; n * n];
+        let mut seen_rows = vec![false; n];
+        let column_index: HashMap<u32, usize> =
+            codes.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+
+        for record in reader.records() {
+            let record = record?;
+            let line = record.position().map(|p| p.line()).unwrap_or_default();
+            let origin = record[0].trim().parse::<u32>().map_err(|_| {
+                format!(
+                    "invalid codigo_ibge '{}' at line {} of {}",
+                    &record[0], line, path
+                )
+            })?;
+            let row = *column_index.get(&origin).ok_or_else(|| {
+                format!(
+                    "codigo_ibge {} at line {} of {} is not in the header",
+                    origin, line, path
+                )
+            })?;
+            if record.len() != n + 1 {
+                return Err(format!(
+                    "line {} of {} has {} values, expected {}",
+                    line,
+                    path,
+                    record.len() - 1,
+                    n
+                )
+                .into());
+            }
+            seen_rows[row] = true;
+
+            for (column, cell) in record.iter().skip(1).enumerate() {
+                let cell = cell.trim();
+                if !cell.is_empty() {
+                    values[row * n + column] = cell.parse::<f64>().map_err(|_| {
+                        format!(
+                            "invalid value '{}' at line {}, column {} of {}",
+                            cell,
+                            line,
+                            column + 2,
+                            path
+                        )
+                    })?;
+                }
+            }
+        }
+
+        if let Some(missing) = seen_rows.iter().position(|&seen| !seen) {
+            return Err(format!("no row for codigo_ibge {} in {}", codes[missing], path).into());
+        }
+
+        Ok((codes, values))
+    }
+
+    fn read_binary(path: &str) -> Result<(Vec<u32>, Vec<f64>), Box<dyn Error>> {
+        let bytes = read(path)?;
+        if bytes.len() < 8 || &bytes[0..4] != BINARY_MAGIC {
+            return Err(format!("{} is not a binary distance matrix", path).into());
+        }
+
+        let n = u32::from_le_bytes(bytes[4..8].try_into()?) as usize;
+        let codes_end = 8 + 4 * n;
+        let expected_len = codes_end + 8 * n * n;
+        if bytes.len() != expected_len {
+            return Err(format!(
+                "{} has {} bytes, expected {} for {} cities",
+                path,
+                bytes.len(),
+                expected_len,
+                n
+            )
+            .into());
+        }
+
+        let codes = bytes[8..codes_end]
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        let values = bytes[codes_end..]
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+
+        Ok((codes, values))
+    }
+
+    pub fn get_values(&self) -> &Vec<f64> {
+        &self.values
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+
+    pub fn get_unit(&self) -> &str {
+        &self.unit
+    }
+
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn get_description(&self) -> String {
+        let symmetry = if self.symmetric {
+            "symmetric"
+        } else {
+            "asymmetric"
+        };
+        format!("{} ({}, {})", self.source, self.unit, symmetry)
+    }
+}
@@ -0,0 +1,520 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs::{OpenOptions, metadata, read_to_string, write},
+    path::{Component, Path},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use csv::WriterBuilder;
+use serde::{Deserialize, Serialize};
+
+use super::{city::City, distance_metric::DistanceMetric};
+
+// Bumped on every change of the fields below; readers refuse newer records
+pub const RUN_RECORD_VERSION: u32 = 1;
+
+// Seconds spent in each phase of a run. Records migrated from metadata.txt only know the
+// algorithm time, the other phases are 0.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PhaseTimings {
+    // cities, road matrix or TSPLIB instance
+    pub loading: f64,
+    pub distance_matrix: f64,
+    pub algorithm: f64,
+    pub lower_bound: f64,
+    pub total: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedBound {
+    pub kind: String,
+    pub value: f64,
+}
+
+// Everything a run produced, written as result.json and as one row of a results CSV
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    schema_version: u32,
+    // unix seconds
+    timestamp: Option<u64>,
+    algorithm: String,
+    // UF, TSPLIB name, or "<cities file stem>/<region>"
+    instance: String,
+    // file stem of the config, or "default"
+    parameter_set: String,
+    // section of the algorithm in the config, null when unknown
+    parameters: serde_json::Value,
+    seed: Option<u64>,
+    // none when the distances come from a matrix
+    metric: Option<String>,
+    matrix: Option<String>,
+    distance: f64,
+    // length of the tour in every metric, for cities with geographic coordinates
+    distances: BTreeMap<String, f64>,
+    timings: PhaseTimings,
+    iterations: Option<u64>,
+    evaluations: Option<u64>,
+    stop_reason: Option<String>,
+    optimal: bool,
+    lower_bound: Option<RecordedBound>,
+    // codigo_ibge, or the node number for TSPLIB instances, without closing the tour
+    tour: Vec<u32>,
+    // what the algorithm reported, free text
+    info: String,
+}
+
+// Flat view of a record, one CSV row
+#[derive(Serialize)]
+struct RunRow<'a> {
+    schema_version: u32,
+    timestamp: Option<u64>,
+    algorithm: &'a str,
+    instance: &'a str,
+    parameter_set: &'a str,
+    parameters: String,
+    seed: Option<u64>,
+    metric: Option<&'a str>,
+    matrix: Option<&'a str>,
+    distance: f64,
+    euclidean: Option<f64>,
+    haversine: Option<f64>,
+    vincenty: Option<f64>,
+    loading_time: f64,
+    distance_matrix_time: f64,
+    algorithm_time: f64,
+    lower_bound_time: f64,
+    total_time: f64,
+    iterations: Option<u64>,
+    evaluations: Option<u64>,
+    stop_reason: Option<&'a str>,
+    optimal: bool,
+    lower_bound: Option<f64>,
+    lower_bound_kind: Option<&'a str>,
+    // codes separated by spaces
+    tour: String,
+}
+
+const METRICS: [DistanceMetric; 3] = [
+    DistanceMetric::Euclidean,
+    DistanceMetric::Haversine,
+    DistanceMetric::Vincenty,
+];
+
+impl RunRecord {
+    pub fn new(algorithm: &str, instance: &str, distance: f64, tour: Vec<u32>) -> Self {
+        RunRecord {
+            schema_version: RUN_RECORD_VERSION,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            algorithm: algorithm.to_uppercase(),
+            instance: instance.to_string(),
+            parameter_set: "default".to_string(),
+            parameters: serde_json::Value::Null,
+            seed: None,
+            metric: None,
+            matrix: None,
+            distance,
+            distances: BTreeMap::new(),
+            timings: PhaseTimings::default(),
+            iterations: None,
+            evaluations: None,
+            stop_reason: None,
+            optimal: false,
+            lower_bound: None,
+            tour,
+            info: String::new(),
+        }
+    }
+
+    pub fn set_parameters(&mut self, parameter_set: &str, parameters: serde_json::Value) {
+        self.parameter_set = parameter_set.to_string();
+        self.parameters = parameters;
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    // The metric of the coordinates, or the description of the matrix that replaced it
+    pub fn set_distance_source(&mut self, metric: &DistanceMetric, matrix: Option<String>) {
+        self.metric = matrix.is_none().then(|| metric.get_name().to_string());
+        self.matrix = matrix;
+    }
+
+    // Length of the closed tour in every metric
+    pub fn measure_distances(&mut self, cities: &[City], path: &[u16]) {
+        self.distances = METRICS
+            .iter()
+            .map(|metric| {
+                let distance = (0..path.len())
+                    .map(|i| {
+                        metric.calculate_distance(
+                            cities[path[i] as usize].get_coordinates(),
+                            cities[path[(i + 1) % path.len()] as usize].get_coordinates(),
+                        )
+                    })
+                    .sum();
+                (metric.get_name().to_string(), distance)
+            })
+            .collect();
+    }
+
+    pub fn set_timings(&mut self, timings: PhaseTimings) {
+        self.timings = timings;
+    }
+
+    pub fn set_termination(
+        &mut self,
+        iterations: u64,
+        evaluations: u64,
+        stop_reason: &str,
+        optimal: bool,
+    ) {
+        self.iterations = Some(iterations);
+        self.evaluations = Some(evaluations);
+        self.stop_reason = Some(stop_reason.to_string());
+        self.optimal = optimal;
+    }
+
+    pub fn set_lower_bound(&mut self, kind: &str, value: f64) {
+        self.lower_bound = Some(RecordedBound {
+            kind: kind.to_string(),
+            value,
+        });
+    }
+
+    pub fn set_info(&mut self, info: &str) {
+        self.info = info.trim_end().to_string();
+    }
+
+    pub fn get_timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+
+    pub fn get_algorithm(&self) -> &String {
+        &self.algorithm
+    }
+
+    pub fn get_instance(&self) -> &String {
+        &self.instance
+    }
+
+    pub fn get_parameter_set(&self) -> &String {
+        &self.parameter_set
+    }
+
+    pub fn get_seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    pub fn get_distance(&self) -> f64 {
+        self.distance
+    }
+
+    pub fn get_stop_reason(&self) -> Option<&String> {
+        self.stop_reason.as_ref()
+    }
+
+    pub fn get_timings(&self) -> &PhaseTimings {
+        &self.timings
+    }
+
+    pub fn get_tour(&self) -> &Vec<u32> {
+        &self.tour
+    }
+
+    pub fn write_json(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn read_json(path: &str) -> Result<Self, Box<dyn Error>> {
+        let content = read_to_string(path)?;
+        let record: RunRecord =
+            serde_json::from_str(&content).map_err(|err| format!("{}: {}", path, err))?;
+        if record.schema_version > RUN_RECORD_VERSION {
+            return Err(format!(
+                "{}: schema version {} is newer than {}",
+                path, record.schema_version, RUN_RECORD_VERSION
+            )
+            .into());
+        }
+        Ok(record)
+    }
+
+    // One row per record, replacing the file
+    pub fn write_csv(path: &str, records: &[&RunRecord]) -> Result<(), Box<dyn Error>> {
+        let mut writer = WriterBuilder::new().from_path(path)?;
+        for record in records {
+            writer.serialize(record.to_row())?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    // Appends the record as a row, with the header when the file is new or empty
+    pub fn append_csv(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let has_rows = metadata(path).is_ok_and(|file| file.len() > 0);
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let mut writer = WriterBuilder::new()
+            .has_headers(!has_rows)
+            .from_writer(file);
+        writer.serialize(self.to_row())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn to_row(&self) -> RunRow<'_> {
+        let distance_in = |metric: &DistanceMetric| self.distances.get(metric.get_name()).copied();
+        RunRow {
+            schema_version: self.schema_version,
+            timestamp: self.timestamp,
+            algorithm: &self.algorithm,
+            instance: &self.instance,
+            parameter_set: &self.parameter_set,
+            parameters: self.parameters.to_string(),
+            seed: self.seed,
+            metric: self.metric.as_deref(),
+            matrix: self.matrix.as_deref(),
+            distance: self.distance,
+            euclidean: distance_in(&DistanceMetric::Euclidean),
+            haversine: distance_in(&DistanceMetric::Haversine),
+            vincenty: distance_in(&DistanceMetric::Vincenty),
+            loading_time: self.timings.loading,
+            distance_matrix_time: self.timings.distance_matrix,
+            algorithm_time: self.timings.algorithm,
+            lower_bound_time: self.timings.lower_bound,
+            total_time: self.timings.total,
+            iterations: self.iterations,
+            evaluations: self.evaluations,
+            stop_reason: self.stop_reason.as_deref(),
+            optimal: self.optimal,
+            lower_bound: self.lower_bound.as_ref().map(|bound| bound.value),
+            lower_bound_kind: self.lower_bound.as_ref().map(|bound| bound.kind.as_str()),
+            tour: self
+                .tour
+                .iter()
+                .map(|code| code.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+
+    // Reads a metadata.txt written before result.json existed. The algorithm and the
+    // instance come from the folder, src/assets/results/<instance>/<ALG>[_suffix]. Files
+    // without a Tour section only have the positions of the Path, the cities of the run
+    // map them to codes; with the cities the distances in every metric are measured too.
+    pub fn read_metadata_file(path: &str, cities: Option<&[City]>) -> Result<Self, Box<dyn Error>> {
+        let content = read_to_string(path)?;
+        let (algorithm, instance, folder_metric) = Self::describe_results_folder(path)?;
+        let invalid = |line: &str| format!("{}: invalid line '{}'", path, line);
+
+        let mut positions: Option<Vec<usize>> = None;
+        let mut codes: Option<Vec<u32>> = None;
+        let mut distance = None;
+        let mut algorithm_time = 0.0;
+        let mut metric = None;
+        let mut matrix = None;
+        let mut seed = None;
+        let mut stop_reason = None;
+        let mut evaluations = None;
+        let mut lower_bound = None;
+        let mut parameters = serde_json::Value::Null;
+        let mut info = vec![];
+
+        let mut lines = content.lines();
+        while let Some(line) = lines.next() {
+            let (key, value) = match line.split_once(": ") {
+                Some((key, value)) => (key, value.trim()),
+                None if line.trim() == "Tour:" => {
+                    // "<position>: <code> <name> ..."
+                    let tour_codes = lines
+                        .by_ref()
+                        .filter_map(|line| line.split_once(": "))
+                        .map(|(_, city)| city.split_whitespace().next().unwrap_or_default())
+                        .map(|code| code.parse::<u32>().map_err(|_| invalid(code)))
+                        .collect::<Result<_, _>>()?;
+                    codes = Some(tour_codes);
+                    continue;
+                }
+                None => {
+                    if !line.trim().is_empty() {
+                        info.push(line.to_string());
+                    }
+                    continue;
+                }
+            };
+            match key {
+                "Path" => {
+                    let path_positions = value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .filter(|position| !position.trim().is_empty())
+                        .map(|position| position.trim().parse::<usize>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| invalid(line))?;
+                    positions = Some(path_positions);
+                }
+                "Distance" => distance = Some(value.parse::<f64>().map_err(|_| invalid(line))?),
+                "Total Time" => algorithm_time = value.parse::<f64>().map_err(|_| invalid(line))?,
+                // "NAME (unit)"
+                "Metric" => metric = value.split_whitespace().next().map(|m| m.to_string()),
+                "Matrix" => matrix = Some(value.to_string()),
+                "Seed" => seed = Some(value.parse::<u64>().map_err(|_| invalid(line))?),
+                "Stop Reason" => stop_reason = Some(value.to_string()),
+                "Evaluations" => {
+                    evaluations = Some(value.parse::<u64>().map_err(|_| invalid(line))?)
+                }
+                // "value (kind)"
+                "Lower Bound" => {
+                    let (bound, kind) = value.split_once(' ').ok_or_else(|| invalid(line))?;
+                    lower_bound = Some(RecordedBound {
+                        kind: kind.trim_matches(|c| c == '(' || c == ')').to_string(),
+                        value: bound.parse::<f64>().map_err(|_| invalid(line))?,
+                    });
+                }
+                // derived from the distance and the bound
+                "Gap" => {}
+                "Parameters" => match parse_described_parameters(value) {
+                    Some(value) => parameters = value,
+                    None => info.push(line.to_string()),
+                },
+                _ => info.push(line.to_string()),
+            }
+        }
+
+        let distance = distance.ok_or_else(|| format!("{}: no Distance line", path))?;
+        // the path is closed on its first city, Branch and Bound closed it twice
+        if let Some(positions) = &mut positions {
+            while positions.len() > 1 && positions.first() == positions.last() {
+                positions.pop();
+            }
+        }
+        let path_in_cities: Option<Vec<u16>> = match (cities, &codes, &positions) {
+            (Some(cities), Some(codes), _) => {
+                let index: HashMap<u32, usize> = cities
+                    .iter()
+                    .enumerate()
+                    .map(|(position, city)| (city.get_code(), position))
+                    .collect();
+                codes
+                    .iter()
+                    .map(|code| index.get(code).map(|&position| position as u16))
+                    .collect()
+            }
+            (Some(cities), None, Some(positions)) => positions
+                .iter()
+                .map(|&position| (position < cities.len()).then_some(position as u16))
+                .collect(),
+            _ => None,
+        };
+        let tour = match (codes, &path_in_cities, cities) {
+            (Some(codes), _, _) => codes,
+            (None, Some(path), Some(cities)) => path
+                .iter()
+                .map(|&position| cities[position as usize].get_code())
+                .collect(),
+            _ => {
+                return Err(format!(
+                    "{}: no Tour section, the cities of {} are needed to read the Path",
+                    path, instance
+                )
+                .into());
+            }
+        };
+
+        let mut record = RunRecord::new(&algorithm, &instance, distance, tour);
+        record.timestamp = metadata(path)
+            .and_then(|file| file.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|elapsed| elapsed.as_secs());
+        record.parameters = parameters;
+        record.seed = seed;
+        record.metric = match &matrix {
+            Some(_) => None,
+            None => Some(metric.or(folder_metric).unwrap_or("EUCLIDEAN".to_string())),
+        };
+        record.matrix = matrix;
+        if let (Some(cities), Some(path)) = (cities, &path_in_cities) {
+            record.measure_distances(cities, path);
+        }
+        record.timings = PhaseTimings {
+            algorithm: algorithm_time,
+            total: algorithm_time,
+            ..PhaseTimings::default()
+        };
+        record.evaluations = evaluations;
+        record.stop_reason = stop_reason;
+        record.lower_bound = lower_bound;
+        record.info = info.join("\n");
+        Ok(record)
+    }
+
+    // (algorithm, instance, metric of the folder suffix) of .../results/<instance>/<ALG>
+    fn describe_results_folder(
+        path: &str,
+    ) -> Result<(String, String, Option<String>), Box<dyn Error>> {
+        let folders: Vec<String> = Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+                _ => None,
+            })
+            .collect();
+        let after_results = match folders.iter().rposition(|folder| folder == "results") {
+            Some(position) => &folders[position + 1..],
+            None => &folders[folders.len().saturating_sub(2)..],
+        };
+        let (instance, algorithm_folder) = match after_results {
+            [instance, algorithm] => (instance.clone(), algorithm),
+            [group, instance, algorithm] if group == "TSPLIB" => (instance.clone(), algorithm),
+            [cities, region, algorithm] => (format!("{}/{}", cities, region), algorithm),
+            _ => {
+                return Err(format!(
+                    "{}: expected the folder <instance>/<algorithm> of the results",
+                    path
+                )
+                .into());
+            }
+        };
+        // "<ALG>_<metric>" or "<ALG>_<matrix stem>"
+        let (algorithm, metric) = match algorithm_folder.split_once('_') {
+            Some((algorithm, suffix)) => (
+                algorithm,
+                DistanceMetric::get_metric_from_str(suffix).map(|m| m.get_name().to_string()),
+            ),
+            None => (algorithm_folder.as_str(), None),
+        };
+        Ok((algorithm.to_uppercase(), instance, metric))
+    }
+}
+
+// Inverse of describe_parameters: "a = 1, b.c = [1, 2]" as JSON
+fn parse_described_parameters(description: &str) -> Option<serde_json::Value> {
+    // a ", " inside a value does not start a new "key = value"
+    let mut entries: Vec<String> = vec![];
+    for piece in description.split(", ") {
+        let starts_entry = piece.split_once(" = ").is_some_and(|(key, _)| {
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        });
+        match entries.last_mut() {
+            Some(entry) if !starts_entry => {
+                entry.push_str(", ");
+                entry.push_str(piece);
+            }
+            _ => entries.push(piece.to_string()),
+        }
+    }
+    let table: toml::Table = toml::from_str(&entries.join("\n")).ok()?;
+    serde_json::to_value(table).ok()
+}
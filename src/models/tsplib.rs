@@ -0,0 +1,603 @@
+use std::{
+    error::Error,
+    fs::{read_to_string, write},
+};
+
+use super::{city::City, coordinates::Coordinates};
+
+// Constants from the TSPLIB95 documentation, needed to reproduce the published optima
+#[allow(clippy::approx_constant)]
+const GEO_PI: f64 = 3.141592;
+const GEO_RRR: f64 = 6378.388;
+
+// node number, x, y
+type Node = (u32, f64, f64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EdgeWeightType {
+    Euc2d,
+    Ceil2d,
+    Geo,
+    Att,
+    Explicit,
+}
+
+impl EdgeWeightType {
+    fn get_type_from_str(str: &str) -> Option<Self> {
+        match str.to_uppercase().as_str() {
+            "EUC_2D" => Some(Self::Euc2d),
+            "CEIL_2D" => Some(Self::Ceil2d),
+            "GEO" => Some(Self::Geo),
+            "ATT" => Some(Self::Att),
+            "EXPLICIT" => Some(Self::Explicit),
+            _ => None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        match self {
+            Self::Euc2d => "EUC_2D",
+            Self::Ceil2d => "CEIL_2D",
+            Self::Geo => "GEO",
+            Self::Att => "ATT",
+            Self::Explicit => "EXPLICIT",
+        }
+    }
+
+    fn calculate_distance(&self, (xi, yi): (f64, f64), (xj, yj): (f64, f64)) -> f64 {
+        let xd = xi - xj;
+        let yd = yi - yj;
+        match self {
+            Self::Euc2d => Self::nint((xd * xd + yd * yd).sqrt()),
+            Self::Ceil2d => (xd * xd + yd * yd).sqrt().ceil(),
+            Self::Att => {
+                let rij = ((xd * xd + yd * yd) / 10.0).sqrt();
+                let tij = Self::nint(rij);
+                if tij < rij { tij + 1.0 } else { tij }
+            }
+            Self::Geo => {
+                let (latitude_i, longitude_i) = (Self::geo_radians(xi), Self::geo_radians(yi));
+                let (latitude_j, longitude_j) = (Self::geo_radians(xj), Self::geo_radians(yj));
+                let q1 = (longitude_i - longitude_j).cos();
+                let q2 = (latitude_i - latitude_j).cos();
+                let q3 = (latitude_i + latitude_j).cos();
+                (GEO_RRR * (0.5 * ((1.0 + q1) * q2 - (1.0 - q1) * q3)).acos() + 1.0).trunc()
+            }
+            Self::Explicit => unreachable!("explicit instances have no distance function"),
+        }
+    }
+
+    fn nint(x: f64) -> f64 {
+        (x + 0.5).trunc()
+    }
+
+    // DDD.MM coordinates, degrees truncated as in the reference implementation
+    fn geo_radians(x: f64) -> f64 {
+        let degrees = x.trunc();
+        let minutes = x - degrees;
+        GEO_PI * (degrees + 5.0 * minutes / 3.0) / 180.0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TsplibInstance {
+    name: String,
+    edge_weight_type: EdgeWeightType,
+    cities: Vec<City>,
+    distance_matrix: Vec<f64>,
+    symmetric: bool,
+}
+
+impl TsplibInstance {
+    pub fn read_from_file(path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&read_to_string(path)?, path)
+    }
+
+    // The path names the instance when the file has no NAME
+    fn parse(content: &str, path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut lines = content.lines().enumerate().peekable();
+
+        let mut name = String::new();
+        let mut problem_type = String::from("TSP");
+        let mut dimension: Option<usize> = None;
+        let mut edge_weight_type: Option<EdgeWeightType> = None;
+        let mut edge_weight_format = String::from("FULL_MATRIX");
+        let mut nodes: Vec<Node> = vec![];
+        let mut display_nodes: Vec<Node> = vec![];
+        let mut weights: Vec<f64> = vec![];
+
+        while let Some((line_number, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim().to_uppercase(), value.trim()),
+                None => (line.to_uppercase(), ""),
+            };
+
+            match key.as_str() {
+                "NAME" => name = value.to_string(),
+                "TYPE" => problem_type = value.to_uppercase(),
+                "DIMENSION" => {
+                    dimension = Some(value.parse::<usize>().map_err(|_| {
+                        format!("invalid DIMENSION '{}' at line {}", value, line_number + 1)
+                    })?)
+                }
+                "EDGE_WEIGHT_TYPE" => {
+                    edge_weight_type = Some(
+                        EdgeWeightType::get_type_from_str(value)
+                            .ok_or_else(|| format!("unsupported EDGE_WEIGHT_TYPE '{}'", value))?,
+                    )
+                }
+                "EDGE_WEIGHT_FORMAT" => edge_weight_format = value.to_uppercase(),
+                "NODE_COORD_SECTION" => nodes = Self::read_nodes(&mut lines)?,
+                "DISPLAY_DATA_SECTION" => display_nodes = Self::read_nodes(&mut lines)?,
+                "EDGE_WEIGHT_SECTION" => weights = Self::read_numbers(&mut lines)?,
+                "FIXED_EDGES_SECTION" | "TOUR_SECTION" => {
+                    Self::read_numbers(&mut lines)?;
+                }
+                "EOF" => break,
+                _ => {}
+            }
+        }
+
+        let n = dimension.ok_or("missing DIMENSION")?;
+        let edge_weight_type = edge_weight_type.ok_or("missing EDGE_WEIGHT_TYPE")?;
+        if n < 2 || n > u16::MAX as usize + 1 {
+            return Err(format!("DIMENSION {} is not supported", n).into());
+        }
+        if problem_type != "TSP" && problem_type != "ATSP" {
+            return Err(format!("TYPE {} is not supported", problem_type).into());
+        }
+
+        let distance_matrix = if edge_weight_type == EdgeWeightType::Explicit {
+            Self::create_explicit_matrix(&weights, n, &edge_weight_format)?
+        } else {
+            if nodes.len() != n {
+                return Err(format!(
+                    "NODE_COORD_SECTION has {} nodes, expected {}",
+                    nodes.len(),
+                    n
+                )
+                .into());
+            }
+            let mut matrix = vec![0.0; n * n];
+            for i in 0..n {
+                for j in i + 1..n {
+                    let distance = edge_weight_type
+                        .calculate_distance((nodes[i].1, nodes[i].2), (nodes[j].1, nodes[j].2));
+                    matrix[i * n + j] = distance;
+                    matrix[j * n + i] = distance;
+                }
+            }
+            matrix
+        };
+
+        let symmetric = (0..n)
+            .all(|i| (i + 1..n).all(|j| distance_matrix[i * n + j] == distance_matrix[j * n + i]));
+
+        let coordinates_nodes = if nodes.len() == n {
+            nodes
+        } else if display_nodes.len() == n {
+            display_nodes
+        } else {
+            (1..=n as u32).map(|node| (node, 0.0, 0.0)).collect()
+        };
+        // x is stored as longitude and y as latitude, so the plots keep the usual orientation.
+        // GEO coordinates are already latitude (x) and longitude (y).
+        let cities = coordinates_nodes
+            .iter()
+            .enumerate()
+            .map(|(id, &(node, x, y))| {
+                let coordinates = if edge_weight_type == EdgeWeightType::Geo {
+                    Coordinates::new(x as f32, y as f32)
+                } else {
+                    Coordinates::new(y as f32, x as f32)
+                };
+                City::new(
+                    id as u16,
+                    node,
+                    node.to_string(),
+                    None,
+                    None,
+                    false,
+                    None,
+                    coordinates,
+                )
+            })
+            .collect();
+
+        if name.is_empty() {
+            name = path.to_string();
+        }
+
+        Ok(TsplibInstance {
+            name,
+            edge_weight_type,
+            cities,
+            distance_matrix,
+            symmetric,
+        })
+    }
+
+    fn read_nodes<'a, I>(lines: &mut std::iter::Peekable<I>) -> Result<Vec<Node>, Box<dyn Error>>
+    where
+        I: Iterator<Item = (usize, &'a str)>,
+    {
+        let mut nodes = vec![];
+        while let Some((line_number, line)) = lines.next_if(|(_, l)| !Self::is_keyword(l)) {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.is_empty() {
+                continue;
+            }
+            if tokens.len() < 3 {
+                return Err(format!("invalid node at line {}: '{}'", line_number + 1, line).into());
+            }
+            let parse_error = || format!("invalid node at line {}: '{}'", line_number + 1, line);
+            nodes.push((
+                tokens[0].parse::<u32>().map_err(|_| parse_error())?,
+                tokens[1].parse::<f64>().map_err(|_| parse_error())?,
+                tokens[2].parse::<f64>().map_err(|_| parse_error())?,
+            ));
+        }
+        Ok(nodes)
+    }
+
+    fn read_numbers<'a, I>(lines: &mut std::iter::Peekable<I>) -> Result<Vec<f64>, Box<dyn Error>>
+    where
+        I: Iterator<Item = (usize, &'a str)>,
+    {
+        let mut numbers = vec![];
+        while let Some((line_number, line)) = lines.next_if(|(_, l)| !Self::is_keyword(l)) {
+            for token in line.split_whitespace() {
+                numbers.push(token.parse::<f64>().map_err(|_| {
+                    format!("invalid number '{}' at line {}", token, line_number + 1)
+                })?);
+            }
+        }
+        Ok(numbers)
+    }
+
+    fn is_keyword(line: &str) -> bool {
+        line.trim()
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic())
+    }
+
+    fn create_explicit_matrix(
+        weights: &[f64],
+        n: usize,
+        format: &str,
+    ) -> Result<Vec<f64>, Box<dyn Error>> {
+        // Column formats of a symmetric matrix list the same pairs as the opposite row format
+        let pairs: Vec<(usize, usize)> = match format {
+            "FULL_MATRIX" => (0..n).flat_map(|i| (0..n).map(move |j| (i, j))).collect(),
+            "UPPER_ROW" | "LOWER_COL" => (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .collect(),
+            "LOWER_ROW" | "UPPER_COL" => (0..n).flat_map(|i| (0..i).map(move |j| (i, j))).collect(),
+            "UPPER_DIAG_ROW" | "LOWER_DIAG_COL" => {
+                (0..n).flat_map(|i| (i..n).map(move |j| (i, j))).collect()
+            }
+            "LOWER_DIAG_ROW" | "UPPER_DIAG_COL" => {
+                (0..n).flat_map(|i| (0..=i).map(move |j| (i, j))).collect()
+            }
+            _ => return Err(format!("unsupported EDGE_WEIGHT_FORMAT '{}'", format).into()),
+        };
+
+        if weights.len() != pairs.len() {
+            return Err(format!(
+                "EDGE_WEIGHT_SECTION has {} values, expected {} for {} with DIMENSION {}",
+                weights.len(),
+                pairs.len(),
+                format,
+                n
+            )
+            .into());
+        }
+
+        let mut matrix = vec![0.0; n * n];
+        for (&(i, j), &weight) in pairs.iter().zip(weights) {
+            if i == j {
+                continue;
+            }
+            matrix[i * n + j] = weight;
+            if format != "FULL_MATRIX" {
+                matrix[j * n + i] = weight;
+            }
+        }
+
+        Ok(matrix)
+    }
+
+    pub fn write_tour(
+        path: &str,
+        name: &str,
+        comment: &str,
+        nodes: &[u32],
+    ) -> Result<(), Box<dyn Error>> {
+        let mut text = format!(
+            "NAME : {}\nCOMMENT : {}\nTYPE : TOUR\nDIMENSION : {}\nTOUR_SECTION\n",
+            name,
+            comment,
+            nodes.len()
+        );
+        for node in nodes {
+            text.push_str(&format!("{}\n", node));
+        }
+        text.push_str("-1\nEOF\n");
+        write(path, text)?;
+        Ok(())
+    }
+
+    // Node numbers of the TOUR_SECTION of a .tour file, up to the -1 that ends it
+    pub fn read_tour(path: &str) -> Result<Vec<u32>, Box<dyn Error>> {
+        let content = read_to_string(path)?;
+        let mut lines = content.lines().enumerate().peekable();
+        while let Some((_, line)) = lines.next() {
+            let key = line.split(':').next().unwrap_or_default().trim();
+            if key.eq_ignore_ascii_case("TOUR_SECTION") {
+                let numbers = Self::read_numbers(&mut lines)?;
+                return numbers
+                    .iter()
+                    .take_while(|&&number| number != -1.0)
+                    .map(|&number| {
+                        if number >= 1.0 && number.fract() == 0.0 && number <= u32::MAX as f64 {
+                            Ok(number as u32)
+                        } else {
+                            Err(format!("invalid node {} in TOUR_SECTION", number).into())
+                        }
+                    })
+                    .collect();
+            }
+        }
+        Err("no TOUR_SECTION".into())
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.name
+    }
+
+    pub fn get_edge_weight_type(&self) -> &EdgeWeightType {
+        &self.edge_weight_type
+    }
+
+    pub fn get_cities(&self) -> &Vec<City> {
+        &self.cities
+    }
+
+    pub fn get_distance_matrix(&self) -> &Vec<f64> {
+        &self.distance_matrix
+    }
+
+    pub fn is_symmetric(&self) -> bool {
+        self.symmetric
+    }
+
+    pub fn get_description(&self) -> String {
+        let symmetry = if self.symmetric {
+            "symmetric"
+        } else {
+            "asymmetric"
+        };
+        format!(
+            "TSPLIB {} ({}, {})",
+            self.name,
+            self.edge_weight_type.get_name(),
+            symmetry
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BURMA14: &str = "NAME: burma14
+TYPE: TSP
+DIMENSION: 14
+EDGE_WEIGHT_TYPE: GEO
+NODE_COORD_SECTION
+   1  16.47       96.10
+   2  16.47       94.44
+   3  20.09       92.54
+   4  22.39       93.37
+   5  25.23       97.24
+   6  22.00       96.05
+   7  20.47       97.02
+   8  17.20       96.29
+   9  16.30       97.38
+  10  14.05       98.12
+  11  16.53       97.38
+  12  21.52       95.59
+  13  19.41       97.13
+  14  20.09       94.55
+EOF
+";
+
+    const ATT48: [(f64, f64); 48] = [
+        (6734.0, 1453.0),
+        (2233.0, 10.0),
+        (5530.0, 1424.0),
+        (401.0, 841.0),
+        (3082.0, 1644.0),
+        (7608.0, 4458.0),
+        (7573.0, 3716.0),
+        (7265.0, 1268.0),
+        (6898.0, 1885.0),
+        (1112.0, 2049.0),
+        (5468.0, 2606.0),
+        (5989.0, 2873.0),
+        (4706.0, 2674.0),
+        (4612.0, 2035.0),
+        (6347.0, 2683.0),
+        (6107.0, 669.0),
+        (7611.0, 5184.0),
+        (7462.0, 3590.0),
+        (7732.0, 4723.0),
+        (5900.0, 3561.0),
+        (4483.0, 3369.0),
+        (6101.0, 1110.0),
+        (5199.0, 2182.0),
+        (1633.0, 2809.0),
+        (4307.0, 2322.0),
+        (675.0, 1006.0),
+        (7555.0, 4819.0),
+        (7541.0, 3981.0),
+        (3177.0, 756.0),
+        (7352.0, 4506.0),
+        (7545.0, 2801.0),
+        (3245.0, 3305.0),
+        (6426.0, 3173.0),
+        (4608.0, 1198.0),
+        (23.0, 2216.0),
+        (7248.0, 3779.0),
+        (7762.0, 4595.0),
+        (7392.0, 2244.0),
+        (3484.0, 2829.0),
+        (6271.0, 2135.0),
+        (4985.0, 140.0),
+        (1916.0, 1569.0),
+        (7280.0, 4899.0),
+        (7509.0, 3239.0),
+        (10.0, 2676.0),
+        (6807.0, 2993.0),
+        (5185.0, 3258.0),
+        (3023.0, 1942.0),
+    ];
+
+    // Length of the closed tour of node numbers
+    fn tour_length(matrix: &[f64], n: usize, tour: &[usize]) -> f64 {
+        (0..tour.len())
+            .map(|i| matrix[(tour[i] - 1) * n + tour[(i + 1) % tour.len()] - 1])
+            .sum()
+    }
+
+    #[test]
+    fn euclidean_rounds_to_nearest() {
+        let euc = EdgeWeightType::Euc2d;
+        assert_eq!(euc.calculate_distance((0.0, 0.0), (3.0, 4.0)), 5.0);
+        assert_eq!(euc.calculate_distance((0.0, 0.0), (1.0, 1.0)), 1.0);
+        // 2.5 rounds up
+        assert_eq!(euc.calculate_distance((0.0, 0.0), (1.5, 2.0)), 3.0);
+    }
+
+    #[test]
+    fn ceil_rounds_up() {
+        let ceil = EdgeWeightType::Ceil2d;
+        assert_eq!(ceil.calculate_distance((0.0, 0.0), (1.0, 1.0)), 2.0);
+        assert_eq!(ceil.calculate_distance((0.0, 0.0), (3.0, 4.0)), 5.0);
+    }
+
+    #[test]
+    fn att_pseudo_euclidean() {
+        let att = EdgeWeightType::Att;
+        // r = sqrt(10) = 3.16, nint 3 is below r so 4
+        assert_eq!(att.calculate_distance((0.0, 0.0), (10.0, 0.0)), 4.0);
+        // r = sqrt(160) = 12.65, nint 13
+        assert_eq!(att.calculate_distance((0.0, 0.0), (0.0, 40.0)), 13.0);
+    }
+
+    #[test]
+    fn att48_optimal_tour() {
+        let content = format!(
+            "NAME: att48\nTYPE: TSP\nDIMENSION: 48\nEDGE_WEIGHT_TYPE: ATT\nNODE_COORD_SECTION\n{}EOF\n",
+            ATT48
+                .iter()
+                .enumerate()
+                .map(|(i, (x, y))| format!("{} {} {}\n", i + 1, x, y))
+                .collect::<String>()
+        );
+        let instance = TsplibInstance::parse(&content, "att48.tsp").unwrap();
+        // att48.opt.tour, the published optimum is 10628
+        let tour = [
+            1, 8, 38, 31, 44, 18, 7, 28, 6, 37, 19, 27, 17, 43, 30, 36, 46, 33, 20, 47, 21, 32, 39,
+            48, 5, 42, 24, 10, 45, 35, 4, 26, 2, 29, 34, 41, 16, 22, 3, 23, 14, 25, 13, 11, 12, 15,
+            40, 9,
+        ];
+        assert_eq!(
+            tour_length(instance.get_distance_matrix(), 48, &tour),
+            10628.0
+        );
+    }
+
+    #[test]
+    fn geo_burma14() {
+        let instance = TsplibInstance::parse(BURMA14, "burma14.tsp").unwrap();
+        let matrix = instance.get_distance_matrix();
+        // first row of the published burma14 distances
+        let first_row = [
+            0.0, 153.0, 510.0, 706.0, 966.0, 581.0, 455.0, 70.0, 160.0, 372.0, 157.0, 567.0, 342.0,
+            398.0,
+        ];
+        assert_eq!(&matrix[..14], &first_row);
+        // the published optimum is 3323
+        let tour = [1, 2, 14, 3, 4, 5, 6, 12, 7, 13, 8, 11, 9, 10];
+        assert_eq!(tour_length(matrix, 14, &tour), 3323.0);
+        assert!(instance.is_symmetric());
+    }
+
+    #[test]
+    fn geo_truncates_degrees() {
+        // 95.59 is 95 degrees 59 minutes, not 96 degrees
+        let expected = GEO_PI * (95.0 + 5.0 * 0.59 / 3.0) / 180.0;
+        assert!((EdgeWeightType::geo_radians(95.59) - expected).abs() < 1e-12);
+        // a point is 1 from itself, the + 1.0 before the truncation
+        let geo = EdgeWeightType::Geo;
+        assert_eq!(geo.calculate_distance((16.47, 96.10), (16.47, 96.10)), 1.0);
+    }
+
+    #[test]
+    fn explicit_formats_fill_the_same_cells() {
+        #[rustfmt::skip]
+        let expected = vec![
+            0.0, 1.0, 2.0, 3.0,
+            1.0, 0.0, 4.0, 5.0,
+            2.0, 4.0, 0.0, 6.0,
+            3.0, 5.0, 6.0, 0.0,
+        ];
+        // the upper triangle by rows lists the pairs in the order of the lower one by columns
+        let upper = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let lower = vec![1.0, 2.0, 4.0, 3.0, 5.0, 6.0];
+        let upper_diagonal = vec![0.0, 1.0, 2.0, 3.0, 0.0, 4.0, 5.0, 0.0, 6.0, 0.0];
+        let lower_diagonal = vec![0.0, 1.0, 0.0, 2.0, 4.0, 0.0, 3.0, 5.0, 6.0, 0.0];
+        let formats = [
+            ("FULL_MATRIX", &expected),
+            ("UPPER_ROW", &upper),
+            ("LOWER_COL", &upper),
+            ("LOWER_ROW", &lower),
+            ("UPPER_COL", &lower),
+            ("UPPER_DIAG_ROW", &upper_diagonal),
+            ("LOWER_DIAG_COL", &upper_diagonal),
+            ("LOWER_DIAG_ROW", &lower_diagonal),
+            ("UPPER_DIAG_COL", &lower_diagonal),
+        ];
+        for (format, weights) in formats {
+            let matrix = TsplibInstance::create_explicit_matrix(weights, 4, format).unwrap();
+            assert_eq!(matrix, expected, "{}", format);
+        }
+    }
+
+    #[test]
+    fn explicit_wrong_weights() {
+        assert!(TsplibInstance::create_explicit_matrix(&[1.0; 5], 4, "UPPER_ROW").is_err());
+        assert!(TsplibInstance::create_explicit_matrix(&[1.0; 6], 4, "UPPER_RAW").is_err());
+    }
+
+    #[test]
+    fn atsp_full_matrix_is_not_mirrored() {
+        let content = "NAME: tiny\nTYPE: ATSP\nDIMENSION: 3\nEDGE_WEIGHT_TYPE: EXPLICIT\n\
+            EDGE_WEIGHT_FORMAT: FULL_MATRIX\nEDGE_WEIGHT_SECTION\n\
+            9999 1 2\n3 9999 4\n5 6 9999\nEOF\n";
+        let instance = TsplibInstance::parse(content, "tiny.atsp").unwrap();
+        // the diagonal is 0 whatever the file says
+        assert_eq!(
+            instance.get_distance_matrix(),
+            &vec![0.0, 1.0, 2.0, 3.0, 0.0, 4.0, 5.0, 6.0, 0.0]
+        );
+        assert!(!instance.is_symmetric());
+    }
+}
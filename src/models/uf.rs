@@ -1,3 +1,4 @@
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum UFEnum {
     AC,
@@ -42,6 +43,10 @@ impl UF {
         &self.uf
     }
 
+    pub fn get_name(&self) -> String {
+        format!("{:?}", self.uf)
+    }
+
     pub fn get_min_max_latitude(&self) -> &(f32, f32) {
         &self.min_max_latitude
     }
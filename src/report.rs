@@ -0,0 +1,704 @@
+use std::{collections::HashMap, error::Error, fs::File};
+
+use csv::Reader;
+use serde::Deserialize;
+
+use crate::archive::ArchivedRun;
+use crate::statistics::{
+    FriedmanResult, TestResult, friedman, mann_whitney, median, nemenyi_critical_difference,
+    same_value, standard_deviation, wilcoxon_signed_rank,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableFormat {
+    Markdown,
+    Latex,
+}
+
+impl TableFormat {
+    pub fn get_format_from_str(str: &str) -> Option<Self> {
+        match str.to_lowercase().as_str() {
+            "markdown" | "md" => Some(Self::Markdown),
+            "latex" | "tex" => Some(Self::Latex),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BestKnownRow {
+    instance: String,
+    distance: f64,
+}
+
+// "instance,distance" rows, e.g. the published optima of the TSPLIB instances
+pub fn read_best_known(path: &str) -> Result<HashMap<String, f64>, Box<dyn Error>> {
+    let mut reader = Reader::from_reader(File::open(path)?);
+    let mut best_known = HashMap::new();
+    for row in reader.deserialize() {
+        let row: BestKnownRow = row.map_err(|err| format!("{}: {}", path, err))?;
+        best_known.insert(row.instance.to_uppercase(), row.distance);
+    }
+    Ok(best_known)
+}
+
+// The runs of one algorithm (and parameter set) on one instance
+struct CompetitorStatistics {
+    competitor: String,
+    distances: Vec<f64>,
+    best: f64,
+    mean: f64,
+    std_dev: f64,
+    median: f64,
+    worst: f64,
+    // relative percentage deviation from the best-known distance
+    best_rpd: f64,
+    mean_rpd: f64,
+    mean_time: f64,
+}
+
+struct InstanceReport {
+    name: String,
+    best_known: f64,
+    // given in the best-known file, or the best run of the archive
+    best_known_given: bool,
+    rows: Vec<CompetitorStatistics>,
+    mann_whitney: Vec<PairComparison>,
+}
+
+struct PairComparison {
+    first: String,
+    second: String,
+    samples: String,
+    result: TestResult,
+    // '+': the first is significantly better, '-': worse, '=': no significant difference
+    outcome: char,
+}
+
+struct FriedmanReport {
+    blocks: usize,
+    result: FriedmanResult,
+    critical_difference: Option<f64>,
+}
+
+// Statistics of every algorithm on every instance and the tests between them, lower
+// distances being better. Each algorithm is one competitor, or each algorithm and parameter
+// set when the runs have more than one set.
+pub struct Report {
+    alpha: f64,
+    competitors: Vec<String>,
+    instances: Vec<InstanceReport>,
+    wilcoxon: Vec<PairComparison>,
+    friedman: Option<FriedmanReport>,
+    notes: Vec<String>,
+}
+
+impl Report {
+    // `best_known` by uppercase instance name; instances missing from it use their best run
+    pub fn new(
+        runs: &[&ArchivedRun],
+        best_known: &HashMap<String, f64>,
+        alpha: f64,
+    ) -> Result<Self, String> {
+        if !(alpha > 0.0 && alpha < 1.0) {
+            return Err(format!("alpha {} must be between 0 and 1", alpha));
+        }
+        let mut parameter_sets: Vec<&String> = runs
+            .iter()
+            .map(|run| run.get_record().get_parameter_set())
+            .collect();
+        parameter_sets.sort();
+        parameter_sets.dedup();
+        let label = |run: &ArchivedRun| {
+            let record = run.get_record();
+            if parameter_sets.len() > 1 {
+                format!(
+                    "{} ({})",
+                    record.get_algorithm(),
+                    record.get_parameter_set()
+                )
+            } else {
+                record.get_algorithm().clone()
+            }
+        };
+
+        let mut competitors: Vec<String> = runs.iter().map(|run| label(run)).collect();
+        competitors.sort();
+        competitors.dedup();
+        if competitors.len() < 2 {
+            return Err(format!(
+                "{} algorithm(s) in the runs, the report compares at least 2",
+                competitors.len()
+            ));
+        }
+        let mut instance_names: Vec<&String> = runs
+            .iter()
+            .map(|run| run.get_record().get_instance())
+            .collect();
+        instance_names.sort();
+        instance_names.dedup();
+
+        let instances: Vec<InstanceReport> = instance_names
+            .into_iter()
+            .map(|name| {
+                let instance_runs: Vec<&&ArchivedRun> = runs
+                    .iter()
+                    .filter(|run| run.get_record().get_instance() == name)
+                    .collect();
+                let given = best_known.get(&name.to_uppercase()).copied();
+                let best_known = given.unwrap_or_else(|| {
+                    instance_runs
+                        .iter()
+                        .map(|run| run.get_record().get_distance())
+                        .fold(f64::MAX, f64::min)
+                });
+                let rows: Vec<CompetitorStatistics> = competitors
+                    .iter()
+                    .filter_map(|competitor| {
+                        let competitor_runs: Vec<&&&ArchivedRun> = instance_runs
+                            .iter()
+                            .filter(|run| &label(run) == competitor)
+                            .collect();
+                        if competitor_runs.is_empty() {
+                            return None;
+                        }
+                        let distances: Vec<f64> = competitor_runs
+                            .iter()
+                            .map(|run| run.get_record().get_distance())
+                            .collect();
+                        let times: Vec<f64> = competitor_runs
+                            .iter()
+                            .map(|run| run.get_record().get_timings().algorithm)
+                            .collect();
+                        Some(CompetitorStatistics::new(
+                            competitor, distances, &times, best_known,
+                        ))
+                    })
+                    .collect();
+                let mann_whitney = pairs(&rows)
+                    .into_iter()
+                    .map(|(first, second)| {
+                        let result = mann_whitney(&first.distances, &second.distances);
+                        // the medians decide, the means break their ties
+                        let first_better = if same_value(first.median, second.median) {
+                            first.mean < second.mean
+                        } else {
+                            first.median < second.median
+                        };
+                        let outcome = outcome(&result, alpha, first_better);
+                        PairComparison {
+                            first: first.competitor.clone(),
+                            second: second.competitor.clone(),
+                            samples: format!(
+                                "{}/{}",
+                                first.distances.len(),
+                                second.distances.len()
+                            ),
+                            result,
+                            outcome,
+                        }
+                    })
+                    .collect();
+                InstanceReport {
+                    name: name.clone(),
+                    best_known,
+                    best_known_given: given.is_some(),
+                    rows,
+                    mann_whitney,
+                }
+            })
+            .collect();
+
+        let mut notes = vec![];
+        // Wilcoxon on the mean RPD of the instances both algorithms ran
+        let mut wilcoxon = vec![];
+        for (i, first) in competitors.iter().enumerate() {
+            for second in &competitors[i + 1..] {
+                let (a, b): (Vec<f64>, Vec<f64>) = instances
+                    .iter()
+                    .filter_map(|instance| {
+                        Some((
+                            instance.get_row(first)?.mean_rpd,
+                            instance.get_row(second)?.mean_rpd,
+                        ))
+                    })
+                    .unzip();
+                if a.is_empty() {
+                    continue;
+                }
+                let result = wilcoxon_signed_rank(&a, &b);
+                // W+ counts the instances where the first is worse
+                let pairs_sum = (a.len() * (a.len() + 1)) as f64 / 2.0;
+                let first_better = result.statistic < pairs_sum - result.statistic;
+                let outcome = outcome(&result, alpha, first_better);
+                wilcoxon.push(PairComparison {
+                    first: first.clone(),
+                    second: second.clone(),
+                    samples: a.len().to_string(),
+                    result,
+                    outcome,
+                });
+            }
+        }
+
+        // Friedman on the instances every algorithm ran
+        let complete: Vec<&InstanceReport> = instances
+            .iter()
+            .filter(|instance| instance.rows.len() == competitors.len())
+            .collect();
+        if complete.len() < instances.len() {
+            notes.push(format!(
+                "Friedman and Nemenyi use the {} of the {} instances that every algorithm ran",
+                complete.len(),
+                instances.len()
+            ));
+        }
+        let friedman = if complete.len() >= 2 {
+            let blocks: Vec<Vec<f64>> = complete
+                .iter()
+                .map(|instance| instance.rows.iter().map(|row| row.mean_rpd).collect())
+                .collect();
+            let critical_difference =
+                nemenyi_critical_difference(alpha, competitors.len(), blocks.len());
+            if critical_difference.is_none() {
+                notes.push(
+                    "Nemenyi critical values are tabulated for alpha 0.05 and 0.10 and up to 10 algorithms"
+                        .to_string(),
+                );
+            }
+            Some(FriedmanReport {
+                blocks: blocks.len(),
+                result: friedman(&blocks),
+                critical_difference,
+            })
+        } else {
+            notes.push("Friedman needs at least 2 instances that every algorithm ran".to_string());
+            None
+        };
+
+        Ok(Report {
+            alpha,
+            competitors,
+            instances,
+            wilcoxon,
+            friedman,
+            notes,
+        })
+    }
+
+    pub fn render(&self, format: TableFormat) -> String {
+        let mut text = String::new();
+        let mut notes = vec![
+            format!(
+                "Significance level {}; + the first algorithm is significantly better, - worse, = no significant difference",
+                self.alpha
+            ),
+            "RPD = 100 × (distance - best-known) / best-known".to_string(),
+        ];
+        notes.extend(self.notes.iter().cloned());
+        match format {
+            TableFormat::Markdown => {
+                text.push_str("# Comparison of the algorithms\n\n");
+                for note in &notes {
+                    text.push_str(&format!("- {}\n", note));
+                }
+                text.push('\n');
+            }
+            TableFormat::Latex => {
+                text.push_str("% Comparison of the algorithms, needs \\usepackage{booktabs}\n");
+                for note in &notes {
+                    text.push_str(&format!("% {}\n", note));
+                }
+                text.push('\n');
+            }
+        }
+
+        text.push_str(&render_table(
+            format,
+            "Best-known distances",
+            &["Instance", "Best-known", "Source"],
+            "lrl",
+            self.instances
+                .iter()
+                .map(|instance| {
+                    vec![
+                        instance.name.clone(),
+                        format!("{:.4}", instance.best_known),
+                        if instance.best_known_given {
+                            "given".to_string()
+                        } else {
+                            "best run".to_string()
+                        },
+                    ]
+                })
+                .collect(),
+        ));
+
+        let mut rows = vec![];
+        for instance in &self.instances {
+            let best_mean = instance
+                .rows
+                .iter()
+                .map(|row| row.mean)
+                .fold(f64::MAX, f64::min);
+            for row in &instance.rows {
+                let mean = format!("{:.4}", row.mean);
+                rows.push(vec![
+                    instance.name.clone(),
+                    row.competitor.clone(),
+                    row.distances.len().to_string(),
+                    format!("{:.4}", row.best),
+                    if same_value(row.mean, best_mean) {
+                        bold(format, &mean)
+                    } else {
+                        mean
+                    },
+                    format!("{:.4}", row.std_dev),
+                    format!("{:.4}", row.median),
+                    format!("{:.4}", row.worst),
+                    format!("{:.2}", row.best_rpd),
+                    format!("{:.2}", row.mean_rpd),
+                    format!("{:.3}", row.mean_time),
+                ]);
+            }
+        }
+        text.push_str(&render_table(
+            format,
+            "Distances per instance (best mean in bold)",
+            &[
+                "Instance",
+                "Algorithm",
+                "Runs",
+                "Best",
+                "Mean",
+                "Std",
+                "Median",
+                "Worst",
+                "RPD best (%)",
+                "RPD mean (%)",
+                "Time (s)",
+            ],
+            "llrrrrrrrrr",
+            rows,
+        ));
+
+        let rows = self
+            .instances
+            .iter()
+            .flat_map(|instance| {
+                instance.mann_whitney.iter().map(|comparison| {
+                    let mut row = vec![instance.name.clone()];
+                    row.extend(comparison.describe());
+                    row
+                })
+            })
+            .collect();
+        text.push_str(&render_table(
+            format,
+            "Mann-Whitney U test per instance",
+            &[
+                "Instance",
+                "Comparison",
+                "Runs",
+                "U",
+                "p",
+                "Exact",
+                "Result",
+            ],
+            "llrrrcc",
+            rows,
+        ));
+
+        text.push_str(&render_table(
+            format,
+            "Wilcoxon signed-rank test over the instances (mean RPD)",
+            &["Comparison", "Instances", "W+", "p", "Exact", "Result"],
+            "lrrrcc",
+            self.wilcoxon
+                .iter()
+                .map(|comparison| comparison.describe())
+                .collect(),
+        ));
+
+        if let Some(friedman) = &self.friedman {
+            let result = &friedman.result;
+            text.push_str(&render_table(
+                format,
+                &format!(
+                    "Friedman test over {} instances (mean RPD): chi-square = {:.4}, df = {}, p = {}",
+                    friedman.blocks,
+                    result.chi_square,
+                    result.degrees_of_freedom,
+                    describe_p_value(result.p_value)
+                ),
+                &["Algorithm", "Average rank"],
+                "lr",
+                self.competitors
+                    .iter()
+                    .zip(&result.average_ranks)
+                    .map(|(competitor, rank)| vec![competitor.clone(), format!("{:.3}", rank)])
+                    .collect(),
+            ));
+
+            if let Some(critical_difference) = friedman.critical_difference {
+                let mut rows = vec![];
+                for i in 0..self.competitors.len() {
+                    for j in i + 1..self.competitors.len() {
+                        let difference = result.average_ranks[i] - result.average_ranks[j];
+                        let outcome = if difference.abs() <= critical_difference {
+                            '='
+                        } else if difference < 0.0 {
+                            '+'
+                        } else {
+                            '-'
+                        };
+                        rows.push(vec![
+                            format!("{} vs {}", self.competitors[i], self.competitors[j]),
+                            format!("{:.3}", difference.abs()),
+                            outcome.to_string(),
+                        ]);
+                    }
+                }
+                text.push_str(&render_table(
+                    format,
+                    &format!(
+                        "Nemenyi post-hoc test: critical difference = {:.3}",
+                        critical_difference
+                    ),
+                    &["Comparison", "Rank difference", "Result"],
+                    "lrc",
+                    rows,
+                ));
+            }
+        }
+        text
+    }
+}
+
+impl CompetitorStatistics {
+    fn new(competitor: &str, distances: Vec<f64>, times: &[f64], best_known: f64) -> Self {
+        let count = distances.len() as f64;
+        let mean = distances.iter().sum::<f64>() / count;
+        let best = distances.iter().copied().fold(f64::MAX, f64::min);
+        let rpd = |distance: f64| {
+            if best_known > 0.0 {
+                (distance - best_known) / best_known * 100.0
+            } else {
+                0.0
+            }
+        };
+        CompetitorStatistics {
+            competitor: competitor.to_string(),
+            best,
+            mean,
+            std_dev: standard_deviation(&distances),
+            median: median(&distances),
+            worst: distances.iter().copied().fold(f64::MIN, f64::max),
+            best_rpd: rpd(best),
+            mean_rpd: distances.iter().map(|&d| rpd(d)).sum::<f64>() / count,
+            mean_time: times.iter().sum::<f64>() / count,
+            distances,
+        }
+    }
+}
+
+impl InstanceReport {
+    fn get_row(&self, competitor: &str) -> Option<&CompetitorStatistics> {
+        self.rows.iter().find(|row| row.competitor == competitor)
+    }
+}
+
+impl PairComparison {
+    fn describe(&self) -> Vec<String> {
+        vec![
+            format!("{} vs {}", self.first, self.second),
+            self.samples.clone(),
+            format!("{}", self.result.statistic),
+            describe_p_value(self.result.p_value),
+            if self.result.approximate { "no" } else { "yes" }.to_string(),
+            self.outcome.to_string(),
+        ]
+    }
+}
+
+fn pairs<T>(items: &[T]) -> Vec<(&T, &T)> {
+    let mut pairs = vec![];
+    for (i, first) in items.iter().enumerate() {
+        for second in &items[i + 1..] {
+            pairs.push((first, second));
+        }
+    }
+    pairs
+}
+
+fn outcome(result: &TestResult, alpha: f64, first_better: bool) -> char {
+    if result.p_value >= alpha {
+        '='
+    } else if first_better {
+        '+'
+    } else {
+        '-'
+    }
+}
+
+fn describe_p_value(p_value: f64) -> String {
+    if p_value < 1e-4 {
+        "< 0.0001".to_string()
+    } else {
+        format!("{:.4}", p_value)
+    }
+}
+
+fn bold(format: TableFormat, text: &str) -> String {
+    match format {
+        TableFormat::Markdown => format!("**{}**", text),
+        TableFormat::Latex => format!("\\textbf{{{}}}", text),
+    }
+}
+
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '_' | '%' | '&' | '#' | '$' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '<' => escaped.push_str("$<$"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// `alignments`: one of l, c or r per column
+fn render_table(
+    format: TableFormat,
+    caption: &str,
+    headers: &[&str],
+    alignments: &str,
+    rows: Vec<Vec<String>>,
+) -> String {
+    let mut text = String::new();
+    match format {
+        TableFormat::Markdown => {
+            text.push_str(&format!("## {}\n\n", caption));
+            if rows.is_empty() {
+                text.push_str("No comparisons.\n\n");
+                return text;
+            }
+            text.push_str(&format!("| {} |\n|", headers.join(" | ")));
+            for alignment in alignments.chars() {
+                text.push_str(match alignment {
+                    'r' => "---:|",
+                    'c' => ":---:|",
+                    _ => "---|",
+                });
+            }
+            text.push('\n');
+            for row in rows {
+                text.push_str(&format!("| {} |\n", row.join(" | ")));
+            }
+        }
+        TableFormat::Latex => {
+            if rows.is_empty() {
+                return text;
+            }
+            text.push_str("\\begin{table}[htbp]\n\\centering\n");
+            text.push_str(&format!("\\caption{{{}}}\n", escape_latex(caption)));
+            text.push_str(&format!(
+                "\\begin{{tabular}}{{{}}}\n\\toprule\n",
+                alignments
+            ));
+            let headers: Vec<String> = headers.iter().map(|h| escape_latex(h)).collect();
+            text.push_str(&format!("{} \\\\\n\\midrule\n", headers.join(" & ")));
+            for row in rows {
+                // bold cells are already LaTeX
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|cell| {
+                        if cell.starts_with("\\textbf") {
+                            cell.clone()
+                        } else {
+                            escape_latex(cell)
+                        }
+                    })
+                    .collect();
+                text.push_str(&format!("{} \\\\\n", cells.join(" & ")));
+            }
+            text.push_str("\\bottomrule\n\\end{tabular}\n\\end{table}\n");
+        }
+    }
+    text.push('\n');
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::models::run_record::RunRecord;
+
+    // One run of each algorithm on every instance, `first` and `second` the distances
+    fn report(first: &[f64], second: &[f64]) -> Report {
+        let mut runs = vec![];
+        for (i, (&a, &b)) in first.iter().zip(second).enumerate() {
+            let instance = format!("I{}", i);
+            for (algorithm, distance) in [("ACO", a), ("SA", b)] {
+                let record = RunRecord::new(algorithm, &instance, distance, vec![]);
+                runs.push(ArchivedRun::new(PathBuf::new(), record));
+            }
+        }
+        let runs: Vec<&ArchivedRun> = runs.iter().collect();
+        Report::new(&runs, &HashMap::new(), 0.05).unwrap()
+    }
+
+    #[test]
+    fn wilcoxon_outcome_follows_the_shorter_tours() {
+        let best = [100.0; 6];
+        let worse = [101.0, 102.0, 103.0, 104.0, 105.0, 106.0];
+
+        // ACO is the first competitor, never worse: W+ = 0, exact p = 2 / 2^6
+        let report = self::report(&best, &worse);
+        let comparison = &report.wilcoxon[0];
+        assert_eq!(
+            (comparison.first.as_str(), comparison.second.as_str()),
+            ("ACO", "SA")
+        );
+        assert_eq!(comparison.result.statistic, 0.0);
+        assert!((comparison.result.p_value - 0.03125).abs() < 1e-12);
+        assert_eq!(comparison.outcome, '+');
+
+        // worse on every instance: W+ = 21, the largest rank sum
+        let report = self::report(&worse, &best);
+        let comparison = &report.wilcoxon[0];
+        assert_eq!(comparison.result.statistic, 21.0);
+        assert_eq!(comparison.outcome, '-');
+    }
+
+    #[test]
+    fn mann_whitney_outcome_follows_the_medians() {
+        let mut runs = vec![];
+        for i in 0..6 {
+            for (algorithm, distance) in [("ACO", 100.0 + i as f64), ("SA", 200.0 + i as f64)] {
+                let record = RunRecord::new(algorithm, "I", distance, vec![]);
+                runs.push(ArchivedRun::new(PathBuf::new(), record));
+            }
+        }
+        let runs: Vec<&ArchivedRun> = runs.iter().collect();
+        let report = Report::new(&runs, &HashMap::new(), 0.05).unwrap();
+        let comparison = &report.instances[0].mann_whitney[0];
+        assert_eq!(comparison.result.statistic, 0.0);
+        assert_eq!(comparison.outcome, '+');
+        // a single instance is not enough for Friedman
+        assert!(report.friedman.is_none());
+    }
+
+    #[test]
+    fn single_competitor_is_refused() {
+        let record = RunRecord::new("SA", "I", 1.0, vec![]);
+        let run = ArchivedRun::new(PathBuf::new(), record);
+        assert!(Report::new(&[&run], &HashMap::new(), 0.05).is_err());
+    }
+}
@@ -0,0 +1,536 @@
+// Nonparametric tests used to compare the algorithms. Small samples without ties get exact
+// p-values, the others the normal (or chi-square) approximation with the tie correction.
+
+// Samples up to this size are counted exactly, the counts stay exact in an f64
+const EXACT_SIGNED_RANK_MAX: usize = 50;
+const EXACT_RANK_SUM_MAX: usize = 25;
+
+// Distances closer than this (relative) are the same
+pub fn same_value(a: f64, b: f64) -> bool {
+    (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+}
+
+// 1-based ranks, tied values share the mean of their ranks
+pub fn average_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && same_value(values[order[end]], values[order[start]]) {
+            end += 1;
+        }
+        // positions start..end hold ranks start + 1..=end
+        let rank = (start + 1 + end) as f64 / 2.0;
+        for &index in &order[start..end] {
+            ranks[index] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+// Σ(t³ - t) over the groups of tied values
+fn tie_correction(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mut correction = 0.0;
+    let mut start = 0;
+    while start < sorted.len() {
+        let mut end = start + 1;
+        while end < sorted.len() && same_value(sorted[end], sorted[start]) {
+            end += 1;
+        }
+        let ties = (end - start) as f64;
+        correction += ties * ties * ties - ties;
+        start = end;
+    }
+    correction
+}
+
+#[derive(Debug, Clone)]
+pub struct TestResult {
+    pub statistic: f64,
+    pub p_value: f64,
+    // normal approximation instead of the exact distribution
+    pub approximate: bool,
+}
+
+// Wilcoxon signed-rank test of the paired samples, two-sided. The statistic is W+, the rank
+// sum of the pairs where a is larger; pairs with no difference are dropped.
+pub fn wilcoxon_signed_rank(a: &[f64], b: &[f64]) -> TestResult {
+    let differences: Vec<f64> = a
+        .iter()
+        .zip(b)
+        .filter(|&(&x, &y)| !same_value(x, y))
+        .map(|(x, y)| x - y)
+        .collect();
+    let n = differences.len();
+    if n == 0 {
+        return TestResult {
+            statistic: 0.0,
+            p_value: 1.0,
+            approximate: false,
+        };
+    }
+    let magnitudes: Vec<f64> = differences.iter().map(|d| d.abs()).collect();
+    let ranks = average_ranks(&magnitudes);
+    let positive = ranks
+        .iter()
+        .zip(&differences)
+        .filter(|&(_, &d)| d > 0.0)
+        .fold(0.0, |sum, (rank, _)| sum + rank);
+    let total = (n * (n + 1)) as f64 / 2.0;
+    let smaller = positive.min(total - positive);
+    let ties = tie_correction(&magnitudes);
+
+    if ties == 0.0 && n <= EXACT_SIGNED_RANK_MAX {
+        // counts[s]: subsets of the ranks 1..=n summing to s
+        let max_sum = n * (n + 1) / 2;
+        let mut counts = vec![0.0; max_sum + 1];
+        counts[0] = 1.0;
+        for rank in 1..=n {
+            for sum in (rank..=max_sum).rev() {
+                counts[sum] += counts[sum - rank];
+            }
+        }
+        let at_most: f64 = counts[..=smaller as usize].iter().sum();
+        return TestResult {
+            statistic: positive,
+            p_value: (2.0 * at_most / 2f64.powi(n as i32)).min(1.0),
+            approximate: false,
+        };
+    }
+
+    let n = n as f64;
+    let mean = n * (n + 1.0) / 4.0;
+    let variance = n * (n + 1.0) * (2.0 * n + 1.0) / 24.0 - ties / 48.0;
+    TestResult {
+        statistic: positive,
+        p_value: two_sided_normal(smaller, mean, variance),
+        approximate: true,
+    }
+}
+
+// Mann-Whitney U test of the independent samples, two-sided. The statistic is the U of a,
+// the pairs (x, y) with x larger than y, ties counting one half.
+pub fn mann_whitney(a: &[f64], b: &[f64]) -> TestResult {
+    let (n1, n2) = (a.len(), b.len());
+    if n1 == 0 || n2 == 0 {
+        return TestResult {
+            statistic: 0.0,
+            p_value: 1.0,
+            approximate: false,
+        };
+    }
+    let combined: Vec<f64> = a.iter().chain(b).copied().collect();
+    let ranks = average_ranks(&combined);
+    let rank_sum: f64 = ranks[..n1].iter().sum();
+    let u = rank_sum - (n1 * (n1 + 1)) as f64 / 2.0;
+    let pairs = (n1 * n2) as f64;
+    let smaller = u.min(pairs - u);
+    let ties = tie_correction(&combined);
+
+    if ties == 0.0 && n1 <= EXACT_RANK_SUM_MAX && n2 <= EXACT_RANK_SUM_MAX {
+        // coefficients of the Gaussian binomial [n1 + n2, n1]: counts[u] orderings with U = u
+        let max_u = n1 * n2;
+        let mut counts = vec![0.0; max_u + 1];
+        counts[0] = 1.0;
+        for i in 1..=n1 {
+            // × (1 - q^(n2 + i))
+            for k in (n2 + i..=max_u).rev() {
+                counts[k] -= counts[k - n2 - i];
+            }
+            // ÷ (1 - q^i)
+            for k in i..=max_u {
+                counts[k] += counts[k - i];
+            }
+        }
+        let total: f64 = counts.iter().sum();
+        let at_most: f64 = counts[..=smaller as usize].iter().sum();
+        return TestResult {
+            statistic: u,
+            p_value: (2.0 * at_most / total).min(1.0),
+            approximate: false,
+        };
+    }
+
+    let n = (n1 + n2) as f64;
+    let mean = pairs / 2.0;
+    let variance = pairs / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+    TestResult {
+        statistic: u,
+        p_value: two_sided_normal(smaller, mean, variance),
+        approximate: true,
+    }
+}
+
+// p of a statistic at or below its mean, with the continuity correction
+fn two_sided_normal(smaller: f64, mean: f64, variance: f64) -> f64 {
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    let z = (smaller - mean + 0.5).min(0.0) / variance.sqrt();
+    (2.0 * normal_cdf(z)).min(1.0)
+}
+
+#[derive(Debug, Clone)]
+pub struct FriedmanResult {
+    // mean rank of every treatment, 1 the best
+    pub average_ranks: Vec<f64>,
+    pub chi_square: f64,
+    pub degrees_of_freedom: usize,
+    pub p_value: f64,
+}
+
+// Friedman test of k treatments over N blocks, blocks[i][j] the value of treatment j in
+// block i, lower is better
+pub fn friedman(blocks: &[Vec<f64>]) -> FriedmanResult {
+    let n = blocks.len() as f64;
+    let k = blocks.first().map_or(0, |block| block.len());
+    let mut average_ranks = vec![0.0; k];
+    for block in blocks {
+        for (j, rank) in self::average_ranks(block).into_iter().enumerate() {
+            average_ranks[j] += rank / n;
+        }
+    }
+    let kf = k as f64;
+    let chi_square = 12.0 * n / (kf * (kf + 1.0))
+        * (average_ranks.iter().map(|r| r * r).sum::<f64>() - kf * (kf + 1.0).powi(2) / 4.0);
+    let degrees_of_freedom = k.saturating_sub(1);
+    FriedmanResult {
+        average_ranks,
+        chi_square,
+        degrees_of_freedom,
+        p_value: chi_square_survival(chi_square.max(0.0), degrees_of_freedom as f64),
+    }
+}
+
+// Critical values q_α of the Nemenyi test for k = 2..=10 treatments (Demšar, 2006)
+const NEMENYI_Q_005: [f64; 9] = [
+    1.960, 2.343, 2.569, 2.728, 2.850, 2.949, 3.031, 3.102, 3.164,
+];
+const NEMENYI_Q_010: [f64; 9] = [
+    1.645, 2.052, 2.291, 2.459, 2.589, 2.693, 2.780, 2.855, 2.920,
+];
+
+// Critical difference of the average ranks of k treatments over N blocks, none when α is
+// not 0.05 or 0.10 or k is out of the table
+pub fn nemenyi_critical_difference(alpha: f64, k: usize, blocks: usize) -> Option<f64> {
+    let table = if same_value(alpha, 0.05) {
+        &NEMENYI_Q_005
+    } else if same_value(alpha, 0.10) {
+        &NEMENYI_Q_010
+    } else {
+        return None;
+    };
+    let q = table.get(k.checked_sub(2)?)?;
+    let k = k as f64;
+    Some(q * (k * (k + 1.0) / (6.0 * blocks as f64)).sqrt())
+}
+
+pub fn median(values: &[f64]) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let middle = sorted.len() / 2;
+    if sorted.is_empty() {
+        0.0
+    } else if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+// Sample standard deviation, 0 for a single value
+pub fn standard_deviation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let squares: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    (squares / (values.len() - 1) as f64).sqrt()
+}
+
+pub fn normal_cdf(z: f64) -> f64 {
+    0.5 * erfc(-z / std::f64::consts::SQRT_2)
+}
+
+// Complementary error function, Chebyshev fit with relative error below 1.2e-7
+// (Numerical Recipes, erfcc)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let value = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0.0 { value } else { 2.0 - value }
+}
+
+// P(X > x) of the chi-square distribution
+pub fn chi_square_survival(x: f64, degrees_of_freedom: f64) -> f64 {
+    if degrees_of_freedom <= 0.0 {
+        return 1.0;
+    }
+    regularized_gamma_q(degrees_of_freedom / 2.0, x / 2.0)
+}
+
+// Q(a, x) = Γ(a, x) / Γ(a): series below a + 1, continued fraction above
+fn regularized_gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefactor = -x + a * x.ln() - ln_gamma(a);
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut denominator = a;
+        for _ in 0..1000 {
+            denominator += 1.0;
+            term *= x / denominator;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-15 {
+                break;
+            }
+        }
+        (1.0 - sum * log_prefactor.exp()).clamp(0.0, 1.0)
+    } else {
+        // modified Lentz
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut fraction = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            fraction *= delta;
+            if (delta - 1.0).abs() < 1e-15 {
+                break;
+            }
+        }
+        (log_prefactor.exp() * fraction).clamp(0.0, 1.0)
+    }
+}
+
+// Lanczos approximation, g = 7
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // reflection
+        return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let mut sum = COEFFICIENTS[0];
+    for (i, &coefficient) in COEFFICIENTS.iter().enumerate().skip(1) {
+        sum += coefficient / (x + i as f64);
+    }
+    let t = x + 7.5;
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn average_ranks_share_ties() {
+        assert_eq!(
+            average_ranks(&[3.0, 1.0, 3.0, 2.0, 3.0]),
+            vec![4.0, 1.0, 4.0, 2.0, 4.0]
+        );
+        assert_eq!(tie_correction(&[3.0, 1.0, 3.0, 2.0, 3.0]), 24.0);
+    }
+
+    // 3.14 is a measurement of the example, not π
+    #[allow(clippy::approx_constant)]
+    #[test]
+    fn wilcoxon_exact() {
+        // R, ?wilcox.test (Hollander & Wolfe): V = 40, p-value = 0.03906
+        let x = [1.83, 0.50, 1.62, 2.48, 1.68, 1.88, 1.55, 3.06, 1.30];
+        let y = [0.878, 0.647, 0.598, 2.05, 1.06, 1.29, 1.06, 3.14, 1.29];
+        let result = wilcoxon_signed_rank(&x, &y);
+        assert!(!result.approximate);
+        assert_eq!(result.statistic, 40.0);
+        assert_close(result.p_value, 20.0 / 512.0, 1e-12);
+        // the same p the other way round, W+ becomes the rank sum of the negative side
+        let reversed = wilcoxon_signed_rank(&y, &x);
+        assert_eq!(reversed.statistic, 5.0);
+        assert_close(reversed.p_value, result.p_value, 1e-12);
+    }
+
+    #[test]
+    fn wilcoxon_ties_and_zeros() {
+        // one zero difference dropped, tied magnitudes: normal approximation with the
+        // continuity and tie corrections, R wilcox.test(paired = TRUE, exact = FALSE)
+        let a = [10.0, 12.0, 15.0, 11.0, 14.0, 9.0, 13.0, 16.0, 12.0, 10.0];
+        let b = [9.0, 10.0, 13.0, 12.0, 11.0, 9.0, 10.0, 12.0, 10.0, 7.0];
+        let result = wilcoxon_signed_rank(&a, &b);
+        assert!(result.approximate);
+        assert_eq!(result.statistic, 43.5);
+        assert_close(result.p_value, 0.014363814855532286, 1e-6);
+    }
+
+    #[test]
+    fn wilcoxon_without_differences() {
+        let result = wilcoxon_signed_rank(&[1.0, 2.0], &[1.0, 2.0]);
+        assert_eq!(result.p_value, 1.0);
+    }
+
+    #[test]
+    fn mann_whitney_exact() {
+        // R, ?wilcox.test: W = 35, p-value = 0.1272 one-sided, twice that two-sided
+        let x = [0.80, 0.83, 1.89, 1.04, 1.45, 1.38, 1.91, 1.64, 0.73, 1.46];
+        let y = [1.15, 0.88, 0.90, 0.74, 1.21];
+        let result = mann_whitney(&x, &y);
+        assert!(!result.approximate);
+        assert_eq!(result.statistic, 35.0);
+        assert_close(result.p_value, 2.0 * 0.1272061272061272, 1e-12);
+        assert_eq!(mann_whitney(&y, &x).statistic, 15.0);
+    }
+
+    #[test]
+    fn mann_whitney_small_exact() {
+        // complete separation of 3 and 3: 2 of the C(6, 3) = 20 orderings are as extreme
+        let result = mann_whitney(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]);
+        assert_eq!(result.statistic, 0.0);
+        assert_close(result.p_value, 0.1, 1e-12);
+    }
+
+    #[test]
+    fn mann_whitney_ties() {
+        // R wilcox.test(exact = FALSE): W = 6.5, p-value = 0.07505
+        let a = [1.0, 2.0, 2.0, 3.0, 4.0, 5.0];
+        let b = [3.0, 3.0, 4.0, 6.0, 7.0, 8.0];
+        let result = mann_whitney(&a, &b);
+        assert!(result.approximate);
+        assert_eq!(result.statistic, 6.5);
+        assert_close(result.p_value, 0.0750461955620098, 1e-6);
+    }
+
+    #[test]
+    fn friedman_and_nemenyi() {
+        // ranks of 4 classifiers on 14 data sets with the average ranks of the example of
+        // Demšar (2006): 3.143, 2.000, 2.893, 1.964, χ²F = 9.28 and CD = 1.25 at α = 0.05
+        let blocks: Vec<Vec<f64>> = [
+            [4.0, 3.0, 2.0, 1.0],
+            [1.0, 2.0, 3.0, 4.0],
+            [4.0, 1.0, 2.0, 3.0],
+            [4.0, 1.0, 3.0, 2.0],
+            [4.0, 2.0, 3.0, 1.0],
+            [1.0, 2.5, 4.0, 2.5],
+            [3.0, 2.0, 4.0, 1.0],
+            [2.5, 2.5, 4.0, 1.0],
+            [4.0, 3.0, 1.5, 1.5],
+            [2.5, 2.5, 2.5, 2.5],
+            [4.0, 2.5, 1.0, 2.5],
+            [3.0, 2.0, 4.0, 1.0],
+            [4.0, 1.0, 2.5, 2.5],
+            [3.0, 1.0, 4.0, 2.0],
+        ]
+        .iter()
+        .map(|block| block.to_vec())
+        .collect();
+        let result = friedman(&blocks);
+        let expected = [3.143, 2.000, 2.893, 1.964];
+        for (rank, expected) in result.average_ranks.iter().zip(expected) {
+            assert_close(*rank, expected, 5e-4);
+        }
+        assert_eq!(result.degrees_of_freedom, 3);
+        assert_close(result.chi_square, 9.28, 5e-3);
+        assert_close(
+            result.p_value,
+            chi_square_survival(result.chi_square, 3.0),
+            1e-12,
+        );
+        assert!(result.p_value < 0.05);
+        assert_close(
+            nemenyi_critical_difference(0.05, 4, 14).unwrap(),
+            1.25,
+            5e-3,
+        );
+        assert_eq!(nemenyi_critical_difference(0.01, 4, 14), None);
+        assert_eq!(nemenyi_critical_difference(0.05, 11, 14), None);
+    }
+
+    #[test]
+    fn chi_square_critical_values() {
+        // χ² tables: the 0.05 and 0.01 critical values
+        assert_close(chi_square_survival(3.841459, 1.0), 0.05, 1e-6);
+        assert_close(chi_square_survival(7.814728, 3.0), 0.05, 1e-6);
+        assert_close(chi_square_survival(11.344867, 3.0), 0.01, 1e-6);
+        assert_close(chi_square_survival(18.307038, 10.0), 0.05, 1e-6);
+        // df = 2 is exp(-x / 2)
+        assert_close(chi_square_survival(6.5, 2.0), (-3.25f64).exp(), 1e-12);
+        assert_eq!(chi_square_survival(0.0, 3.0), 1.0);
+    }
+
+    #[test]
+    fn ln_gamma_known_values() {
+        assert_close(ln_gamma(1.0), 0.0, 1e-13);
+        assert_close(ln_gamma(5.0), 24f64.ln(), 1e-13);
+        assert_close(ln_gamma(10.0), 362880f64.ln(), 1e-12);
+        assert_close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-13);
+        // reflection below 0.5: Γ(1/4) = 3.625609908221908
+        assert_close(ln_gamma(0.25), 3.625_609_908_221_908_f64.ln(), 1e-13);
+    }
+
+    #[test]
+    fn normal_cdf_known_values() {
+        // erfc is good to 1.2e-7
+        assert_close(normal_cdf(0.0), 0.5, 1e-7);
+        assert_close(normal_cdf(1.959963984540054), 0.975, 1e-7);
+        assert_close(normal_cdf(-1.0), 0.15865525393145707, 1e-7);
+        assert_close(normal_cdf(-3.0), 0.0013498980316301, 1e-8);
+    }
+
+    #[test]
+    fn median_and_standard_deviation() {
+        assert_eq!(median(&[3.0, 1.0, 2.0]), 2.0);
+        assert_eq!(median(&[4.0, 1.0, 2.0, 3.0]), 2.5);
+        assert_close(
+            standard_deviation(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]),
+            (32.0f64 / 7.0).sqrt(),
+            1e-12,
+        );
+        assert_eq!(standard_deviation(&[1.0]), 0.0);
+    }
+}